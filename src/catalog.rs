@@ -0,0 +1,470 @@
+use crate::row::{Row, Value};
+use crate::schema::Schema;
+use crate::storage::{BPlusTree, Pager, PagerError};
+use parking_lot::{RwLock, RwLockReadGuard};
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::Arc;
+
+const MAX_TABLE_NAME_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogEntry {
+    pub name: String,
+    pub root_page_id: usize,
+    pub sql: String,
+    pub schema: Schema,
+}
+
+impl CatalogEntry {
+    /// Parse an entry of the catalog rooted at the given page, which is
+    /// reported as corrupted if the entry can't be parsed.
+    fn from_row(row: &Row, catalog_root_page_id: usize) -> Result<Self, PagerError> {
+        let corrupted = || PagerError::CorruptedPage(catalog_root_page_id);
+        let [name, sql] = row.values.as_slice() else {
+            return Err(corrupted());
+        };
+        let (name, sql) = (name.to_string(), sql.to_string());
+
+        // Similar to SQLite, we only persist the statement used to create the
+        // table and parse the schema from it every time the database is opened.
+        let definition = sql
+            .split_once('(')
+            .and_then(|(_, definition)| definition.strip_suffix(')'))
+            .ok_or_else(corrupted)?;
+        let schema = definition.parse().map_err(|_| corrupted())?;
+
+        Ok(Self {
+            name,
+            root_page_id: row.id as usize,
+            sql,
+            schema,
+        })
+    }
+
+    fn to_row(&self) -> Row {
+        Row::new(
+            self.root_page_id as u64,
+            vec![
+                Value::Text(self.name.clone()),
+                Value::Text(self.sql.clone()),
+            ],
+        )
+    }
+}
+
+// TRADEOFF: Our catalog is stored as a regular B+ tree using our Row format,
+// with the schema (root_page_id integer, name text, sql text) where:
+//
+//   - root_page_id is the root page id of the table
+//   - name is the table name
+//   - sql is the statement used to create the table
+//
+// Since our B+ tree is keyed by id, we can't search the catalog by table name
+// efficiently. Hence, similar to SQLite, we load every entries into memory when
+// the database is opened and only use the B+ tree to persist the changes.
+//
+// This is fine as the number of tables is usually small.
+pub struct Catalog {
+    pager: Arc<Pager>,
+    root_page_id: usize,
+    entries: RwLock<HashMap<String, CatalogEntry>>,
+    // Shared by every operation on our tables, and held exclusively by our
+    // vacuum, see Catalog::vacuum.
+    vacuum_lock: RwLock<()>,
+}
+
+/// The tree of a table, which our vacuum doesn't move while it's in use,
+/// see Catalog::table_tree.
+pub struct TableTree<'a> {
+    tree: BPlusTree<'a, u64, Row>,
+    _vacuum: RwLockReadGuard<'a, ()>,
+}
+
+impl<'a> Deref for TableTree<'a> {
+    type Target = BPlusTree<'a, u64, Row>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl Catalog {
+    pub fn new(pager: Arc<Pager>) -> Result<Self, PagerError> {
+        let root_page_id = pager.catalog_root_page_id();
+
+        let mut entries = HashMap::new();
+        for row in BPlusTree::<u64, Row>::new(&pager, root_page_id).values()? {
+            let entry = CatalogEntry::from_row(&row, root_page_id)?;
+            entries.insert(entry.name.clone(), entry);
+        }
+
+        Ok(Self {
+            pager,
+            root_page_id,
+            entries: RwLock::new(entries),
+            vacuum_lock: RwLock::new(()),
+        })
+    }
+
+    /// Write every pages of our database to disk, see Pager::flush_all_pages.
+    pub fn flush(&self) -> Result<(), PagerError> {
+        self.pager.flush_all_pages()
+    }
+
+    fn tree(&self, root_page_id: usize) -> BPlusTree<'_, u64, Row> {
+        BPlusTree::new(&self.pager, root_page_id)
+    }
+
+    pub fn get(&self, name: &str) -> Option<CatalogEntry> {
+        self.entries.read().get(name).cloned()
+    }
+
+    /// The tree of the given table, looked up every time it's used, as our
+    /// vacuum moves the root of every tables.
+    pub fn table_tree(&self, name: &str) -> Option<TableTree<'_>> {
+        let vacuum = self.vacuum_lock.read();
+        let root_page_id = self.entries.read().get(name)?.root_page_id;
+
+        Some(TableTree {
+            tree: self.tree(root_page_id),
+            _vacuum: vacuum,
+        })
+    }
+
+    pub fn table_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.entries.read().keys().cloned().collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn create_table(&self, name: &str, schema: &Schema) -> Result<CatalogEntry, String> {
+        validate_table_name(name)?;
+
+        let _vacuum = self.vacuum_lock.read();
+        let mut entries = self.entries.write();
+        if entries.contains_key(name) {
+            return Err(format!("table {name} already exists"));
+        }
+
+        let sql = format!("create table {name} ({schema})");
+        let row = Row::new(0, vec![Value::Text(name.to_string()), Value::Text(sql)]);
+        let table = BPlusTree::<u64, Row>::create(&self.pager).map_err(|err| err.to_string())?;
+        let row = Row::new(table.root_page_id() as u64, row.values);
+        if let Err(err) = self.tree(self.root_page_id).insert(row.id, &row) {
+            // Otherwise, the root page of our table is never freed.
+            table.destroy().map_err(|err| err.to_string())?;
+            return Err(err);
+        }
+
+        let entry =
+            CatalogEntry::from_row(&row, self.root_page_id).map_err(|err| err.to_string())?;
+        entries.insert(entry.name.clone(), entry.clone());
+
+        Ok(entry)
+    }
+
+    pub fn drop_table(&self, name: &str) -> Result<CatalogEntry, String> {
+        let _vacuum = self.vacuum_lock.read();
+        let mut entries = self.entries.write();
+        let root_page_id = entries
+            .get(name)
+            .ok_or_else(|| format!("no such table: {name}"))?
+            .root_page_id;
+
+        // Our entry is only removed once it's deleted from our tree, so a
+        // failed delete leaves the table both in memory and on disk.
+        self.tree(self.root_page_id)
+            .delete(root_page_id as u64)
+            .map_err(|err| err.to_string())?;
+        let entry = entries.remove(name).unwrap();
+        self.tree(entry.root_page_id)
+            .destroy()
+            .map_err(|err| err.to_string())?;
+
+        Ok(entry)
+    }
+
+    // TRADEOFF: Similar to SQLite, our vacuum builds a fresh copy of the
+    // database into another file, which is then renamed over our file.
+    //
+    // A crash in the middle of a vacuum leaves our database untouched, at the
+    // cost of twice the disk space until the copy replaces it. Our trees are
+    // streamed into the copy a leaf node at a time, so the rows of our
+    // database never have to fit into memory.
+    /// Rebuild the catalog and every tables into a fresh contiguous set of
+    /// pages, dropping the deleted rows, see BPlusTree::bulk_load.
+    ///
+    /// The root page ids of the tables change, which our tables look up
+    /// again through Catalog::table_tree, so we wait for their operations to
+    /// complete. Every page in our buffer pool is discarded, so we fail if
+    /// any of them is still pinned. The caller must ensure that no
+    /// transaction is active, as the rows they marked as deleted are dropped.
+    pub fn vacuum(&self) -> Result<(), PagerError> {
+        let _vacuum = self.vacuum_lock.write();
+        // Otherwise, pages of our old file could be read ahead into our
+        // buffer pool while it's replaced.
+        let _read_ahead = self.pager.pause_read_ahead();
+        let mut entries = self.entries.write();
+        let mut names: Vec<String> = entries.keys().cloned().collect();
+        names.sort_unstable();
+
+        let vacuumed = self.pager.create_vacuum_database()?;
+        match self.vacuum_into(&vacuumed, &entries, &names) {
+            Ok(vacuumed_entries) => {
+                self.pager.replace_with(vacuumed)?;
+                *entries = vacuumed_entries;
+                Ok(())
+            }
+            Err(err) => {
+                // Our entries and our file are only changed once every tree
+                // is copied, so a corrupted page leaves both untouched.
+                let _ = vacuumed.discard();
+                Err(err)
+            }
+        }
+    }
+
+    /// Copy every tables and the catalog into the vacuumed database, and
+    /// return our entries with their new root page ids.
+    fn vacuum_into(
+        &self,
+        vacuumed: &Pager,
+        entries: &HashMap<String, CatalogEntry>,
+        names: &[String],
+    ) -> Result<HashMap<String, CatalogEntry>, PagerError> {
+        // The root of the catalog is kept, followed by the root of every
+        // tables in the order of their names, so the new root page ids are
+        // known before any tree is written.
+        let root_page_id = vacuumed.catalog_root_page_id();
+        vacuumed.reset_page_allocation(names.len() + 1);
+
+        let mut vacuumed_entries = HashMap::new();
+        for (i, name) in names.iter().enumerate() {
+            let mut entry = entries[name].clone();
+            let table_root_page_id = root_page_id + i + 1;
+            BPlusTree::<u64, Row>::new(vacuumed, table_root_page_id)
+                .bulk_load(self.tree(entry.root_page_id).entries())?;
+
+            entry.root_page_id = table_root_page_id;
+            vacuumed_entries.insert(entry.name.clone(), entry);
+        }
+
+        let rows = names.iter().map(|name| {
+            Ok((
+                vacuumed_entries[name].root_page_id as u64,
+                vacuumed_entries[name].to_row(),
+            ))
+        });
+        BPlusTree::<u64, Row>::new(vacuumed, root_page_id).bulk_load(rows)?;
+
+        Ok(vacuumed_entries)
+    }
+}
+
+fn validate_table_name(name: &str) -> Result<(), String> {
+    if name.len() > MAX_TABLE_NAME_LENGTH {
+        return Err("Table name is too long.".to_string());
+    }
+
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid table name '{name}'"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::PagerOptions;
+    use std::time::Duration;
+
+    #[test]
+    fn create_and_drop_table() {
+        let pager = setup_test_pager();
+        let catalog = Catalog::new(pager.clone()).unwrap();
+        assert!(catalog.table_names().is_empty());
+
+        let schema = test_schema();
+        let users = catalog.create_table("users", &schema).unwrap();
+        let posts = catalog.create_table("posts", &schema).unwrap();
+        assert_ne!(users.root_page_id, posts.root_page_id);
+        assert_eq!(catalog.table_names(), vec!["posts", "users"]);
+        assert_eq!(catalog.get("users"), Some(users.clone()));
+        assert_eq!(users.schema, schema);
+        assert_eq!(
+            users.sql,
+            "create table users (id integer not null, name text(32), score real)"
+        );
+
+        let result = catalog.create_table("users", &schema);
+        assert_eq!(result, Err("table users already exists".to_string()));
+
+        assert_eq!(catalog.drop_table("posts").unwrap(), posts);
+        assert_eq!(catalog.get("posts"), None);
+        assert_eq!(catalog.table_names(), vec!["users"]);
+
+        let result = catalog.drop_table("posts");
+        assert_eq!(result, Err("no such table: posts".to_string()));
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn free_root_page_when_catalog_insert_fails() {
+        let pager = setup_test_pager();
+        let catalog = Catalog::new(pager.clone()).unwrap();
+
+        // The root page of our next table is already taken by a stale entry.
+        let root_page_id = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        BPlusTree::<u64, Row>::new(&pager, root_page_id)
+            .destroy()
+            .unwrap();
+        let stale = Row::new(root_page_id as u64, vec![]);
+        assert!(catalog
+            .tree(catalog.root_page_id)
+            .insert(stale.id, &stale)
+            .is_ok());
+
+        let result = catalog.create_table("users", &test_schema());
+        assert_eq!(result, Err("duplicate key".to_string()));
+        assert_eq!(catalog.get("users"), None);
+        let reused = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        assert_eq!(reused, root_page_id);
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn load_entries_from_disk() {
+        let pager = setup_test_pager();
+        let catalog = Catalog::new(pager.clone()).unwrap();
+        let users = catalog.create_table("users", &test_schema()).unwrap();
+        catalog.create_table("posts", &test_schema()).unwrap();
+        catalog.drop_table("posts").unwrap();
+        pager.flush_all_pages().unwrap();
+
+        let catalog = Catalog::new(setup_test_pager()).unwrap();
+        assert_eq!(catalog.table_names(), vec!["users"]);
+        assert_eq!(catalog.get("users"), Some(users));
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn error_when_every_frames_are_pinned() {
+        cleanup_test_db_file();
+        let options = PagerOptions {
+            buffer_pool_instances: Some(1),
+            frame_wait_timeout: Some(Duration::from_millis(10)),
+            background_writer: None,
+            ..PagerOptions::default()
+        };
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let pager = Arc::new(Pager::open_with_options(&file, 2, options).unwrap());
+        let catalog = Catalog::new(pager.clone()).unwrap();
+        let users = catalog.create_table("users", &test_schema()).unwrap();
+
+        let catalog_root = pager.fetch_read_page_guard(catalog.root_page_id).unwrap();
+        let users_root = pager.fetch_read_page_guard(users.root_page_id).unwrap();
+        let result = catalog.create_table("posts", &test_schema());
+        assert_eq!(result, Err(PagerError::NoFreePageAvailable.to_string()));
+        assert_eq!(catalog.get("posts"), None);
+
+        drop(catalog_root);
+        drop(users_root);
+        assert!(catalog.create_table("posts", &test_schema()).is_ok());
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn error_when_catalog_entry_is_corrupted() {
+        let pager = setup_test_pager();
+        let catalog = Catalog::new(pager.clone()).unwrap();
+        catalog.create_table("users", &test_schema()).unwrap();
+        let corrupted = Row::new(
+            42,
+            vec![
+                Value::Text("posts".to_string()),
+                Value::Text("create table posts".to_string()),
+            ],
+        );
+        assert!(catalog
+            .tree(catalog.root_page_id)
+            .insert(corrupted.id, &corrupted)
+            .is_ok());
+        pager.flush_all_pages().unwrap();
+
+        assert!(matches!(
+            Catalog::new(setup_test_pager()),
+            Err(PagerError::CorruptedPage(id)) if id == catalog.root_page_id
+        ));
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn error_when_table_name_is_invalid() {
+        let pager = setup_test_pager();
+        let catalog = Catalog::new(pager).unwrap();
+
+        let schema = test_schema();
+        let result = catalog.create_table("1users", &schema);
+        assert_eq!(result, Err("invalid table name '1users'".to_string()));
+
+        let result = catalog.create_table("user's", &schema);
+        assert_eq!(result, Err("invalid table name 'user's'".to_string()));
+
+        let result = catalog.create_table(&"a".repeat(33), &schema);
+        assert_eq!(result, Err("Table name is too long.".to_string()));
+        assert!(catalog.table_names().is_empty());
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn load_entries_with_long_definition_from_disk() {
+        let pager = setup_test_pager();
+        let catalog = Catalog::new(pager.clone()).unwrap();
+
+        // The definition doesn't fit in a leaf cell and is stored in overflow pages.
+        let columns: Vec<String> = (0..500).map(|i| format!("column{i} text")).collect();
+        let schema = format!("id integer, {}", columns.join(", "))
+            .parse()
+            .unwrap();
+        let users = catalog.create_table("users", &schema).unwrap();
+        pager.flush_all_pages().unwrap();
+
+        let catalog = Catalog::new(setup_test_pager()).unwrap();
+        assert_eq!(catalog.get("users"), Some(users));
+
+        cleanup_test_db_file();
+    }
+
+    fn test_schema() -> Schema {
+        "id integer, name text(32), score real".parse().unwrap()
+    }
+
+    fn setup_test_pager() -> Arc<Pager> {
+        Arc::new(Pager::new(
+            format!("test-{:?}.db", std::thread::current().id()),
+            8,
+        ))
+    }
+
+    fn cleanup_test_db_file() {
+        let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+    }
+}
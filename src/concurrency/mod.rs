@@ -6,7 +6,7 @@ mod transaction_manager;
 pub use {
     lock_manager::LockManager,
     table::{RowID, Table, TableIntoIter},
-    transaction::{IsolationLevel, Transaction},
+    transaction::{IsolationLevel, Transaction, TransactionState},
     transaction_manager::TransactionManager,
 };
 
@@ -52,13 +52,13 @@ mod test {
                 let result = execution_engine.execute(index_scan_plan_node.clone());
                 let (_rid, row) = &result[0];
                 assert_eq!(row.id, 5);
-                assert_eq!(row.username(), "user5");
+                assert_eq!(row.values[0].to_string(), "user5");
 
                 // Make sure that T2 finish it's read write first before we attempt to read again.
                 std::thread::sleep(std::time::Duration::from_millis(15));
                 let (_, row) = &execution_engine.execute(index_scan_plan_node)[0];
                 assert_eq!(row.id, 5);
-                assert_eq!(row.username(), "user5");
+                assert_eq!(row.values[0].to_string(), "user5");
 
                 let mut t1 = t1.write();
                 tm.commit(&tb, &mut t1);
@@ -76,7 +76,7 @@ mod test {
                 let update_plan_node = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["username".to_string()],
-                    new_row: Row::new("0", "new_name", "").unwrap(),
+                    new_row: Row::from_str("0 new_name ").unwrap(),
                 });
 
                 // Make sure that T2 start later than T1..
@@ -125,20 +125,20 @@ mod test {
                 let update_plan_node = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["username".to_string()],
-                    new_row: Row::new("0", "new_name", "").unwrap(),
+                    new_row: Row::from_str("0 new_name ").unwrap(),
                 });
 
                 let result = execution_engine.execute(index_scan_plan_node.clone());
                 let (_rid, row) = &result[0];
                 assert_eq!(row.id, 5);
-                assert_eq!(row.username(), "user5");
+                assert_eq!(row.values[0].to_string(), "user5");
 
                 execution_engine.execute(update_plan_node);
 
                 let result = execution_engine.execute(index_scan_plan_node);
                 let (_rid, row) = &result[0];
                 assert_eq!(row.id, 5);
-                assert_eq!(row.username(), "new_name");
+                assert_eq!(row.values[0].to_string(), "new_name");
 
                 // Make sure that T2 finish it's transaction before we abort
                 std::thread::sleep(std::time::Duration::from_millis(20));
@@ -168,7 +168,7 @@ mod test {
                 let result = execution_engine.execute(index_scan_plan_node);
                 let (_rid, row) = &result[0];
                 assert_eq!(row.id, 5);
-                assert_eq!(row.username(), "user5");
+                assert_eq!(row.values[0].to_string(), "user5");
 
                 let mut t2 = t2.write();
                 tm.commit(&tb, &mut t2);
@@ -216,12 +216,12 @@ mod test {
                 let update_plan_node_a = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["username".to_string()],
-                    new_row: Row::new("0", "t1_name", "").unwrap(),
+                    new_row: Row::from_str("0 t1_name ").unwrap(),
                 });
                 let update_plan_node_b = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["email".to_string()],
-                    new_row: Row::new("0", "", "t1_email").unwrap(),
+                    new_row: Row::from_str("0  t1_email").unwrap(),
                 });
 
                 execution_engine.execute(update_plan_node_a);
@@ -232,8 +232,8 @@ mod test {
 
                 let result = execution_engine.execute(index_scan_plan_node);
                 let (_, row) = &result[0];
-                assert_eq!(row.username(), "t1_name");
-                assert_eq!(row.email(), "t1_email");
+                assert_eq!(row.values[0].to_string(), "t1_name");
+                assert_eq!(row.values[1].to_string(), "t1_email");
 
                 let mut t1 = t1.write();
                 tm.abort(&tb, &mut t1);
@@ -251,12 +251,12 @@ mod test {
                 let update_plan_node_a = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["username".to_string()],
-                    new_row: Row::new("0", "t2_name", "").unwrap(),
+                    new_row: Row::from_str("0 t2_name ").unwrap(),
                 });
                 let update_plan_node_b = PlanNode::Update(UpdatePlanNode {
                     child: Box::new(index_scan_plan_node.clone()),
                     columns: vec!["email".to_string()],
-                    new_row: Row::new("0", "", "t2_email").unwrap(),
+                    new_row: Row::from_str("0  t2_email").unwrap(),
                 });
 
                 // Make sure that T1 start first before continue:
@@ -265,8 +265,8 @@ mod test {
                 execution_engine.execute(update_plan_node_b);
                 let result = execution_engine.execute(index_scan_plan_node);
                 let (_, row) = &result[0];
-                assert_eq!(row.username(), "t2_name");
-                assert_eq!(row.email(), "t2_email");
+                assert_eq!(row.values[0].to_string(), "t2_name");
+                assert_eq!(row.values[1].to_string(), "t2_email");
 
                 let mut t2 = t2.write();
                 tm.commit(&tb, &mut t2);
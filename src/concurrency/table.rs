@@ -2,9 +2,12 @@ use super::{
     lock_manager::LockManager,
     transaction::{Transaction, WriteRecord, WriteRecordType},
 };
-use crate::storage::{Node, NodeType, Pager};
-use crate::{row::Row, storage::Page};
-use parking_lot::{RwLockUpgradableReadGuard, RwLockWriteGuard};
+use crate::catalog::{Catalog, CatalogEntry, TableTree};
+use crate::database::{default_table_schema, Database, DEFAULT_TABLE_NAME};
+use crate::row::Row;
+use crate::schema::Schema;
+use crate::storage::{Node, NodeType, Pager, PagerError};
+use parking_lot::RwLockWriteGuard;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::sync::Arc;
@@ -22,7 +25,12 @@ impl RowID {
 }
 
 pub struct Table {
+    // Our root is looked up by name on every operation, as it's moved by
+    // our vacuum, see Catalog::table_tree.
+    name: String,
+    catalog: Arc<Catalog>,
     pager: Arc<Pager>,
+    schema: Schema,
     lock_manager: Arc<LockManager>,
 }
 
@@ -34,93 +42,119 @@ pub struct TableIntoIter {
 }
 
 impl Iterator for TableIntoIter {
-    type Item = (RowID, Row);
+    type Item = Result<(RowID, Row), PagerError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.node.clone().and_then(|node| {
-            let rid = RowID::new(self.page_id, self.slot_num);
-            let item = node.get_row(self.slot_num);
-            let item = item.as_ref()?.to_owned();
+        let node = self.node.as_ref()?;
 
-            self.slot_num += 1;
-
-            if self.slot_num == node.num_of_cells as usize && node.next_leaf_offset == 0 {
+        // Move to the next leaf node once every rows of this one are read.
+        if self.slot_num >= node.num_of_cells as usize {
+            let next_leaf_offset = node.next_leaf_offset as usize;
+            if next_leaf_offset == 0 {
                 self.node = None;
-            } else if self.slot_num >= node.num_of_cells as usize {
-                let page = self
-                    .pager
-                    .fetch_read_page_with_retry(node.next_leaf_offset as usize);
-                self.page_id = page.page_id.unwrap();
-                self.node = page.node.clone();
-                self.pager.unpin_page_with_read_guard(page, false);
-                self.slot_num = 0;
+                return None;
             }
 
-            Some((rid, item))
-        })
+            let page = match self.pager.fetch_scan_page_with_retry(next_leaf_offset) {
+                Ok(page) => page,
+                Err(err) => {
+                    self.node = None;
+                    return Some(Err(err));
+                }
+            };
+            self.page_id = page.page_id.unwrap();
+            self.node = page.node.clone();
+            drop(page);
+            self.slot_num = 0;
+
+            return self.next();
+        }
+
+        let rid = RowID::new(self.page_id, self.slot_num);
+        let item = node.get_row(self.slot_num)?;
+        self.slot_num += 1;
+
+        Some(Ok((rid, item)))
     }
 }
 
 impl Table {
+    /// Open the default table of the database at the given path,
+    /// creating it if it doesn't exist yet.
     pub fn new(path: impl AsRef<Path>, pool_size: usize, lock_manager: Arc<LockManager>) -> Table {
-        let pager = Pager::new(path, pool_size);
+        let database = Database::new(path, pool_size);
+        database
+            .table_or_create(DEFAULT_TABLE_NAME, &default_table_schema())
+            .unwrap();
+        database
+            .transactional_table(DEFAULT_TABLE_NAME, lock_manager)
+            .unwrap()
+    }
+
+    pub fn from_catalog(
+        pager: Arc<Pager>,
+        catalog: Arc<Catalog>,
+        entry: CatalogEntry,
+        lock_manager: Arc<LockManager>,
+    ) -> Table {
         Table {
-            pager: Arc::new(pager),
+            name: entry.name,
+            pager,
+            catalog,
+            schema: entry.schema,
             lock_manager,
         }
     }
 
     pub fn get_row_id(
         &self,
-        key: u32,
+        key: u64,
         transaction: &mut RwLockWriteGuard<Transaction>,
     ) -> Option<RowID> {
-        self.pager
-            .search(0, key)
-            .map(|(page_id, slot_num)| RowID::new(page_id, slot_num))
+        match self.tree().map(|tree| tree.search(key)) {
+            Some(Ok(cursor)) => Some(RowID::new(cursor.page_num, cursor.cell_num)),
+            _ => {
+                transaction.set_state(super::transaction::TransactionState::Aborted);
+                None
+            }
+        }
+    }
+
+    /// Our tree, or None once our table is dropped.
+    fn tree(&self) -> Option<TableTree<'_>> {
+        self.catalog.table_tree(&self.name)
     }
 
-    pub fn iter(&self) -> TableIntoIter {
+    pub fn iter(&self) -> Result<TableIntoIter, PagerError> {
+        let Some(tree) = self.tree() else {
+            return Ok(TableIntoIter {
+                pager: self.pager.clone(),
+                node: None,
+                page_id: 0,
+                slot_num: 0,
+            });
+        };
+
         // Search for the first leaf node
-        let page = self.search_page(0, 0);
+        let page = tree.search_page(None)?;
         let page_id = page.page_id.unwrap();
         let node = page.node.clone().unwrap();
-        self.pager.unpin_page_with_read_guard(page, false);
+        drop(page);
         assert_eq!(node.node_type, NodeType::Leaf);
 
-        TableIntoIter {
+        Ok(TableIntoIter {
             pager: self.pager.clone(),
             node: Some(node),
             page_id,
             slot_num: 0,
-        }
-    }
-
-    fn search_page(&self, page_num: usize, key: u32) -> RwLockUpgradableReadGuard<Page> {
-        match self.pager.fetch_read_page_guard(page_num) {
-            Err(_) => {
-                let duration = std::time::Duration::from_millis(1000);
-                std::thread::sleep(duration);
-
-                self.search_page(page_num, key)
-            }
-            Ok(page) => {
-                let node = page.node.as_ref().unwrap();
-
-                if node.node_type == NodeType::Leaf {
-                    return page;
-                }
-
-                let next_page_num = node.search(key).unwrap();
-                self.pager.unpin_page_with_read_guard(page, false);
-                self.search_page(next_page_num, key)
-            }
-        }
+        })
     }
 
     pub fn get(&self, rid: RowID, transaction: &mut RwLockWriteGuard<Transaction>) -> Option<Row> {
         if let Ok(page) = self.pager.fetch_read_page_guard(rid.page_id) {
-            page.get_row(rid.slot_num)
+            let row = page.get_row(rid.slot_num);
+            drop(page);
+            row
         } else {
             transaction.set_state(super::transaction::TransactionState::Aborted);
             None
@@ -132,7 +166,10 @@ impl Table {
         row: &Row,
         transaction: &mut RwLockWriteGuard<Transaction>,
     ) -> Option<RowID> {
-        if let Ok((page_id, slot_num)) = self.pager.insert_row(0, row) {
+        let row = self.schema.coerce(row).ok()?;
+
+        let inserted = self.tree().map(|tree| tree.insert(row.id, &row));
+        if let Some(Ok((page_id, slot_num))) = inserted {
             // The RID probably need to be added to the row
             // as well? It's currently unused by row/tuple.
             let rid = RowID { page_id, slot_num };
@@ -143,14 +180,18 @@ impl Table {
         }
     }
 
-    pub fn apply_delete(&self, key: u32) {
-        self.pager.delete_by_key(0, key);
+    pub fn apply_delete(&self, key: u64) {
+        // The row is already marked as deleted, so the transaction commits
+        // even if its cell is only removed by a later vacuum.
+        if let Some(tree) = self.tree() {
+            let _ = tree.delete(key);
+        }
     }
 
     pub fn rollback_delete(&self, rid: &RowID) {
         let mut page = self.pager.fetch_write_page_guard(rid.page_id).unwrap();
         page.mark_row_as_undeleted(rid.slot_num);
-        self.pager.unpin_page_with_write_guard(page, true);
+        drop(page);
     }
 
     pub fn delete(
@@ -161,7 +202,7 @@ impl Table {
     ) -> bool {
         if let Ok(mut page) = self.pager.fetch_write_page_guard(rid.page_id) {
             page.mark_row_as_deleted(rid.slot_num);
-            self.pager.unpin_page_with_write_guard(page, true);
+            drop(page);
 
             transaction.push_write_set(WriteRecord::new(WriteRecordType::Delete, *rid, row.id));
             true
@@ -174,10 +215,17 @@ impl Table {
         &self,
         row: &Row,
         new_row: &Row,
-        columns: &Vec<String>,
+        columns: &[String],
         rid: &RowID,
         transaction: &mut RwLockWriteGuard<Transaction>,
     ) -> bool {
+        let Ok(new_row) = self.schema.coerce(new_row) else {
+            return false;
+        };
+        let Ok(indexes) = self.schema.value_indexes(columns) else {
+            return false;
+        };
+
         // Make sure we have access to a lock first before we acquire the write page
         // from our pager.
         if transaction.is_shared_lock(rid) {
@@ -185,12 +233,16 @@ impl Table {
         }
 
         if let Ok(mut page) = self.pager.fetch_write_page_guard(rid.page_id) {
-            assert!(page.update_row(rid.slot_num, new_row, columns));
-            self.pager.unpin_page_with_write_guard(page, true);
+            if !page.update_row(rid.slot_num, &new_row, &indexes) {
+                drop(page);
+                return false;
+            }
+
+            drop(page);
 
             let mut write_record = WriteRecord::new(WriteRecordType::Update, *rid, row.id);
             write_record.old_row = Some(row.clone());
-            write_record.columns = columns.clone();
+            write_record.columns = columns.to_vec();
             transaction.push_write_set(write_record);
 
             true
@@ -199,10 +251,12 @@ impl Table {
         }
     }
 
-    pub fn rollback_update(&self, rid: &RowID, row: &Row, columns: &Vec<String>) {
+    pub fn rollback_update(&self, rid: &RowID, row: &Row, columns: &[String]) {
+        let indexes = self.schema.value_indexes(columns).unwrap();
+
         if let Ok(mut page) = self.pager.fetch_write_page_guard(rid.page_id) {
-            page.update_row(rid.slot_num, row, columns);
-            self.pager.unpin_page_with_write_guard(page, true);
+            page.update_row(rid.slot_num, row, &indexes);
+            drop(page);
         }
     }
 }
@@ -220,7 +274,8 @@ mod test {
         let table = setup_table(&tm, lock_manager.clone());
 
         let mut rid = 1;
-        for (_, row) in table.iter() {
+        for item in table.iter().unwrap() {
+            let (_, row) = item.unwrap();
             assert_eq!(row.id, rid);
             rid += 1;
         }
@@ -228,8 +283,9 @@ mod test {
         // Verify it can be iterate multiple times
         // without table being consumed.
         rid = 1;
-        for (_, row) in table.iter() {
-            assert_eq!(row.username(), format!("user{rid}"));
+        for item in table.iter().unwrap() {
+            let (_, row) = item.unwrap();
+            assert_eq!(row.values[0].to_string(), format!("user{rid}"));
             rid += 1;
         }
 
@@ -245,15 +301,15 @@ mod test {
         let transaction = tm.begin(IsolationLevel::ReadCommited);
         let mut t = transaction.write();
         let rid = table.get_row_id(1, &mut t).unwrap();
-        let row = Row::new("1", "user1", "user1@email.com").unwrap();
-        let new_row = Row::new("1", "john", "john@email.com").unwrap();
+        let row = Row::from_str("1 user1 user1@email.com").unwrap();
+        let new_row = Row::from_str("1 john john@email.com").unwrap();
         let columns = vec!["username".to_string(), "email".to_string()];
         assert!(table.update(&row, &new_row, &columns, &rid, &mut t));
 
         let row = table.get(rid, &mut t).unwrap();
         assert_eq!(row.id, 1);
-        assert_eq!(row.username(), "john");
-        assert_eq!(row.email(), "john@email.com");
+        assert_eq!(row.values[0].to_string(), "john");
+        assert_eq!(row.values[1].to_string(), "john@email.com");
         tm.commit(&table, &mut t);
 
         cleanup_table();
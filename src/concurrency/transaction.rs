@@ -12,14 +12,14 @@ pub enum WriteRecordType {
 #[derive(Debug)]
 pub struct WriteRecord {
     pub rid: RowID,
-    pub key: u32,
+    pub key: u64,
     pub wr_type: WriteRecordType,
     pub old_row: Option<Row>,
     pub columns: Vec<String>,
 }
 
 impl WriteRecord {
-    pub fn new(wr_type: WriteRecordType, rid: RowID, key: u32) -> Self {
+    pub fn new(wr_type: WriteRecordType, rid: RowID, key: u64) -> Self {
         Self {
             wr_type,
             rid,
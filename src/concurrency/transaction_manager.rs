@@ -98,6 +98,25 @@ impl TransactionManager {
         }
     }
 
+    /// Run f unless a transaction is active, while no transaction can begin,
+    /// see Database::vacuum.
+    ///
+    /// A transaction latched by another thread is still being worked on, so
+    /// it's active whatever its state.
+    pub fn without_active_transactions<T>(&self, f: impl FnOnce() -> T) -> Option<T> {
+        let map = self.transaction_map.write();
+        let is_active = map.values().any(|transaction| {
+            transaction.try_read().is_none_or(|t| {
+                t.state != TransactionState::Aborted && t.state != TransactionState::Committed
+            })
+        });
+        if is_active {
+            return None;
+        }
+
+        Some(f())
+    }
+
     fn get_transaction(&self, txn_id: &u32) -> Arc<RwLock<Transaction>> {
         let map = self.transaction_map.read();
         map.get(txn_id).expect("transaction not found").clone()
@@ -287,8 +306,8 @@ mod test {
             assert!(row.is_some());
 
             let row = row.unwrap();
-            assert_eq!(row.username(), "apple");
-            assert_eq!(row.email(), "apple@apple.com");
+            assert_eq!(row.values[0].to_string(), "apple");
+            assert_eq!(row.values[1].to_string(), "apple@apple.com");
         });
 
         // Finally delete and commit it
@@ -303,8 +322,8 @@ mod test {
             assert!(row.is_some());
 
             let row = row.unwrap();
-            assert_eq!(row.username(), "john");
-            assert_eq!(row.email(), "tim@apple.com");
+            assert_eq!(row.values[0].to_string(), "john");
+            assert_eq!(row.values[1].to_string(), "tim@apple.com");
         });
 
         cleanup_table();
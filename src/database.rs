@@ -0,0 +1,514 @@
+use crate::catalog::Catalog;
+use crate::concurrency::{self, LockManager, TransactionManager};
+use crate::schema::Schema;
+use crate::storage::{Pager, PagerError, PagerOptions, PagerStats, SynchronousMode};
+use crate::table::Table;
+use std::path::Path;
+use std::sync::Arc;
+
+// The table used by our REPL and the single table APIs when
+// no table name is given.
+pub const DEFAULT_TABLE_NAME: &str = "users";
+
+pub fn default_table_schema() -> Schema {
+    "id integer, username text(32), email text(255)"
+        .parse()
+        .unwrap()
+}
+
+pub struct Database {
+    pager: Arc<Pager>,
+    catalog: Arc<Catalog>,
+    // The transactions of our database, see Database::transaction_manager.
+    lock_manager: Arc<LockManager>,
+    transaction_manager: Arc<TransactionManager>,
+}
+
+impl Database {
+    pub fn new(path: impl AsRef<Path>, pool_size: usize) -> Database {
+        Self::from_pager(Pager::new(path, pool_size))
+    }
+
+    /// Open the database at the given path with our options, see
+    /// Pager::open_with_options.
+    pub fn with_options(
+        path: impl AsRef<Path>,
+        pool_size: usize,
+        options: PagerOptions,
+    ) -> Database {
+        let pager = Pager::open_with_options(path, pool_size, options)
+            .unwrap_or_else(|err| panic!("fail to open database: {err}"));
+        Self::from_pager(pager)
+    }
+
+    fn from_pager(pager: Pager) -> Database {
+        let pager = Arc::new(pager);
+        Pager::start_background_writer(&pager);
+        Pager::start_read_ahead(&pager);
+        let catalog = Catalog::new(pager.clone())
+            .unwrap_or_else(|err| panic!("fail to open database: {err}"));
+        let catalog = Arc::new(catalog);
+
+        let lock_manager = Arc::new(LockManager::new());
+        let transaction_manager = Arc::new(TransactionManager::new(lock_manager.clone()));
+
+        Database {
+            pager,
+            catalog,
+            lock_manager,
+            transaction_manager,
+        }
+    }
+
+    pub fn create_table(&self, name: &str, schema: &Schema) -> Result<Table, String> {
+        let entry = self.catalog.create_table(name, schema)?;
+        Ok(Table::from_catalog(self.catalog.clone(), entry))
+    }
+
+    pub fn drop_table(&self, name: &str) -> Result<(), String> {
+        self.catalog.drop_table(name).map(|_| ())
+    }
+
+    pub fn table(&self, name: &str) -> Option<Table> {
+        self.catalog
+            .get(name)
+            .map(|entry| Table::from_catalog(self.catalog.clone(), entry))
+    }
+
+    pub fn table_or_create(&self, name: &str, schema: &Schema) -> Result<Table, String> {
+        match self.table(name) {
+            Some(table) => Ok(table),
+            None => self.create_table(name, schema),
+        }
+    }
+
+    pub fn transactional_table(
+        &self,
+        name: &str,
+        lock_manager: Arc<LockManager>,
+    ) -> Option<concurrency::Table> {
+        self.catalog.get(name).map(|entry| {
+            concurrency::Table::from_catalog(
+                self.pager.clone(),
+                self.catalog.clone(),
+                entry,
+                lock_manager,
+            )
+        })
+    }
+
+    /// The lock manager of our transaction manager, for the tables of its
+    /// transactions, see Database::transactional_table.
+    pub fn lock_manager(&self) -> Arc<LockManager> {
+        self.lock_manager.clone()
+    }
+
+    /// Our transactions, which have to begin with our transaction manager
+    /// for our vacuum to know about them, see Database::vacuum.
+    pub fn transaction_manager(&self) -> Arc<TransactionManager> {
+        self.transaction_manager.clone()
+    }
+
+    pub fn table_names(&self) -> Vec<String> {
+        self.catalog.table_names()
+    }
+
+    pub fn flush(&self) -> Result<(), PagerError> {
+        self.catalog.flush()
+    }
+
+    /// Compact our database file, see Catalog::vacuum.
+    ///
+    /// Similar to SQLite, we refuse to vacuum while a transaction of our
+    /// transaction manager is active, as the rows it marked as deleted would
+    /// be dropped before it commits or rolls back.
+    pub fn vacuum(&self) -> Result<(), String> {
+        self.transaction_manager
+            .without_active_transactions(|| self.catalog.vacuum())
+            .ok_or_else(|| "cannot vacuum while a transaction is active".to_string())?
+            .map_err(|err| err.to_string())
+    }
+
+    pub fn pages(&self) -> String {
+        self.pager.debug_pages()
+    }
+
+    /// Grow or shrink our buffer pool, see Pager::resize.
+    pub fn resize_buffer_pool(&self, pool_size: usize) -> Result<(), PagerError> {
+        self.pager.resize(pool_size)
+    }
+
+    pub fn stats(&self) -> PagerStats {
+        self.pager.stats()
+    }
+
+    pub fn synchronous(&self) -> SynchronousMode {
+        self.pager.synchronous()
+    }
+
+    pub fn set_synchronous(&self, synchronous: SynchronousMode) {
+        self.pager.set_synchronous(synchronous);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::concurrency::IsolationLevel;
+    use crate::query::prepare_statement;
+    use crate::row::Row;
+    use crate::storage::{BPlusTree, BackgroundWriterOptions};
+    use std::str::FromStr;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn tables_share_the_same_pager() {
+        let database = setup_test_database();
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+        let posts = database
+            .create_table(
+                "posts",
+                &"id integer, title text, score real".parse().unwrap(),
+            )
+            .unwrap();
+
+        for i in 1..50 {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            users.insert(&row);
+        }
+
+        let row = Row::from_str("1 post1 4.5").unwrap();
+        assert_eq!(posts.insert(&row), "inserting into page: 3, cell: 0...\n");
+
+        let row = Row::from_str("2 post2 high").unwrap();
+        assert_eq!(posts.insert(&row), "invalid score provided");
+        database.flush().unwrap();
+
+        let database = setup_test_database();
+        assert_eq!(database.table_names(), vec!["posts", "users"]);
+
+        let users = database.table("users").unwrap();
+        let expected_output: String = (1..50)
+            .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
+            .collect();
+        assert_eq!(
+            users.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+
+        let posts = database.table("posts").unwrap();
+        assert_eq!(
+            posts.select(&prepare_statement("select").unwrap()),
+            "(1, post1, 4.5)\n"
+        );
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn drop_table() {
+        let database = setup_test_database();
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+        let row = Row::from_str("1 user1 user1@email.com").unwrap();
+        users.insert(&row);
+
+        assert!(database.drop_table("users").is_ok());
+        assert!(database.table("users").is_none());
+        assert_eq!(
+            database.drop_table("users"),
+            Err("no such table: users".to_string())
+        );
+
+        // Recreate the table with the same name should give us an empty table.
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+        assert_eq!(users.select(&prepare_statement("select").unwrap()), "");
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn vacuum() {
+        let database = setup_test_database();
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+        let posts = database
+            .create_table("posts", &"id integer, body text".parse().unwrap())
+            .unwrap();
+        for i in 1..50 {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            users.insert(&row);
+            posts.insert(&Row::from_str(&format!("{i} post{i}")).unwrap());
+        }
+        database.drop_table("users").unwrap();
+        database.flush().unwrap();
+        let page_count = database.pager.header().page_count;
+
+        database.vacuum().unwrap();
+        assert!(database.pager.header().page_count < page_count);
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        assert!(!Path::new(&format!("{file}-vacuum")).exists());
+
+        // Our tables look their root pages up again once they're moved.
+        posts.insert(&Row::from_str("50 post50").unwrap());
+        let expected_output: String = (1..=50).map(|i| format!("({i}, post{i})\n")).collect();
+        assert_eq!(
+            posts.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+        assert_eq!(
+            users.select(&prepare_statement("select").unwrap()),
+            "no such table: users\n"
+        );
+        database.flush().unwrap();
+
+        let database = setup_test_database();
+        assert_eq!(database.table_names(), vec!["posts"]);
+        let posts = database.table("posts").unwrap();
+        assert_eq!(
+            posts.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn vacuum_a_table_read_ahead_by_a_scan() {
+        cleanup_test_db_file();
+        let database = setup_test_database();
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+        for i in 1..400 {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            users.insert(&row);
+        }
+        for i in (1..400).filter(|i| i % 3 == 0) {
+            users.delete(&Row::new(i, vec![]));
+        }
+        database.flush().unwrap();
+        drop(database);
+
+        // Our scan is read ahead in the background while our vacuum moves
+        // the leaf pages read ahead.
+        let database = setup_test_database();
+        let users = database.table("users").unwrap();
+        let expected_output: String = (1..400)
+            .filter(|i| i % 3 != 0)
+            .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
+            .collect();
+        assert_eq!(
+            users.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+        let root_page_id = database.catalog.get("users").unwrap().root_page_id;
+        let tree = BPlusTree::<u64, Row>::new(&database.pager, root_page_id);
+        let page = tree.search_page(None).unwrap();
+        let next_leaf = page.node.as_ref().unwrap().next_leaf_offset as usize;
+        drop(page);
+        drop(database.pager.fetch_read_page_guard(next_leaf).unwrap());
+
+        database.vacuum().unwrap();
+        let users = database.table("users").unwrap();
+        assert_eq!(
+            users.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+
+        let database = setup_test_database();
+        let users = database.table("users").unwrap();
+        assert_eq!(
+            users.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn vacuum_while_tables_are_used() {
+        cleanup_test_db_file();
+        let database = setup_test_database();
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+
+        // Our vacuum waits for the inserts in progress, which wait for it.
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..300 {
+                    let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+                    users.insert(&row);
+                }
+            });
+            for _ in 0..5 {
+                database.vacuum().unwrap();
+            }
+        });
+
+        let expected_output: String = (1..300)
+            .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
+            .collect();
+        assert_eq!(
+            users.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn refuse_to_vacuum_while_pages_are_pinned() {
+        cleanup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let options = PagerOptions {
+            frame_wait_timeout: Some(Duration::from_millis(10)),
+            ..PagerOptions::default()
+        };
+        let database = Database::with_options(&file, 8, options);
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+        for i in 1..50 {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            users.insert(&row);
+        }
+
+        // Our catalog is rebuilt from its entries in memory, so its root is
+        // only found pinned once our buffer pool is discarded.
+        let root_page_id = database.pager.catalog_root_page_id();
+        let page = database.pager.fetch_read_page_guard(root_page_id).unwrap();
+        assert_eq!(
+            database.vacuum(),
+            Err("database pages are still in use".to_string())
+        );
+        assert!(!Path::new(&format!("{file}-vacuum")).exists());
+        drop(page);
+
+        let expected_output: String = (1..50)
+            .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
+            .collect();
+        assert_eq!(
+            users.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+        assert_eq!(database.vacuum(), Ok(()));
+        assert_eq!(
+            users.select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn refuse_to_vacuum_while_a_transaction_is_active() {
+        let database = setup_test_database();
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+        for i in 1..50 {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            users.insert(&row);
+        }
+
+        let table = database
+            .transactional_table("users", database.lock_manager())
+            .unwrap();
+        let transaction_manager = database.transaction_manager();
+        let transaction = transaction_manager.begin(IsolationLevel::RepeatableRead);
+        let mut t = transaction.write();
+        let row = Row::from_str("5 user5 user5@email.com").unwrap();
+        let rid = table.get_row_id(5, &mut t).unwrap();
+        assert!(table.delete(&row, &rid, &mut t));
+        drop(t);
+
+        // Otherwise, rolling back the delete would mark another row as
+        // undeleted, as the deleted row is gone.
+        assert_eq!(
+            database.vacuum(),
+            Err("cannot vacuum while a transaction is active".to_string())
+        );
+
+        transaction_manager.abort(&table, &mut transaction.write());
+        assert_eq!(database.vacuum(), Ok(()));
+        let transaction = transaction_manager.begin(IsolationLevel::RepeatableRead);
+        let rid = table.get_row_id(5, &mut transaction.write()).unwrap();
+        assert_eq!(table.get(rid, &mut transaction.write()), Some(row));
+        transaction_manager.commit(&table, &mut transaction.write());
+        let expected_output: String = (1..50)
+            .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
+            .collect();
+        assert_eq!(
+            database
+                .table("users")
+                .unwrap()
+                .select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn write_dirty_pages_in_background() {
+        cleanup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let options = PagerOptions {
+            background_writer: Some(BackgroundWriterOptions {
+                interval: Duration::from_millis(10),
+                batch_size: 4,
+            }),
+            ..PagerOptions::default()
+        };
+        let database = Database::with_options(&file, 8, options);
+        let users = database
+            .create_table("users", &default_table_schema())
+            .unwrap();
+        for i in 1..50 {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            users.insert(&row);
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while database.pager.dirty_page_count() > 0 {
+            assert!(Instant::now() < deadline, "dirty pages are not written");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // Our rows are on disk without flushing our database.
+        let options = PagerOptions {
+            background_writer: None,
+            ..PagerOptions::default()
+        };
+        let reopened = Database::with_options(&file, 8, options);
+        let expected_output: String = (1..50)
+            .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
+            .collect();
+        assert_eq!(
+            reopened
+                .table("users")
+                .unwrap()
+                .select(&prepare_statement("select").unwrap()),
+            expected_output
+        );
+
+        cleanup_test_db_file();
+    }
+
+    fn setup_test_database() -> Database {
+        Database::new(format!("test-{:?}.db", std::thread::current().id()), 8)
+    }
+
+    fn cleanup_test_db_file() {
+        let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+    }
+}
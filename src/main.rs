@@ -1,25 +1,30 @@
+use crate::database::{default_table_schema, Database, DEFAULT_TABLE_NAME};
 use crate::query::*;
-use crate::storage::LEAF_NODE_CELL_SIZE;
-use crate::table::*;
+use crate::storage::{
+    BackgroundWriterOptions, PagerOptions, ReplacementPolicy, StorageBackend, SynchronousMode,
+};
 use std::io::Write;
 use std::process::exit;
+use std::time::Duration;
 
-#[macro_use]
-extern crate serde_big_array;
-big_array! {
-    BigArray;
-    32, 255, LEAF_NODE_CELL_SIZE
-}
-
+mod catalog;
 mod concurrency;
+mod database;
 mod query;
 mod recovery;
 mod row;
+mod schema;
 mod storage;
 mod table;
 
 fn main() -> std::io::Result<()> {
-    let mut table = Table::new("data.db", 8);
+    let database = match options_from_args() {
+        Some(options) => Database::with_options("data.db", 8, options),
+        None => Database::new("data.db", 8),
+    };
+    database
+        .table_or_create(DEFAULT_TABLE_NAME, &default_table_schema())
+        .unwrap();
     let mut buffer = String::new();
 
     loop {
@@ -27,9 +32,12 @@ fn main() -> std::io::Result<()> {
         std::io::stdin().read_line(&mut buffer)?;
 
         let input = buffer.trim();
-        let output = handle_input(&mut table, input);
+        let output = handle_input(&database, input);
         if output == "Exit" {
-            table.flush();
+            if let Err(err) = database.flush() {
+                eprintln!("{err}");
+                exit(1);
+            }
             exit(0);
         }
 
@@ -40,23 +48,141 @@ fn main() -> std::io::Result<()> {
     }
 }
 
+/// The options given by `--page-size <bytes>`, which is only used when
+/// data.db is created, `--replacer <lru|clock|lru-k|lru-<k>|lfu>`,
+/// `--pool-instances <n>` to split our buffer pool, `--read-ahead <pages>`
+/// (0 to disable), `--storage <file|mmap|io_uring>`,
+/// `--synchronous <off|normal|full>`, and `--writer-interval <ms>`
+/// (0 to disable) and `--writer-batch <pages>` for our background writer.
+fn options_from_args() -> Option<PagerOptions> {
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| {
+        let index = args.iter().position(|arg| arg == flag)?;
+        let value = args
+            .get(index + 1)
+            .unwrap_or_else(|| panic!("{flag} expects a value"));
+        Some(value.clone())
+    };
+
+    let page_size = value_of("--page-size").map(|page_size| {
+        page_size
+            .parse()
+            .unwrap_or_else(|_| panic!("--page-size expects a number of bytes"))
+    });
+    let replacement_policy = value_of("--replacer").map(|policy| {
+        policy
+            .parse::<ReplacementPolicy>()
+            .unwrap_or_else(|err| panic!("{err}"))
+    });
+    let buffer_pool_instances = value_of("--pool-instances").map(|instances| {
+        instances
+            .parse()
+            .ok()
+            .filter(|&instances: &usize| instances > 0)
+            .unwrap_or_else(|| panic!("--pool-instances expects a positive number of instances"))
+    });
+    let read_ahead = value_of("--read-ahead").map(|read_ahead| {
+        read_ahead
+            .parse()
+            .unwrap_or_else(|_| panic!("--read-ahead expects a number of pages"))
+    });
+    let storage = value_of("--storage").map(|storage| {
+        storage
+            .parse::<StorageBackend>()
+            .unwrap_or_else(|err| panic!("{err}"))
+    });
+    let synchronous = value_of("--synchronous").map(|synchronous| {
+        synchronous
+            .parse::<SynchronousMode>()
+            .unwrap_or_else(|err| panic!("{err}"))
+    });
+    let writer_interval = value_of("--writer-interval").map(|interval| {
+        interval
+            .parse()
+            .map(Duration::from_millis)
+            .unwrap_or_else(|_| panic!("--writer-interval expects a number of milliseconds"))
+    });
+    let writer_batch_size = value_of("--writer-batch").map(|batch_size| {
+        batch_size
+            .parse()
+            .unwrap_or_else(|_| panic!("--writer-batch expects a number of pages"))
+    });
+    if page_size.is_none()
+        && replacement_policy.is_none()
+        && buffer_pool_instances.is_none()
+        && read_ahead.is_none()
+        && storage.is_none()
+        && synchronous.is_none()
+        && writer_interval.is_none()
+        && writer_batch_size.is_none()
+    {
+        return None;
+    }
+
+    let default = PagerOptions::default();
+    let default_writer = BackgroundWriterOptions::default();
+    let background_writer = match writer_interval {
+        Some(interval) if interval.is_zero() => None,
+        _ => Some(BackgroundWriterOptions {
+            interval: writer_interval.unwrap_or(default_writer.interval),
+            batch_size: writer_batch_size.unwrap_or(default_writer.batch_size),
+        }),
+    };
+    Some(PagerOptions {
+        page_size: page_size.unwrap_or(default.page_size),
+        replacement_policy: replacement_policy.unwrap_or(default.replacement_policy),
+        buffer_pool_instances: buffer_pool_instances.or(default.buffer_pool_instances),
+        read_ahead: read_ahead.unwrap_or(default.read_ahead),
+        storage: storage.unwrap_or(default.storage),
+        synchronous: synchronous.unwrap_or(default.synchronous),
+        background_writer,
+        ..default
+    })
+}
+
 fn print_prompt() {
     print!("db > ");
     let _ = std::io::stdout().flush();
 }
 
-fn handle_input(table: &mut Table, input: &str) -> String {
+fn handle_input(database: &Database, input: &str) -> String {
     if input.starts_with('.') {
         match handle_meta_command(input) {
             MetaCommand::Exit => return "Exit".to_string(),
-            MetaCommand::PrintTree => return table.to_string(),
-            MetaCommand::PrintPages => return table.pages(),
+            MetaCommand::PrintTree(table_name) => {
+                let table_name = table_name.as_deref().unwrap_or(DEFAULT_TABLE_NAME);
+                return database
+                    .table(table_name)
+                    .map_or_else(|| format!("no such table: {table_name}"), |t| t.to_string());
+            }
+            MetaCommand::PrintPages => return database.pages(),
+            MetaCommand::PrintStats => return database.stats().to_string(),
+            MetaCommand::ListTables => return database.table_names().join("\n"),
+            MetaCommand::Vacuum => {
+                return database.vacuum().map_or_else(|err| err, |_| "".to_string());
+            }
+            MetaCommand::Resize(Some(pool_size)) => {
+                return database
+                    .resize_buffer_pool(pool_size)
+                    .map_or_else(|err| err.to_string(), |_| "".to_string());
+            }
+            MetaCommand::Resize(None) => return "expect a positive number of frames".to_string(),
+            MetaCommand::Synchronous(None) => return database.synchronous().to_string(),
+            MetaCommand::Synchronous(Some(synchronous)) => {
+                return synchronous.parse::<SynchronousMode>().map_or_else(
+                    |err| err,
+                    |synchronous| {
+                        database.set_synchronous(synchronous);
+                        "".to_string()
+                    },
+                );
+            }
             MetaCommand::Unrecognized => return format!("Unrecognized command '{input}'."),
         }
     }
 
     match prepare_statement(input) {
-        Ok(statement) => execute_statement(table, &statement),
+        Ok(statement) => execute_statement(database, &statement),
         Err(reason) => reason,
     }
 }
@@ -71,8 +197,8 @@ mod test {
 
     #[test]
     fn exit_command() {
-        let mut table = setup_test_table();
-        let output = handle_input(&mut table, ".exit");
+        let database = setup_test_database();
+        let output = handle_input(&database, ".exit");
         assert_eq!(output, "Exit");
 
         clean_test();
@@ -80,36 +206,77 @@ mod test {
 
     #[test]
     fn unrecognized_command() {
-        let mut table = setup_test_table();
-        let output = handle_input(&mut table, ".dfaskfd");
+        let database = setup_test_database();
+        let output = handle_input(&database, ".dfaskfd");
         assert_eq!(output, "Unrecognized command '.dfaskfd'.");
 
         clean_test();
     }
 
+    #[test]
+    fn stats_command() {
+        let database = setup_test_database();
+        handle_input(&database, "insert 1 john john@email.com");
+
+        let output = handle_input(&database, ".stats");
+        assert!(output.starts_with("hits: "), "{output}");
+        assert!(output.contains("\nleaf pages: "), "{output}");
+
+        clean_test();
+    }
+
+    #[test]
+    fn resize_command() {
+        let database = setup_test_database();
+
+        let output = handle_input(&database, ".resize 16");
+        assert_eq!(output, "");
+        let output = handle_input(&database, ".stats");
+        assert!(output.contains("\npool size: 16\n"), "{output}");
+
+        let output = handle_input(&database, ".resize 0");
+        assert_eq!(output, "expect a positive number of frames");
+
+        clean_test();
+    }
+
+    #[test]
+    fn synchronous_command() {
+        let database = setup_test_database();
+
+        let output = handle_input(&database, ".synchronous");
+        assert_eq!(output, "normal");
+        let output = handle_input(&database, ".synchronous FULL");
+        assert_eq!(output, "");
+        let output = handle_input(&database, ".synchronous");
+        assert_eq!(output, "full");
+
+        let output = handle_input(&database, ".synchronous fast");
+        assert_eq!(output, "unknown synchronous mode fast");
+
+        clean_test();
+    }
+
     #[test]
     fn invalid_statement() {
-        let mut table = setup_test_table();
-        let output = handle_input(&mut table, "insert 1 apple apple apple");
-        assert_eq!(
-            output,
-            "Unrecognized keyword at start of '1 apple apple apple'."
-        );
+        let database = setup_test_database();
+        let output = handle_input(&database, "insert 1 apple apple apple");
+        assert_eq!(output, "4 values for 3 columns");
 
         clean_test();
     }
 
     #[test]
     fn select_statement() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         assert_eq!(output, "");
 
-        handle_input(&mut table, "insert 1 john john@email.com");
-        handle_input(&mut table, "insert 2 wick wick@email.com");
+        handle_input(&database, "insert 1 john john@email.com");
+        handle_input(&database, "insert 2 wick wick@email.com");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         assert_eq!(
             output,
             "(1, john, john@email.com)\n(2, wick, wick@email.com)\n"
@@ -120,18 +287,18 @@ mod test {
 
     #[test]
     fn select_by_id_statement() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
-        let output = handle_input(&mut table, "select 1");
+        let output = handle_input(&database, "select 1");
         assert_eq!(output, "");
 
-        handle_input(&mut table, "insert 1 john john@email.com");
-        handle_input(&mut table, "insert 2 wick wick@email.com");
+        handle_input(&database, "insert 1 john john@email.com");
+        handle_input(&database, "insert 2 wick wick@email.com");
 
-        let output = handle_input(&mut table, "select 1");
+        let output = handle_input(&database, "select 1");
         assert_eq!(output, "(1, john, john@email.com)\n");
 
-        let output = handle_input(&mut table, "select 2");
+        let output = handle_input(&database, "select 2");
         assert_eq!(output, "(2, wick, wick@email.com)\n");
 
         clean_test();
@@ -139,29 +306,29 @@ mod test {
 
     #[test]
     fn insert_statement() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
-        let output = handle_input(&mut table, "insert 2 john john@email.com");
-        assert_eq!(output, "inserting into page: 0, cell: 0...\n");
+        let output = handle_input(&database, "insert 2 john john@email.com");
+        assert_eq!(output, "inserting into page: 2, cell: 0...\n");
 
-        let output = handle_input(&mut table, "insert 1 john john@email.com");
-        assert_eq!(output, "inserting into page: 0, cell: 0...\n");
+        let output = handle_input(&database, "insert 1 john john@email.com");
+        assert_eq!(output, "inserting into page: 2, cell: 0...\n");
 
-        let output = handle_input(&mut table, "insert 3 john john@email.com");
-        assert_eq!(output, "inserting into page: 0, cell: 2...\n");
+        let output = handle_input(&database, "insert 3 john john@email.com");
+        assert_eq!(output, "inserting into page: 2, cell: 2...\n");
 
         clean_test();
     }
 
     #[test]
     fn insert_up_to_3_leaf_node() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in 1..15 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
-        handle_input(&mut table, "insert 15 user15 user15@email.com");
+        handle_input(&database, "insert 15 user15 user15@email.com");
 
         let expected_output = "- internal (size 1)
   - leaf (size 7)
@@ -183,7 +350,7 @@ mod test {
     - 14
     - 15
 ";
-        let output = table.to_string();
+        let output = handle_input(&database, ".tree");
         assert_eq!(output, expected_output);
 
         clean_test();
@@ -191,7 +358,7 @@ mod test {
 
     #[test]
     fn insert_up_to_4_leaf_node_split_when_child_max_key_larger_than_right_max_key() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
         let inputs = [
             "insert 18 user18 person18@example.com",
             "insert 7 user7 person7@example.com",
@@ -226,7 +393,7 @@ mod test {
         ];
 
         for input in inputs {
-            handle_input(&mut table, input);
+            handle_input(&database, input);
         }
 
         let expected_output = "- internal (size 3)
@@ -268,7 +435,7 @@ mod test {
     - 29
     - 30
 ";
-        let output = table.to_string();
+        let output = handle_input(&database, ".tree");
         assert_eq!(output, expected_output);
 
         clean_test();
@@ -276,7 +443,7 @@ mod test {
 
     #[test]
     fn insert_up_to_4_leaf_node_split_when_child_max_key_not_larger_than_right_max_key() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
         let inputs = [
             "insert 1 user18 person18@example.com",
             "insert 4 user7 person7@example.com",
@@ -316,7 +483,7 @@ mod test {
         ];
 
         for input in inputs {
-            handle_input(&mut table, input);
+            handle_input(&database, input);
         }
 
         let expected_output = "- internal (size 3)
@@ -362,17 +529,17 @@ mod test {
     - 88
     - 90
 ";
-        let output = table.to_string();
+        let output = handle_input(&database, ".tree");
         assert_eq!(output, expected_output);
         clean_test()
     }
 
     #[test]
     fn insert_and_split_internal_node() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in 1..36 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
         let expected_output = "- internal (size 1)
@@ -423,7 +590,7 @@ mod test {
       - 34
       - 35
 ";
-        let output = table.to_string();
+        let output = handle_input(&database, ".tree");
         assert_eq!(output, expected_output);
 
         clean_test();
@@ -431,34 +598,34 @@ mod test {
 
     #[test]
     fn insert_string_at_max_length() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
         let mut username = String::new();
         for _ in 0..32 {
             username.push('a');
         }
 
-        let output = handle_input(&mut table, &format!("insert 1 {username} john@email.com"));
-        assert_eq!(output, "inserting into page: 0, cell: 0...\n");
+        let output = handle_input(&database, &format!("insert 1 {username} john@email.com"));
+        assert_eq!(output, "inserting into page: 2, cell: 0...\n");
 
         let mut email = String::new();
         for _ in 0..255 {
             email.push('a');
         }
 
-        let output = handle_input(&mut table, &format!("insert 2 john {email}"));
-        assert_eq!(output, "inserting into page: 0, cell: 1...\n");
+        let output = handle_input(&database, &format!("insert 2 john {email}"));
+        assert_eq!(output, "inserting into page: 2, cell: 1...\n");
 
         clean_test();
     }
 
     #[test]
     fn error_when_duplicate_key() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
-        let output = handle_input(&mut table, "insert 1 john john@email.com");
-        assert_eq!(output, "inserting into page: 0, cell: 0...\n");
+        let output = handle_input(&database, "insert 1 john john@email.com");
+        assert_eq!(output, "inserting into page: 2, cell: 0...\n");
 
-        let output = handle_input(&mut table, "insert 1 john john@email.com");
+        let output = handle_input(&database, "insert 1 john john@email.com");
         assert_eq!(output, "duplicate key\n");
 
         clean_test();
@@ -466,8 +633,8 @@ mod test {
 
     #[test]
     fn error_when_id_is_negative() {
-        let mut table = setup_test_table();
-        let output = handle_input(&mut table, "insert -1 john john@email.com");
+        let database = setup_test_database();
+        let output = handle_input(&database, "insert -1 john john@email.com");
         assert_eq!(output, "invalid id provided");
 
         clean_test();
@@ -475,21 +642,21 @@ mod test {
 
     #[test]
     fn error_when_string_are_too_long() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
         let mut username = String::new();
         for _ in 0..33 {
             username.push('a');
         }
 
-        let output = handle_input(&mut table, &format!("insert 1 {username} john@email.com"));
-        assert_eq!(output, "Name is too long.");
+        let output = handle_input(&database, &format!("insert 1 {username} john@email.com"));
+        assert_eq!(output, "Username is too long.");
 
         let mut email = String::new();
         for _ in 0..256 {
             email.push('a');
         }
 
-        let output = handle_input(&mut table, &format!("insert 1 john {email}"));
+        let output = handle_input(&database, &format!("insert 1 john {email}"));
         assert_eq!(output, "Email is too long.");
 
         clean_test();
@@ -497,19 +664,19 @@ mod test {
 
     #[test]
     fn persist_data_to_file() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
-        handle_input(&mut table, "insert 2 john john@email.com");
-        handle_input(&mut table, "insert 1 wick wick@email.com");
-        let output = handle_input(&mut table, "select");
+        handle_input(&database, "insert 2 john john@email.com");
+        handle_input(&database, "insert 1 wick wick@email.com");
+        let output = handle_input(&database, "select");
         assert_eq!(
             output,
             "(1, wick, wick@email.com)\n(2, john, john@email.com)\n"
         );
-        table.flush();
+        database.flush().unwrap();
 
-        let mut reopen_table = setup_test_table();
-        let output = handle_input(&mut reopen_table, "select");
+        let reopen_database = setup_test_database();
+        let output = handle_input(&reopen_database, "select");
         assert_eq!(
             output,
             "(1, wick, wick@email.com)\n(2, john, john@email.com)\n"
@@ -520,14 +687,14 @@ mod test {
 
     #[test]
     fn persist_leaf_and_internal_node_to_file() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
         let row_count = 1000;
 
         for i in 1..row_count {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         let expected_output: Vec<String> = (1..row_count)
             .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
             .collect();
@@ -536,10 +703,10 @@ mod test {
 
         // To test it doesn't go stack overflow.
         // table.to_string();
-        table.flush();
+        database.flush().unwrap();
 
-        let mut reopen_table = setup_test_table();
-        let output = handle_input(&mut reopen_table, "select");
+        let reopen_database = setup_test_database();
+        let output = handle_input(&reopen_database, "select");
         assert_eq!(output, expected_output.join(""));
 
         clean_test();
@@ -688,12 +855,12 @@ mod test {
     }
 
     fn test_insertion<T: std::fmt::Display + Ord>(mut ids: Vec<T>) {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
         for i in &ids {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         ids.sort();
 
         let expected_output: Vec<String> = ids
@@ -747,13 +914,13 @@ mod test {
 
     quickcheck! {
         fn insert_and_select_prop(ids: UniqueIDs) -> bool {
-            let mut table = setup_test_table();
+            let database = setup_test_database();
 
             for i in &ids.0 {
-                handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+                handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
             }
 
-            let output = handle_input(&mut table, "select");
+            let output = handle_input(&database, "select");
 
             let mut sorted_ids = ids.0.clone();
             sorted_ids.sort();
@@ -770,19 +937,19 @@ mod test {
 
     #[test]
     fn delete_row_from_tree_with_only_root_node() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in 1..10 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
-        let output = handle_input(&mut table, "delete 5");
+        let output = handle_input(&database, "delete 5");
         assert_eq!(output, "deleted 5");
 
-        let output = handle_input(&mut table, "select 5");
+        let output = handle_input(&database, "select 5");
         assert_eq!(output, "");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         let expected_output = [1, 2, 3, 4, 6, 7, 8, 9]
             .iter()
             .map(|i| format!("({i}, user{i}, user{i}@email.com)\n"))
@@ -796,19 +963,19 @@ mod test {
 
     #[test]
     fn delete_row_from_tree_with_2_level_internal_and_leaf_node() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in 1..20 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
-        let output = handle_input(&mut table, "delete 5");
+        let output = handle_input(&database, "delete 5");
         assert_eq!(output, "deleted 5");
 
-        let output = handle_input(&mut table, "select 5");
+        let output = handle_input(&database, "select 5");
         assert_eq!(output, "");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         let expected_output = (1..20)
             .filter(|&i| i != 5)
             .collect::<Vec<u32>>()
@@ -824,22 +991,22 @@ mod test {
 
     #[test]
     fn delete_row_from_tree_with_3_level_internal_and_leaf_node() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in 1..100 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
-        let output = handle_input(&mut table, "delete 5");
+        let output = handle_input(&database, "delete 5");
         assert_eq!(output, "deleted 5");
 
-        let output = handle_input(&mut table, "delete 90");
+        let output = handle_input(&database, "delete 90");
         assert_eq!(output, "deleted 90");
 
-        let output = handle_input(&mut table, "delete 55");
+        let output = handle_input(&database, "delete 55");
         assert_eq!(output, "deleted 55");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         let expected_output = (1..100)
             .filter(|&i| i != 5 && i != 90 && i != 55)
             .collect::<Vec<u32>>()
@@ -855,16 +1022,16 @@ mod test {
 
     #[test]
     fn delete_row_with_id_in_internal_node() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in 1..100 {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
-        let output = handle_input(&mut table, "delete 7");
+        let output = handle_input(&database, "delete 7");
         assert_eq!(output, "deleted 7");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         let expected_output = (1..100)
             .filter(|&i| i != 7)
             .collect::<Vec<u32>>()
@@ -875,31 +1042,31 @@ mod test {
 
         assert_eq!(output, expected_output);
 
-        let output = handle_input(&mut table, "insert 7 user7 user7@email.com");
-        assert_eq!(output, "inserting into page: 1, cell: 6...\n");
+        let output = handle_input(&database, "insert 7 user7 user7@email.com");
+        assert_eq!(output, "inserting into page: 3, cell: 6...\n");
 
         clean_test();
     }
 
     #[test]
     fn delete_everything() {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in [1, 100] {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
-        let output = handle_input(&mut table, "delete 1");
+        let output = handle_input(&database, "delete 1");
         assert_eq!(output, "deleted 1");
 
-        let output = handle_input(&mut table, "delete 100");
+        let output = handle_input(&database, "delete 100");
         assert_eq!(output, "deleted 100");
 
-        let output = handle_input(&mut table, "select");
+        let output = handle_input(&database, "select");
         assert_eq!(output, "");
 
-        handle_input(&mut table, "insert 7 user7 user7@email.com");
-        let output = handle_input(&mut table, "select");
+        handle_input(&database, "insert 7 user7 user7@email.com");
+        let output = handle_input(&database, "select");
         assert_eq!(output, "(7, user7, user7@email.com)\n");
 
         clean_test();
@@ -1134,17 +1301,17 @@ mod test {
     }
 
     fn test_deletion(delete_input: DeleteInputs) {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in &delete_input.insertion_ids {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
         for i in &delete_input.deletion_ids {
-            let output = handle_input(&mut table, &format!("delete {i}"));
+            let output = handle_input(&database, &format!("delete {i}"));
             assert_eq!(output, format!("deleted {i}"));
 
-            let output = handle_input(&mut table, "select");
+            let output = handle_input(&database, "select");
             let mut sorted_ids = delete_input.insertion_ids.clone();
             sorted_ids.sort();
 
@@ -1173,6 +1340,118 @@ mod test {
         clean_test();
     }
 
+    #[test]
+    fn create_and_drop_tables() {
+        let database = setup_test_database();
+
+        let output = handle_input(
+            &database,
+            "create table orders (id integer, username text(32), email text(255))",
+        );
+        assert_eq!(output, "");
+
+        let output = handle_input(&database, "create table orders (id integer)");
+        assert_eq!(output, "table orders already exists");
+
+        let output = handle_input(&database, ".tables");
+        assert_eq!(output, "orders\nusers");
+
+        handle_input(&database, "insert 1 john john@email.com");
+        handle_input(&database, "insert into orders 2 jane jane@email.com");
+
+        let output = handle_input(&database, "select from orders");
+        assert_eq!(output, "(2, jane, jane@email.com)\n");
+
+        let output = handle_input(&database, "select");
+        assert_eq!(output, "(1, john, john@email.com)\n");
+
+        let output = handle_input(&database, "drop table orders");
+        assert_eq!(output, "");
+
+        let output = handle_input(&database, "select from orders");
+        assert_eq!(output, "no such table: orders");
+
+        let output = handle_input(&database, ".tree orders");
+        assert_eq!(output, "no such table: orders");
+
+        let output = handle_input(&database, ".tables");
+        assert_eq!(output, "users");
+
+        database.flush().unwrap();
+        let reopen_database = setup_test_database();
+        let output = handle_input(&reopen_database, ".tables");
+        assert_eq!(output, "users");
+
+        clean_test();
+    }
+
+    #[test]
+    fn vacuum_command() {
+        let database = setup_test_database();
+        for i in 1..=30 {
+            handle_input(
+                &database,
+                &format!("insert {i} user{i} person{i}@example.com"),
+            );
+        }
+        for i in 1..=25 {
+            handle_input(&database, &format!("delete {i}"));
+        }
+
+        let output = handle_input(&database, ".vacuum");
+        assert_eq!(output, "");
+
+        let output = handle_input(&database, "select");
+        let expected_output: String = (26..=30)
+            .map(|i| format!("({i}, user{i}, person{i}@example.com)\n"))
+            .collect();
+        assert_eq!(output, expected_output);
+
+        let output = handle_input(&database, ".tree");
+        assert_eq!(
+            output,
+            "- leaf (size 5)\n  - 26\n  - 27\n  - 28\n  - 29\n  - 30\n"
+        );
+
+        clean_test();
+    }
+
+    #[test]
+    fn table_with_typed_columns() {
+        let database = setup_test_database();
+
+        let output = handle_input(
+            &database,
+            "create table products (id integer, name text(16) not null, price real, stock integer, active boolean)",
+        );
+        assert_eq!(output, "");
+
+        handle_input(&database, "insert into products 1 apple 1.5 10 true");
+        handle_input(&database, "insert into products 2 banana 2 null false");
+
+        let output = handle_input(&database, "select from products");
+        assert_eq!(
+            output,
+            "(1, apple, 1.5, 10, true)\n(2, banana, 2, NULL, false)\n"
+        );
+
+        let output = handle_input(&database, "insert into products 3 null 1.5 10 true");
+        assert_eq!(output, "NOT NULL constraint failed: name");
+
+        let output = handle_input(&database, "insert into products 3 cherry cheap 10 true");
+        assert_eq!(output, "invalid price provided");
+
+        let output = handle_input(&database, "insert into products 3 cherry 1.5 10");
+        assert_eq!(output, "4 values for 5 columns");
+
+        database.flush().unwrap();
+        let reopen_database = setup_test_database();
+        let output = handle_input(&reopen_database, "select from products 2");
+        assert_eq!(output, "(2, banana, 2, NULL, false)\n");
+
+        clean_test();
+    }
+
     #[test]
     fn quickcheck_insert_delete_and_select() {
         // Change the Gen::new(size) to have quickcheck
@@ -1185,17 +1464,17 @@ mod test {
     }
 
     fn insert_delete_and_select_prop(delete_input: DeleteInputs) -> bool {
-        let mut table = setup_test_table();
+        let database = setup_test_database();
 
         for i in &delete_input.insertion_ids {
-            handle_input(&mut table, &format!("insert {i} user{i} user{i}@email.com"));
+            handle_input(&database, &format!("insert {i} user{i} user{i}@email.com"));
         }
 
         for i in &delete_input.deletion_ids {
-            let output = handle_input(&mut table, &format!("delete {i}"));
+            let output = handle_input(&database, &format!("delete {i}"));
             assert_eq!(output, format!("deleted {i}"));
 
-            let output = handle_input(&mut table, "select");
+            let output = handle_input(&database, "select");
             let mut sorted_ids = delete_input.insertion_ids.clone();
             sorted_ids.sort();
 
@@ -1229,8 +1508,12 @@ mod test {
         true
     }
 
-    fn setup_test_table() -> Table {
-        return Table::new(format!("test-{:?}.db", std::thread::current().id()), 8);
+    fn setup_test_database() -> Database {
+        let database = Database::new(format!("test-{:?}.db", std::thread::current().id()), 8);
+        database
+            .table_or_create(DEFAULT_TABLE_NAME, &default_table_schema())
+            .unwrap();
+        database
     }
 
     fn clean_test() {
@@ -4,7 +4,7 @@ use super::query_plan::{
     DeletePlanNode, IndexScanPlanNode, PlanNode, SeqScanPlanNode, UpdatePlanNode,
 };
 use crate::{
-    concurrency::{LockManager, RowID, Table, TableIntoIter, Transaction},
+    concurrency::{LockManager, RowID, Table, TableIntoIter, Transaction, TransactionState},
     row::Row,
 };
 use std::sync::Arc;
@@ -93,12 +93,28 @@ impl SequenceScanExecutor {
 impl Executor for SequenceScanExecutor {
     fn next(&mut self) -> Option<(RowID, Row)> {
         let table = &self.execution_context.table;
+        let mut transaction = self.execution_context.transaction.write();
+
+        // Similar to Table::get, our transaction is aborted when a page of
+        // our table can't be read.
         if self.iter.is_none() {
-            self.iter = Some(table.iter());
+            match table.iter() {
+                Ok(iter) => self.iter = Some(iter),
+                Err(_) => {
+                    transaction.set_state(TransactionState::Aborted);
+                    return None;
+                }
+            }
         };
 
         let iter = self.iter.as_mut().unwrap();
-        iter.next()
+        match iter.next()? {
+            Ok(item) => Some(item),
+            Err(_) => {
+                transaction.set_state(TransactionState::Aborted);
+                None
+            }
+        }
     }
 }
 
@@ -398,7 +414,7 @@ mod test {
             transaction,
         });
 
-        let new_row = Row::new("0", "user1", "email").unwrap();
+        let new_row = Row::from_str("0 user1 email").unwrap();
         let columns = vec!["username".to_string()];
         let plan_node = UpdatePlanNode {
             child: Box::new(PlanNode::SeqScan(seq_plan_node)),
@@ -420,7 +436,7 @@ mod test {
         let seq_plan_node = SeqScanPlanNode { predicate };
         let mut executor = SequenceScanExecutor::new(ctx, seq_plan_node);
         while let Some((_, row)) = executor.next() {
-            assert_eq!(row.username(), "user1");
+            assert_eq!(row.values[0].to_string(), "user1");
             assert!(row.id != 0);
         }
 
@@ -445,7 +461,7 @@ mod test {
         let update_plan_node = UpdatePlanNode {
             child: Box::new(PlanNode::IndexScan(child_plan_node.clone())),
             columns: vec!["email".to_string()],
-            new_row: Row::new("0", "0", "new@email.com").unwrap(),
+            new_row: Row::from_str("0 0 new@email.com").unwrap(),
         };
 
         let result = execution_engine.execute(PlanNode::Update(update_plan_node));
@@ -459,7 +475,7 @@ mod test {
         assert_eq!(result.len(), 1);
         let (_, row) = &result[0];
         assert_eq!(row.id, 15);
-        assert_eq!(row.email(), "new@email.com");
+        assert_eq!(row.values[1].to_string(), "new@email.com");
 
         cleanup_table();
     }
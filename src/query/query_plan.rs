@@ -16,7 +16,7 @@ pub struct SeqScanPlanNode {
 
 #[derive(Clone)]
 pub struct IndexScanPlanNode {
-    pub key: u32,
+    pub key: u64,
 }
 
 #[derive(Clone)]
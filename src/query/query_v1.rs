@@ -1,5 +1,6 @@
+use crate::database::{Database, DEFAULT_TABLE_NAME};
 use crate::row::Row;
-use crate::table::*;
+use crate::schema::Schema;
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -7,8 +8,14 @@ pub enum MetaCommand {
     // Success,
     Unrecognized,
     Exit,
-    PrintTree,
+    PrintTree(Option<String>),
     PrintPages,
+    PrintStats,
+    ListTables,
+    Vacuum,
+    Resize(Option<usize>),
+    // Print our synchronous mode, or change it to the given one.
+    Synchronous(Option<String>),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -16,6 +23,8 @@ pub enum StatementType {
     Select,
     Insert,
     Delete,
+    CreateTable,
+    DropTable,
 }
 
 impl FromStr for StatementType {
@@ -25,6 +34,8 @@ impl FromStr for StatementType {
             "select" => Ok(StatementType::Select),
             "insert" => Ok(StatementType::Insert),
             "delete" => Ok(StatementType::Delete),
+            "create" => Ok(StatementType::CreateTable),
+            "drop" => Ok(StatementType::DropTable),
             _ => Err("unrecognized statement".into()),
         }
     }
@@ -33,47 +44,136 @@ impl FromStr for StatementType {
 #[derive(Debug)]
 pub struct Statement {
     statement_type: StatementType,
+    // None mean the statement is executed against DEFAULT_TABLE_NAME.
+    pub table_name: Option<String>,
     pub row: Option<Row>,
+    // The columns of the table for create table.
+    pub schema: Option<Schema>,
 }
 
 pub fn handle_meta_command(command: &str) -> MetaCommand {
     if command.eq(".exit") {
         MetaCommand::Exit
     } else if command.eq(".tree") {
-        MetaCommand::PrintTree
+        MetaCommand::PrintTree(None)
+    } else if let Some(table_name) = command.strip_prefix(".tree ") {
+        MetaCommand::PrintTree(Some(table_name.to_string()))
     } else if command.eq(".pages") {
         MetaCommand::PrintPages
+    } else if command.eq(".stats") {
+        MetaCommand::PrintStats
+    } else if command.eq(".tables") {
+        MetaCommand::ListTables
+    } else if command.eq(".vacuum") {
+        MetaCommand::Vacuum
+    } else if let Some(pool_size) = command.strip_prefix(".resize ") {
+        MetaCommand::Resize(pool_size.parse().ok().filter(|&pool_size| pool_size > 0))
+    } else if command.eq(".synchronous") {
+        MetaCommand::Synchronous(None)
+    } else if let Some(synchronous) = command.strip_prefix(".synchronous ") {
+        MetaCommand::Synchronous(Some(synchronous.to_string()))
     } else {
         MetaCommand::Unrecognized
     }
 }
 
 pub fn prepare_statement(input: &str) -> Result<Statement, String> {
-    match input.split_once(' ') {
-        None => {
-            let statement_type = StatementType::from_str(input)?;
+    let (action, rest) = match input.split_once(' ') {
+        None => (input, None),
+        Some((action, rest)) => (action, Some(rest)),
+    };
+    let statement_type = StatementType::from_str(action)?;
+
+    // create table <name> (<column> <type>, ...) and drop table <name>
+    if statement_type == StatementType::CreateTable || statement_type == StatementType::DropTable {
+        let table_name = match rest.and_then(|rest| rest.split_once(' ')) {
+            Some(("table", table_name)) => table_name,
+            _ => return Err(format!("Unrecognized keyword at start of '{input}'.")),
+        };
+
+        if statement_type == StatementType::DropTable {
+            return Ok(Statement {
+                statement_type,
+                table_name: Some(table_name.to_string()),
+                row: None,
+                schema: None,
+            });
+        }
+
+        let (table_name, definition) = table_name
+            .split_once('(')
+            .and_then(|(table_name, definition)| {
+                Some((table_name.trim(), definition.strip_suffix(')')?))
+            })
+            .ok_or_else(|| format!("missing column definitions for '{input}'."))?;
+
+        return Ok(Statement {
+            statement_type,
+            table_name: Some(table_name.to_string()),
+            row: None,
+            schema: Some(Schema::from_str(definition)?),
+        });
+    }
 
+    // The table can be specified with insert into <name> ...,
+    // select from <name> ... and delete from <name> ...
+    let keyword = if statement_type == StatementType::Insert {
+        "into "
+    } else {
+        "from "
+    };
+    let (table_name, rest) = match rest.and_then(|rest| rest.strip_prefix(keyword)) {
+        Some(rest) => match rest.split_once(' ') {
+            None => (Some(rest.to_string()), None),
+            Some((table_name, rest)) => (Some(table_name.to_string()), Some(rest)),
+        },
+        None => (None, rest),
+    };
+
+    match rest {
+        None => {
             if statement_type == StatementType::Insert {
                 Err("missing row value for insert".to_string())
             } else {
                 Ok(Statement {
                     statement_type,
+                    table_name,
                     row: None,
+                    schema: None,
                 })
             }
         }
-        Some((action, rest)) => Ok(Statement {
-            statement_type: StatementType::from_str(action)?,
+        Some(rest) => Ok(Statement {
+            statement_type,
+            table_name,
             row: Some(Row::from_str(rest)?),
+            schema: None,
         }),
     }
 }
 
-pub fn execute_statement(table: &mut Table, statement: &Statement) -> String {
+pub fn execute_statement(database: &Database, statement: &Statement) -> String {
+    let table_name = statement
+        .table_name
+        .as_deref()
+        .unwrap_or(DEFAULT_TABLE_NAME);
+
     match statement.statement_type {
-        StatementType::Select => table.select(statement),
-        StatementType::Insert => table.insert(statement.row.as_ref().unwrap()),
-        StatementType::Delete => table.delete(statement.row.as_ref().unwrap()),
+        StatementType::CreateTable => database
+            .create_table(table_name, statement.schema.as_ref().unwrap())
+            .map_or_else(|err| err, |_| "".to_string()),
+        StatementType::DropTable => database
+            .drop_table(table_name)
+            .map_or_else(|err| err, |_| "".to_string()),
+        _ => match database.table(table_name) {
+            None => format!("no such table: {table_name}"),
+            Some(table) => match statement.statement_type {
+                StatementType::Select => table.select(statement),
+                StatementType::Insert => table.insert(statement.row.as_ref().unwrap()),
+                StatementType::Delete => table.delete(statement.row.as_ref().unwrap()),
+                _ => unreachable!(),
+            },
+        },
     }
 }
 
@@ -98,7 +198,7 @@ mod test {
 
         let statement = result.unwrap();
         assert_eq!(statement.statement_type, StatementType::Select);
-        assert_eq!(statement.row, Some(Row::new("1", "", "").unwrap()));
+        assert_eq!(statement.row, Some(Row::from_str("1").unwrap()));
     }
 
     #[test]
@@ -108,11 +208,71 @@ mod test {
 
         let statement = result.unwrap();
         assert_eq!(statement.statement_type, StatementType::Delete);
-        assert_eq!(statement.row, Some(Row::new("1", "", "").unwrap()));
+        assert_eq!(statement.row, Some(Row::from_str("1").unwrap()));
+    }
+
+    #[test]
+    fn parse_statement_with_table_name() {
+        let statement = prepare_statement("select from posts").unwrap();
+        assert_eq!(statement.statement_type, StatementType::Select);
+        assert_eq!(statement.table_name, Some("posts".to_string()));
+        assert_eq!(statement.row, None);
+
+        let statement = prepare_statement("select from posts 1").unwrap();
+        assert_eq!(statement.table_name, Some("posts".to_string()));
+        assert_eq!(statement.row, Some(Row::from_str("1").unwrap()));
+
+        let statement = prepare_statement("insert into posts 1 john john@email.com").unwrap();
+        assert_eq!(statement.statement_type, StatementType::Insert);
+        assert_eq!(statement.table_name, Some("posts".to_string()));
+        assert_eq!(
+            statement.row,
+            Some(Row::from_str("1 john john@email.com").unwrap())
+        );
+
+        let statement = prepare_statement("delete from posts 1").unwrap();
+        assert_eq!(statement.statement_type, StatementType::Delete);
+        assert_eq!(statement.table_name, Some("posts".to_string()));
+
+        let result = prepare_statement("insert into posts");
+        assert_eq!(result.unwrap_err(), "missing row value for insert");
+    }
+
+    #[test]
+    fn parse_create_and_drop_table() {
+        let statement = prepare_statement("create table posts (id integer, title text)").unwrap();
+        assert_eq!(statement.statement_type, StatementType::CreateTable);
+        assert_eq!(statement.table_name, Some("posts".to_string()));
+        assert_eq!(
+            statement.schema,
+            Some(Schema::from_str("id integer, title text").unwrap())
+        );
+
+        let statement = prepare_statement("drop table posts").unwrap();
+        assert_eq!(statement.statement_type, StatementType::DropTable);
+        assert_eq!(statement.table_name, Some("posts".to_string()));
+
+        let result = prepare_statement("create posts");
+        assert_eq!(
+            result.unwrap_err(),
+            "Unrecognized keyword at start of 'create posts'."
+        );
+
+        let result = prepare_statement("create table posts");
+        assert_eq!(
+            result.unwrap_err(),
+            "missing column definitions for 'create table posts'."
+        );
+
+        let result = prepare_statement("create table posts (title text)");
+        assert_eq!(
+            result.unwrap_err(),
+            "the first column must be an integer key"
+        );
     }
 
     #[test]
-    fn error_when_parse_action_with_non_u32_id() {
+    fn error_when_parse_action_with_non_u64_id() {
         let result = prepare_statement("select apple");
         assert!(result.is_err());
 
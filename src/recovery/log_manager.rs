@@ -1,15 +1,18 @@
 use tracing::trace;
 
 use super::log_record::LogRecord;
-use crate::storage::DiskManager;
+use crate::storage::{DiskManager, SharedSynchronousMode, WriteAheadLog};
 use std::{io::Read, path::Path, sync::atomic::AtomicU32, sync::Mutex, thread::JoinHandle};
 
 const LOG_BUFFER_SIZE: usize = 4096;
 
+#[derive(Debug)]
 struct LogManager {
     disk_manager: DiskManager,
     next_lsn: AtomicU32,
-    persistent_lsn: Option<AtomicU32>,
+    // The lsn up to which our log is on disk, or 0 if none is, see
+    // Pager::attach_log.
+    persistent_lsn: AtomicU32,
 
     // Alternatively, we should wrap the following 3 fields
     // in its own data structure and so we can just use a single Mutex to
@@ -23,10 +26,20 @@ struct LogManager {
 
 impl LogManager {
     pub fn new(path: impl AsRef<Path>) -> Self {
+        Self::with_synchronous(path, SharedSynchronousMode::default())
+    }
+
+    /// Open our log, which is synced on every append unless our
+    /// synchronous mode is off, e.g. the one of our Pager, see
+    /// Pager::shared_synchronous.
+    pub fn with_synchronous(path: impl AsRef<Path>, synchronous: SharedSynchronousMode) -> Self {
+        let mut disk_manager = DiskManager::new(path);
+        disk_manager.set_synchronous(synchronous);
+
         Self {
-            disk_manager: DiskManager::new(path),
+            disk_manager,
             next_lsn: AtomicU32::new(1),
-            persistent_lsn: None,
+            persistent_lsn: AtomicU32::new(0),
             log_buffer: Mutex::new([0; LOG_BUFFER_SIZE]),
             flush_buffer: Mutex::new([0; LOG_BUFFER_SIZE]),
             offset: Mutex::new(0),
@@ -39,9 +52,7 @@ impl LogManager {
     }
 
     pub fn persistent_lsn(&self) -> Option<u32> {
-        self.persistent_lsn
-            .as_ref()
-            .map(|p_lsn| p_lsn.load(std::sync::atomic::Ordering::SeqCst))
+        Some(self.flushed_lsn()).filter(|&lsn| lsn > 0)
     }
 
     pub fn offset(&self) -> usize {
@@ -53,7 +64,11 @@ impl LogManager {
     }
 
     pub fn append_log(&self, log_record: &mut LogRecord) -> u32 {
-        // One Lock
+        let mut offset = self.offset.lock().unwrap();
+
+        // Our lsn is taken while holding our offset, so our records are
+        // appended in the order of their lsn, and flushing our log buffer
+        // flushes every lsn before ours.
         let lsn = self
             .next_lsn
             .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
@@ -61,8 +76,6 @@ impl LogManager {
 
         let bytes = bincode::serialize(&log_record).unwrap();
 
-        // Another lock
-        let mut offset = self.offset.lock().unwrap();
         let mut log_buffer = self.log_buffer.lock().unwrap();
         let mut end = *offset + bytes.len();
 
@@ -72,23 +85,8 @@ impl LogManager {
         if end > log_buffer.len() {
             trace!("log buffer full at lsn: {lsn}, swapping with flush_buffer");
 
-            let mut flush_buffer = self.flush_buffer.lock().unwrap();
-
-            // Since we are wrapping both buffer in a Mutex, we need to
-            // dereference it before swapping. Else, we are essentially
-            // swapping the MutexGuard.
-            //
-            // This will cause a deadlock as we aren't dropping the correct
-            // MutexGuard of flush_buffer. It caused self.flush() to attempt
-            // to acquire the same lock and lead to deadlock.
-            std::mem::swap(&mut *log_buffer, &mut *flush_buffer);
-            drop(flush_buffer);
-
             // Flush manually once we full.
-            self.flush(*offset);
-
-            // Reset the range as well.
-            *offset = 0;
+            self.swap_and_flush(&mut offset, &mut log_buffer, lsn - 1);
             end = *offset + bytes.len();
         }
 
@@ -98,6 +96,34 @@ impl LogManager {
         lsn
     }
 
+    /// Swap our full log buffer with our flush buffer and write it to disk,
+    /// which puts our log on disk up to the given lsn.
+    fn swap_and_flush(
+        &self,
+        offset: &mut usize,
+        log_buffer: &mut [u8; LOG_BUFFER_SIZE],
+        flushed_lsn: u32,
+    ) {
+        let mut flush_buffer = self.flush_buffer.lock().unwrap();
+
+        // Since we are wrapping both buffer in a Mutex, we need to
+        // dereference it before swapping. Else, we are essentially
+        // swapping the MutexGuard.
+        //
+        // This will cause a deadlock as we aren't dropping the correct
+        // MutexGuard of flush_buffer. It caused self.flush() to attempt
+        // to acquire the same lock and lead to deadlock.
+        std::mem::swap(log_buffer, &mut *flush_buffer);
+        drop(flush_buffer);
+
+        self.flush(*offset);
+        self.persistent_lsn
+            .fetch_max(flushed_lsn, std::sync::atomic::Ordering::SeqCst);
+
+        // Reset the range as well.
+        *offset = 0;
+    }
+
     pub fn flush(&self, offset: usize) {
         trace!("flush WAL to disk up to offset {offset}");
         let mut flush_buffer = self.flush_buffer.lock().unwrap();
@@ -132,10 +158,31 @@ impl LogManager {
     }
 }
 
+impl WriteAheadLog for LogManager {
+    fn flushed_lsn(&self) -> u32 {
+        self.persistent_lsn
+            .load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    fn flush_until(&self, lsn: u32) {
+        let mut offset = self.offset.lock().unwrap();
+        // Another thread might have flushed it while we were waiting.
+        if lsn <= self.flushed_lsn() {
+            return;
+        }
+
+        let mut log_buffer = self.log_buffer.lock().unwrap();
+        let flushed_lsn = self.next_lsn() - 1;
+        self.swap_and_flush(&mut offset, &mut log_buffer, flushed_lsn);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::recovery::log_record::LogRecordType;
+    use crate::row::Row;
+    use crate::storage::{BPlusTree, Pager, SynchronousMode};
     use std::sync::Arc;
 
     #[test]
@@ -205,6 +252,52 @@ mod test {
         let _ = std::fs::remove_file(file);
     }
 
+    #[test]
+    fn flush_log_before_a_page_is_evicted() {
+        let file = format!("test_{:?}.wal", std::thread::current().id());
+        let db_file = format!("test-{:?}.db", std::thread::current().id());
+        let log_manager = Arc::new(LogManager::new(&file));
+        let pager = Pager::new(&db_file, 1);
+        pager.attach_log(log_manager.clone());
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+
+        let mut lr = LogRecord::new(1, None, LogRecordType::Insert);
+        let mut page = pager.fetch_write_page_guard(root).unwrap();
+        page.lsn = log_manager.append_log(&mut lr);
+        drop(page);
+        assert_eq!(log_manager.persistent_lsn(), None);
+
+        // Our catalog root page takes the only frame of our dirty page.
+        drop(pager.fetch_read_page_guard(pager.catalog_root_page_id()));
+        assert_eq!(log_manager.persistent_lsn(), Some(1));
+        assert_eq!(log_manager.get_logs().len(), 1);
+
+        let _ = std::fs::remove_file(file);
+        let _ = std::fs::remove_file(db_file);
+    }
+
+    #[test]
+    fn sync_log_as_our_pager_synchronous_mode_changes() {
+        let file = format!("test_{:?}.wal", std::thread::current().id());
+        let db_file = format!("test-{:?}.db", std::thread::current().id());
+        let pager = Pager::new(&db_file, 8);
+        let log_manager = LogManager::with_synchronous(&file, pager.shared_synchronous());
+        let mut lr = LogRecord::new(1, None, LogRecordType::Insert);
+
+        pager.set_synchronous(SynchronousMode::Off);
+        log_manager.flush_until(log_manager.append_log(&mut lr));
+        assert_eq!(log_manager.disk_manager.synced_appends(), 0);
+
+        pager.set_synchronous(SynchronousMode::Normal);
+        log_manager.flush_until(log_manager.append_log(&mut lr));
+        assert_eq!(log_manager.disk_manager.synced_appends(), 1);
+
+        let _ = std::fs::remove_file(file);
+        let _ = std::fs::remove_file(db_file);
+    }
+
     #[test]
     fn test_race_condition_of_swapping_buffer() {
         let file = format!("test_{:?}.wal", std::thread::current().id());
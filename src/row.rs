@@ -1,106 +1,273 @@
-use crate::BigArray;
-use serde::{Deserialize, Serialize};
+use crate::storage::Record;
 use std::str::FromStr;
 
-const USERNAME_SIZE: usize = 32;
-const EMAIL_SIZE: usize = 255;
-pub const ROW_SIZE: usize = USERNAME_SIZE + EMAIL_SIZE + 4 + std::mem::size_of::<bool>(); // u32 is 4 x u8;
+// Type tags of our record format. See Row::to_bytes.
+const NULL_TAG: u8 = 0;
+const INTEGER_TAG: u8 = 1;
+const REAL_TAG: u8 = 2;
+const TEXT_TAG: u8 = 3;
+const BLOB_TAG: u8 = 4;
+const BOOLEAN_TAG: u8 = 5;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+    Boolean(bool),
+}
+
+impl Value {
+    fn encoded_len(&self) -> usize {
+        1 + match self {
+            Value::Null => 0,
+            Value::Integer(_) | Value::Real(_) => 8,
+            Value::Text(text) => 4 + text.len(),
+            Value::Blob(blob) => 4 + blob.len(),
+            Value::Boolean(_) => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(integer) => write!(f, "{integer}"),
+            Value::Real(real) => write!(f, "{real}"),
+            Value::Text(text) => write!(f, "{text}"),
+            Value::Blob(blob) => {
+                write!(f, "x'")?;
+                for byte in blob {
+                    write!(f, "{byte:02x}")?;
+                }
+                write!(f, "'")
+            }
+            Value::Boolean(boolean) => write!(f, "{boolean}"),
+        }
+    }
+}
+
+// A row is made of the key of our B+ tree (id) and the values of every
+// other column of the table, in the order they are defined in the Schema.
+#[derive(Debug, PartialEq, Clone)]
 pub struct Row {
-    pub id: u32,
-    #[serde(with = "BigArray")]
-    pub username: [u8; USERNAME_SIZE],
-    #[serde(with = "BigArray")]
-    pub email: [u8; EMAIL_SIZE],
+    pub id: u64,
+    pub values: Vec<Value>,
     pub is_deleted: bool,
 }
 
 impl Row {
-    pub fn new(id: &str, u: &str, m: &str) -> Result<Row, String> {
-        let id = id
-            .parse::<u32>()
-            .map_err(|_e| "invalid id provided".to_string())?;
+    pub fn new(id: u64, values: Vec<Value>) -> Row {
+        Row {
+            id,
+            values,
+            is_deleted: false,
+        }
+    }
 
-        let mut username: [u8; USERNAME_SIZE] = [0; USERNAME_SIZE];
-        let mut email: [u8; EMAIL_SIZE] = [0; EMAIL_SIZE];
+    pub fn update(&mut self, index: usize, new_row: &Row) {
+        self.values[index] = new_row.values[index].clone();
+    }
 
-        let mut index = 0;
-        for c in u.bytes() {
-            username[index] = c;
-            index += 1;
-        }
+    // TRADEOFF: Similar to SQLite, our record format is self-describing.
+    //
+    //   [number of values: u16][type tag: u8][value]...
+    //
+    // where INTEGER and REAL take 8 bytes, BOOLEAN takes 1 byte, TEXT and BLOB
+    // are prefixed with their length as u32 and NULL takes no space at all.
+    //
+    // Storing a type tag for every value cost us a byte per column, but it means
+    // we don't need the Schema to decode a row. Hence, the storage layer
+    // (Node, Page and Pager) doesn't need to know which table it's working with.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.encoded_len());
+        bytes.extend_from_slice(&(self.values.len() as u16).to_le_bytes());
 
-        index = 0;
-        for c in m.bytes() {
-            email[index] = c;
-            index += 1;
+        for value in &self.values {
+            match value {
+                Value::Null => bytes.push(NULL_TAG),
+                Value::Integer(integer) => {
+                    bytes.push(INTEGER_TAG);
+                    bytes.extend_from_slice(&integer.to_le_bytes());
+                }
+                Value::Real(real) => {
+                    bytes.push(REAL_TAG);
+                    bytes.extend_from_slice(&real.to_le_bytes());
+                }
+                Value::Text(text) => {
+                    bytes.push(TEXT_TAG);
+                    bytes.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                    bytes.extend_from_slice(text.as_bytes());
+                }
+                Value::Blob(blob) => {
+                    bytes.push(BLOB_TAG);
+                    bytes.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+                    bytes.extend_from_slice(blob);
+                }
+                Value::Boolean(boolean) => {
+                    bytes.push(BOOLEAN_TAG);
+                    bytes.push(*boolean as u8);
+                }
+            }
         }
 
-        Ok(Row {
-            is_deleted: false,
-            id,
-            username,
-            email,
-        })
+        bytes
     }
 
-    pub fn update(&mut self, column: &str, new_row: &Row) {
-        match column {
-            "username" => {
-                self.username = new_row.username;
-            }
-            "email" => {
-                self.email = new_row.email;
-            }
-            _ => panic!("invalid column name: {}", column),
+    /// Decode the values of a row encoded by Row::to_bytes. Any trailing
+    /// bytes after the last value are ignored.
+    pub fn from_bytes(id: u64, bytes: &[u8]) -> Row {
+        let num_of_values = u16::from_le_bytes([bytes[0], bytes[1]]) as usize;
+        let mut values = Vec::with_capacity(num_of_values);
+        let mut offset = 2;
+
+        for _ in 0..num_of_values {
+            let tag = bytes[offset];
+            offset += 1;
+
+            let value = match tag {
+                NULL_TAG => Value::Null,
+                INTEGER_TAG => {
+                    let integer = i64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+                    offset += 8;
+                    Value::Integer(integer)
+                }
+                REAL_TAG => {
+                    let real = f64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+                    offset += 8;
+                    Value::Real(real)
+                }
+                TEXT_TAG | BLOB_TAG => {
+                    let len =
+                        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+                    offset += 4;
+                    let data = bytes[offset..offset + len].to_vec();
+                    offset += len;
+
+                    if tag == TEXT_TAG {
+                        Value::Text(String::from_utf8_lossy(&data).into_owned())
+                    } else {
+                        Value::Blob(data)
+                    }
+                }
+                BOOLEAN_TAG => {
+                    let boolean = bytes[offset] != 0;
+                    offset += 1;
+                    Value::Boolean(boolean)
+                }
+                _ => unreachable!("invalid type tag {tag}"),
+            };
+
+            values.push(value);
         }
-    }
 
-    pub fn username(&self) -> String {
-        // Since we are converting from a fixed size array, there will be NULL
-        // characters at the end. Hence, we need to trim it.
-        //
-        // While it doesn't impact outputing to the display, it caused
-        // issue with our test, as the result will have additional character while
-        // our expectation don't.
-        String::from_utf8_lossy(&self.username)
-            .trim_end_matches(char::from(0))
-            .to_owned()
+        Row::new(id, values)
     }
 
-    pub fn email(&self) -> String {
-        String::from_utf8_lossy(&self.email)
-            .trim_end_matches(char::from(0))
-            .to_owned()
+    /// The number of bytes needed by Row::to_bytes.
+    pub fn encoded_len(&self) -> usize {
+        2 + self.values.iter().map(Value::encoded_len).sum::<usize>()
     }
 }
 
+// Parse the values given in our REPL, e.g. "1 john john@email.com".
+//
+// Except for the id, every value is kept as a TEXT literal, as we don't know
+// the type of the columns here. The literals are converted to the right type
+// with Schema::coerce before they are written into a table.
 impl FromStr for Row {
     type Err = String;
 
     fn from_str(row: &str) -> Result<Self, Self::Err> {
-        let columns: Vec<&str> = row.split(' ').collect();
-        match columns[..] {
-            [id] => Self::new(id, "", ""),
-            [id, name, email] => {
-                if name.len() > USERNAME_SIZE {
-                    return Err("Name is too long.".to_string());
-                }
+        let mut columns = row.split(' ');
+        let id = columns
+            .next()
+            .unwrap_or_default()
+            .parse::<u64>()
+            .map_err(|_e| "invalid id provided".to_string())?;
+        let values = columns
+            .map(|column| Value::Text(column.to_string()))
+            .collect();
 
-                if email.len() > EMAIL_SIZE {
-                    return Err("Email is too long.".to_string());
-                }
+        Ok(Self::new(id, values))
+    }
+}
 
-                Self::new(id, name, email)
-            }
-            _ => Err(format!("Unrecognized keyword at start of '{row}'.")),
+// Our tables are B+ trees keyed by the id of their rows, where the deleted
+// flag of a row is kept in the flags of its leaf cell.
+impl Record<u64> for Row {
+    fn to_record(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
+
+    fn from_record(key: u64, record: &[u8], is_deleted: bool) -> Self {
+        Row {
+            is_deleted,
+            ..Row::from_bytes(key, record)
         }
     }
+
+    fn is_deleted(&self) -> bool {
+        self.is_deleted
+    }
 }
 
 impl std::string::ToString for Row {
     fn to_string(&self) -> String {
-        format!("({}, {}, {})", self.id, self.username(), self.email())
+        let mut output = format!("({}", self.id);
+        for value in &self.values {
+            output.push_str(&format!(", {value}"));
+        }
+        output.push(')');
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_bytes_from_bytes() {
+        let row = Row::new(
+            1,
+            vec![
+                Value::Null,
+                Value::Integer(-42),
+                Value::Real(1.5),
+                Value::Text("john".to_string()),
+                Value::Blob(vec![0, 1, 255]),
+                Value::Boolean(true),
+            ],
+        );
+
+        let mut bytes = row.to_bytes();
+        assert_eq!(bytes.len(), row.encoded_len());
+
+        // Trailing bytes, e.g. the unused space of a cell, are ignored.
+        bytes.append(&mut vec![0; 10]);
+        assert_eq!(Row::from_bytes(1, &bytes), row);
+    }
+
+    #[test]
+    fn from_str_and_to_string() {
+        let row = Row::from_str("1 john john@email.com").unwrap();
+        assert_eq!(row.id, 1);
+        assert_eq!(
+            row.values,
+            vec![
+                Value::Text("john".to_string()),
+                Value::Text("john@email.com".to_string())
+            ]
+        );
+        assert_eq!(row.to_string(), "(1, john, john@email.com)");
+
+        let row = Row::new(2, vec![Value::Null, Value::Blob(vec![10, 11])]);
+        assert_eq!(row.to_string(), "(2, NULL, x'0a0b')");
+
+        assert_eq!(Row::from_str("-1 john").unwrap_err(), "invalid id provided");
     }
 }
@@ -0,0 +1,345 @@
+use crate::row::{Row, Value};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DataType {
+    Integer,
+    Real,
+    // TEXT(n) limits the number of bytes a value can take.
+    Text(Option<usize>),
+    Blob,
+    Boolean,
+}
+
+impl FromStr for DataType {
+    type Err = String;
+
+    fn from_str(data_type: &str) -> Result<Self, Self::Err> {
+        let data_type = data_type.to_lowercase();
+        let (name, max_length) = match data_type.split_once('(') {
+            None => (data_type.as_str(), None),
+            Some((name, length)) => {
+                let max_length = length
+                    .strip_suffix(')')
+                    .and_then(|length| length.trim().parse::<usize>().ok())
+                    .ok_or_else(|| format!("invalid type '{data_type}'"))?;
+                (name.trim(), Some(max_length))
+            }
+        };
+
+        match (name, max_length) {
+            ("integer" | "int", None) => Ok(DataType::Integer),
+            ("real" | "float" | "double", None) => Ok(DataType::Real),
+            ("text" | "varchar", max_length) => Ok(DataType::Text(max_length)),
+            ("blob", None) => Ok(DataType::Blob),
+            ("boolean" | "bool", None) => Ok(DataType::Boolean),
+            _ => Err(format!("invalid type '{data_type}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::Integer => write!(f, "integer"),
+            DataType::Real => write!(f, "real"),
+            DataType::Text(None) => write!(f, "text"),
+            DataType::Text(Some(max_length)) => write!(f, "text({max_length})"),
+            DataType::Blob => write!(f, "blob"),
+            DataType::Boolean => write!(f, "boolean"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Column {
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+impl Column {
+    pub fn new(name: &str, data_type: DataType, nullable: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type,
+            nullable,
+        }
+    }
+
+    // Convert a value into the type of the column.
+    //
+    // Values given in our REPL are always TEXT literals (see Row::from_str),
+    // which we parse here now that we know the type they should be.
+    fn coerce(&self, value: &Value) -> Result<Value, String> {
+        let is_null = match value {
+            Value::Null => true,
+            Value::Text(literal) => literal.eq_ignore_ascii_case("null"),
+            _ => false,
+        };
+
+        if is_null {
+            return if self.nullable {
+                Ok(Value::Null)
+            } else {
+                Err(format!("NOT NULL constraint failed: {}", self.name))
+            };
+        }
+
+        let invalid = || format!("invalid {} provided", self.name);
+
+        match (self.data_type, value) {
+            (DataType::Integer, Value::Integer(_))
+            | (DataType::Real, Value::Real(_))
+            | (DataType::Blob, Value::Blob(_))
+            | (DataType::Boolean, Value::Boolean(_)) => Ok(value.clone()),
+            (DataType::Real, Value::Integer(integer)) => Ok(Value::Real(*integer as f64)),
+            (DataType::Text(max_length), Value::Text(text)) => {
+                if max_length.is_some_and(|max_length| text.len() > max_length) {
+                    Err(format!("{} is too long.", capitalize(&self.name)))
+                } else {
+                    Ok(value.clone())
+                }
+            }
+            (DataType::Integer, Value::Text(literal)) => {
+                literal.parse().map(Value::Integer).map_err(|_| invalid())
+            }
+            (DataType::Real, Value::Text(literal)) => {
+                literal.parse().map(Value::Real).map_err(|_| invalid())
+            }
+            (DataType::Blob, Value::Text(literal)) => Ok(Value::Blob(literal.as_bytes().to_vec())),
+            (DataType::Boolean, Value::Text(literal)) => match literal.to_lowercase().as_str() {
+                "true" | "1" => Ok(Value::Boolean(true)),
+                "false" | "0" => Ok(Value::Boolean(false)),
+                _ => Err(invalid()),
+            },
+            _ => Err("datatype mismatch".to_string()),
+        }
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+    }
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    // <name> <type> [not null]
+    fn from_str(definition: &str) -> Result<Self, Self::Err> {
+        let definition = definition.trim();
+        let (name, rest) = definition
+            .split_once(' ')
+            .ok_or_else(|| format!("missing type for column '{definition}'"))?;
+
+        let rest = rest.trim();
+        let (data_type, nullable) = match rest.to_lowercase().strip_suffix("not null") {
+            Some(data_type) => (data_type.trim().to_string(), false),
+            None => (rest.to_string(), true),
+        };
+
+        Ok(Column::new(name, DataType::from_str(&data_type)?, nullable))
+    }
+}
+
+impl std::fmt::Display for Column {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.data_type)?;
+        if !self.nullable {
+            write!(f, " not null")?;
+        }
+        Ok(())
+    }
+}
+
+// TRADEOFF: Our B+ tree is keyed by Row.id, hence, the first column
+// of every table must be an INTEGER and act as the primary key of the
+// table, similar to SQLite INTEGER PRIMARY KEY.
+//
+// Every other column is stored in Row.values, in the order they are defined.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Schema {
+    pub columns: Vec<Column>,
+}
+
+impl Schema {
+    pub fn new(mut columns: Vec<Column>) -> Result<Self, String> {
+        match columns.first_mut() {
+            Some(key) if key.data_type == DataType::Integer => key.nullable = false,
+            _ => return Err("the first column must be an integer key".to_string()),
+        }
+
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|c| c.name == column.name) {
+                return Err(format!("duplicate column name: {}", column.name));
+            }
+        }
+
+        Ok(Self { columns })
+    }
+
+    /// Return the index of the column in Row.values.
+    pub fn value_index(&self, name: &str) -> Result<usize, String> {
+        match self.columns.iter().position(|c| c.name == name) {
+            Some(0) => Err(format!("cannot update key column {name}")),
+            Some(index) => Ok(index - 1),
+            None => Err(format!("no such column: {name}")),
+        }
+    }
+
+    pub fn value_indexes(&self, names: &[String]) -> Result<Vec<usize>, String> {
+        names.iter().map(|name| self.value_index(name)).collect()
+    }
+
+    /// Validate the row against our schema and convert its values
+    /// into the type of their column.
+    pub fn coerce(&self, row: &Row) -> Result<Row, String> {
+        if row.values.len() + 1 != self.columns.len() {
+            return Err(format!(
+                "{} values for {} columns",
+                row.values.len() + 1,
+                self.columns.len()
+            ));
+        }
+
+        let values = self.columns[1..]
+            .iter()
+            .zip(&row.values)
+            .map(|(column, value)| column.coerce(value))
+            .collect::<Result<Vec<Value>, String>>()?;
+
+        Ok(Row::new(row.id, values))
+    }
+}
+
+impl FromStr for Schema {
+    type Err = String;
+
+    // Column definitions separated by comma, e.g.
+    // "id integer, username text(32), email text(255) not null"
+    fn from_str(definition: &str) -> Result<Self, Self::Err> {
+        let columns = definition
+            .split(',')
+            .map(Column::from_str)
+            .collect::<Result<Vec<Column>, String>>()?;
+
+        Schema::new(columns)
+    }
+}
+
+impl std::fmt::Display for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let columns: Vec<String> = self.columns.iter().map(|c| c.to_string()).collect();
+        write!(f, "{}", columns.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_schema() {
+        let schema = Schema::from_str(
+            "id integer, name TEXT(32) not null, score real, avatar blob, active bool",
+        )
+        .unwrap();
+
+        assert_eq!(
+            schema.columns,
+            vec![
+                Column::new("id", DataType::Integer, false),
+                Column::new("name", DataType::Text(Some(32)), false),
+                Column::new("score", DataType::Real, true),
+                Column::new("avatar", DataType::Blob, true),
+                Column::new("active", DataType::Boolean, true),
+            ]
+        );
+        assert_eq!(
+            schema.to_string(),
+            "id integer not null, name text(32) not null, score real, avatar blob, active boolean"
+        );
+        assert_eq!(Schema::from_str(&schema.to_string()), Ok(schema));
+    }
+
+    #[test]
+    fn error_when_parse_invalid_schema() {
+        assert_eq!(
+            Schema::from_str("name text, id integer"),
+            Err("the first column must be an integer key".to_string())
+        );
+        assert_eq!(
+            Schema::from_str("id integer, name text, name text"),
+            Err("duplicate column name: name".to_string())
+        );
+        assert_eq!(
+            Schema::from_str("id integer, name string"),
+            Err("invalid type 'string'".to_string())
+        );
+        assert_eq!(
+            Schema::from_str("id integer, name"),
+            Err("missing type for column 'name'".to_string())
+        );
+    }
+
+    #[test]
+    fn coerce() {
+        let schema = Schema::from_str(
+            "id integer, name text(4) not null, age integer, score real, avatar blob, active bool",
+        )
+        .unwrap();
+
+        let row = Row::from_str("1 john 20 1.5 abc true").unwrap();
+        let row = schema.coerce(&row).unwrap();
+        assert_eq!(
+            row.values,
+            vec![
+                Value::Text("john".to_string()),
+                Value::Integer(20),
+                Value::Real(1.5),
+                Value::Blob(b"abc".to_vec()),
+                Value::Boolean(true),
+            ]
+        );
+
+        let row = Row::from_str("1 john null NULL null null").unwrap();
+        let row = schema.coerce(&row).unwrap();
+        assert_eq!(row.values[1..], vec![Value::Null; 4]);
+
+        let row = Row::from_str("1 john").unwrap();
+        assert_eq!(schema.coerce(&row).unwrap_err(), "2 values for 6 columns");
+
+        let row = Row::from_str("1 null 20 1.5 abc true").unwrap();
+        assert_eq!(
+            schema.coerce(&row).unwrap_err(),
+            "NOT NULL constraint failed: name"
+        );
+
+        let row = Row::from_str("1 johnny 20 1.5 abc true").unwrap();
+        assert_eq!(schema.coerce(&row).unwrap_err(), "Name is too long.");
+
+        let row = Row::from_str("1 john twenty 1.5 abc true").unwrap();
+        assert_eq!(schema.coerce(&row).unwrap_err(), "invalid age provided");
+
+        let row = Row::from_str("1 john 20 1.5 abc maybe").unwrap();
+        assert_eq!(schema.coerce(&row).unwrap_err(), "invalid active provided");
+    }
+
+    #[test]
+    fn value_index() {
+        let schema = Schema::from_str("id integer, username text, email text").unwrap();
+        assert_eq!(schema.value_index("email"), Ok(1));
+        assert_eq!(
+            schema.value_index("id"),
+            Err("cannot update key column id".to_string())
+        );
+        assert_eq!(
+            schema.value_indexes(&["username".to_string(), "phone".to_string()]),
+            Err("no such column: phone".to_string())
+        );
+    }
+}
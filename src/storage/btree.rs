@@ -0,0 +1,1679 @@
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use tracing::{debug, warn};
+
+use super::node::{Cell, InternalCell, Node, NodeLayout, INTERNAL_NODE_CELL_HEADER_SIZE};
+use super::page_guard::{ReadPageGuard, WritePageGuard};
+use super::{Key, NodeType, Page, Pager, PagerError};
+
+#[derive(PartialEq, Eq)]
+pub enum Operation {
+    Insert,
+    Delete,
+}
+
+// A node of a tree being bulk loaded that is waiting for its parent, with its
+// page id once allocated and the separator between it and the node after it,
+// see BPlusTree::bulk_load.
+type BulkLoadNode = (Option<usize>, Vec<u8>, Node);
+
+#[derive(Debug)]
+pub struct Cursor {
+    pub page_num: usize,
+    pub cell_num: usize,
+    pub key_existed: bool,
+    pub end_of_table: bool,
+}
+
+/// A value of a BPlusTree, encoded into the record of its leaf cell.
+pub trait Record<K>: Sized {
+    fn to_record(&self) -> Vec<u8>;
+
+    /// Decode the record of a leaf cell stored under the given key.
+    fn from_record(key: K, record: &[u8], is_deleted: bool) -> Self;
+
+    /// Whether the value is marked as deleted by a transaction that
+    /// hasn't committed yet, see Page::mark_row_as_deleted.
+    fn is_deleted(&self) -> bool {
+        false
+    }
+}
+
+impl<K> Record<K> for Vec<u8> {
+    fn to_record(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn from_record(_key: K, record: &[u8], _is_deleted: bool) -> Self {
+        record.to_vec()
+    }
+}
+
+// TRADEOFF: Our trees don't own their pages, they are only a root page id
+// borrowing our Pager.
+//
+// Every trees of our database (the catalog and every tables) share the
+// frames of a single buffer pool, so a tree is cheap to build for every
+// operation, e.g. from the root page id of a CatalogEntry. However, nothing
+// prevents two trees with different key or value types from being built on
+// the same root page.
+/// A B+ tree stored in the pages of our Pager, which is only used to fetch,
+/// pin and unpin its pages.
+#[derive(Debug)]
+pub struct BPlusTree<'a, K, V> {
+    pager: &'a Pager,
+    root_page_id: usize,
+    layout: NodeLayout,
+    marker: PhantomData<fn(K) -> V>,
+}
+
+impl<'a, K: Key, V: Record<K>> BPlusTree<'a, K, V> {
+    pub fn new(pager: &'a Pager, root_page_id: usize) -> Self {
+        Self {
+            pager,
+            root_page_id,
+            layout: pager.layout(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Allocate an empty root leaf node for a new tree.
+    pub fn create(pager: &'a Pager) -> Result<Self, PagerError> {
+        let mut page = pager.new_page()?;
+        let page_id = page.page_id.unwrap();
+        page.node = Some(Node::root(pager.layout()));
+        drop(page);
+
+        Ok(Self::new(pager, page_id))
+    }
+
+    pub fn root_page_id(&self) -> usize {
+        self.root_page_id
+    }
+
+    fn value(cell: &Cell) -> V {
+        V::from_record(K::decode(cell.key()), &cell.record(), cell.is_deleted())
+    }
+
+    /// Return all the values of the tree by walking through our leaf nodes
+    /// from left to right.
+    pub fn values(&self) -> Result<Vec<V>, PagerError> {
+        let mut values = Vec::new();
+
+        let mut page = self.search_page(None)?;
+
+        let mut node = page.node.as_ref().unwrap();
+        assert_eq!(node.node_type, NodeType::Leaf);
+
+        if node.num_of_cells == 0 {
+            drop(page);
+            return Ok(values);
+        };
+
+        loop {
+            for cell in &node.cells {
+                values.push(Self::value(cell));
+            }
+
+            if node.next_leaf_offset == 0 {
+                drop(page);
+                break;
+            } else {
+                let page_num = node.next_leaf_offset as usize;
+                drop(page);
+
+                page = self.pager.fetch_scan_page_with_retry(page_num)?;
+                node = page.node.as_ref().unwrap();
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Return the leaf page where the given key is, or our first leaf page
+    /// if None.
+    pub fn search_page(&self, key: Option<K>) -> Result<ReadPageGuard<'a>, PagerError> {
+        // The empty key is ordered before every other keys.
+        let key = key.map_or(Vec::new(), |key| key.encode());
+        let mut page_num = self.root_page_id;
+        loop {
+            let page = self.pager.fetch_read_page_with_retry(page_num)?;
+            let node = page.node.as_ref().unwrap();
+
+            if node.node_type == NodeType::Leaf {
+                return Ok(page);
+            }
+
+            page_num = node.search(&key).unwrap();
+            drop(page);
+        }
+    }
+
+    pub fn get(&self, key: K) -> Result<Option<V>, PagerError> {
+        let key = key.encode();
+        let deadline = self.pager.wait_deadline();
+        let mut parent_page: Option<ReadPageGuard> = None;
+        let mut page_num = self.root_page_id;
+
+        loop {
+            let epoch = self.pager.release_epoch(page_num);
+            let page = match self.pager.fetch_read_page_guard(page_num) {
+                Ok(page) => page,
+                Err(err) => {
+                    // Don't wake up from releasing our own page.
+                    let parent_page_id = parent_page.take().map(|page| page.page_id.unwrap());
+                    let epoch = epoch + self.pager.releases_of(page_num, parent_page_id);
+
+                    self.pager.wait_for_retry(err, page_num, epoch, deadline)?;
+
+                    // Restart at root
+                    page_num = self.root_page_id;
+                    continue;
+                }
+            };
+
+            if let Some(page) = parent_page.take() {
+                drop(page);
+            }
+
+            let node = page.node.as_ref().unwrap();
+            if node.node_type == NodeType::Leaf {
+                let value = node
+                    .search(&key)
+                    .ok()
+                    .map(|index| Self::value(&node.cells[index]));
+                drop(page);
+                return Ok(value);
+            } else if let Ok(next_page_num) = node.search(&key) {
+                page_num = next_page_num;
+                parent_page = Some(page);
+            } else {
+                unreachable!("this shouldn't happen!");
+            }
+        }
+    }
+
+    fn node_to_string(&self, node_index: usize, indent_level: usize) -> String {
+        let page = self.pager.fetch_read_page_guard(node_index).unwrap();
+        let node = page.node.as_ref().unwrap();
+        let mut result = String::new();
+
+        if node.node_type == NodeType::Internal {
+            for _ in 0..indent_level {
+                result += "  ";
+            }
+            result += &format!("- internal (size {})\n", node.num_of_cells);
+            let most_righ_child_index = node.right_child_offset as usize;
+
+            let mut child_pointers = vec![];
+            for c in &node.internal_cells {
+                let child_index = c.child_pointer() as usize;
+                child_pointers.push((child_index, K::decode(c.key())));
+            }
+            drop(page);
+
+            for (i, k) in child_pointers {
+                result += &self.node_to_string(i, indent_level + 1);
+
+                for _ in 0..indent_level + 1 {
+                    result += "  ";
+                }
+                result += &format!("- key {:?}\n", k);
+            }
+
+            result += &self.node_to_string(most_righ_child_index, indent_level + 1);
+        } else if node.node_type == NodeType::Leaf {
+            for _ in 0..indent_level {
+                result += "  ";
+            }
+
+            result += &format!("- leaf (size {})\n", node.num_of_cells);
+            for c in &node.cells {
+                for _ in 0..indent_level + 1 {
+                    result += "  ";
+                }
+                result += &format!("- {:?}\n", K::decode(c.key()));
+            }
+
+            drop(page);
+        }
+
+        result
+    }
+
+    pub fn to_tree_string(&self) -> String {
+        self.node_to_string(self.root_page_id, 0)
+    }
+
+    /// Remove every pages of the tree from our buffer pool.
+    ///
+    /// The caller must ensure that no other threads are accessing the tree
+    /// anymore. E.g. by removing it from the catalog first.
+    pub fn destroy(self) -> Result<(), PagerError> {
+        let mut page_ids = vec![self.root_page_id];
+
+        while let Some(page_id) = page_ids.pop() {
+            let mut page = self.pager.fetch_write_page_guard_with_retry(page_id)?;
+            let node = page.node.as_mut().unwrap();
+
+            if node.node_type == NodeType::Internal {
+                page_ids.push(node.right_child_offset as usize);
+                for cell in &node.internal_cells {
+                    page_ids.push(cell.child_pointer() as usize);
+                }
+            }
+
+            for cell_num in (0..node.cells.len()).rev() {
+                node.delete(cell_num);
+            }
+
+            self.pager.delete_page_with_write_guard(page);
+        }
+
+        Ok(())
+    }
+
+    // ---------------------
+    // Concurrent Operations
+    // ---------------------
+    //
+    // TRADEOFF: Pages allocated or fetched in the middle of splitting nodes
+    // can't be reported as an error, as we have no way to undo the changes
+    // already made to the other nodes, so an error panics there. A sibling
+    // we fail to fetch is not merged with instead, leaving our node
+    // underflowing, see BPlusTree::concurrent_maybe_merge_nodes.
+    fn new_page_or_panic(&self) -> WritePageGuard<'a> {
+        self.pager.new_page().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn fetch_write_page_or_panic(&self, page_id: usize) -> WritePageGuard<'a> {
+        self.pager
+            .fetch_write_page_guard_with_retry(page_id)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn search_and_then<F, T>(
+        &self,
+        key: &[u8],
+        operation: Operation,
+        func: F,
+    ) -> Result<T, PagerError>
+    where
+        F: FnOnce(Cursor, Vec<WritePageGuard<'a>>, WritePageGuard<'a>) -> T,
+    {
+        let deadline = self.pager.wait_deadline();
+        let mut parent_page_guards: Vec<WritePageGuard> = vec![];
+        let mut page_num = self.root_page_id;
+
+        loop {
+            let epoch = self.pager.release_epoch(page_num);
+            let page = match self.pager.fetch_write_page_guard(page_num) {
+                Ok(page) => page,
+                Err(err) => {
+                    // Don't wake up from releasing our own pages.
+                    let parent_page_ids = parent_page_guards
+                        .drain(..)
+                        .map(|page| page.page_id.unwrap());
+                    let epoch = epoch + self.pager.releases_of(page_num, parent_page_ids);
+
+                    self.pager.wait_for_retry(err, page_num, epoch, deadline)?;
+
+                    // Restart at root
+                    page_num = self.root_page_id;
+                    continue;
+                }
+            };
+
+            let node = page.node.as_ref().unwrap();
+            let num_of_cells = node.num_of_cells as usize;
+            let might_split_or_merge = if node.node_type == NodeType::Leaf {
+                // Leaf nodes split and merge based on the space used by
+                // their cells rather than on their number of cells.
+                if operation == Operation::Insert {
+                    node.might_split()
+                } else {
+                    node.might_underflow(key)
+                }
+            } else if operation == Operation::Insert {
+                node.internal_might_split()
+            } else {
+                node.internal_might_underflow()
+            };
+
+            if !might_split_or_merge {
+                parent_page_guards.clear();
+            }
+            if node.node_type == NodeType::Leaf {
+                let (cell_num, key_existed) = match node.search(key) {
+                    Ok(index) => (index, true),
+                    Err(index) => (index, false),
+                };
+                let cursor = Cursor {
+                    page_num,
+                    cell_num,
+                    key_existed,
+                    end_of_table: cell_num == num_of_cells,
+                };
+                return Ok(func(cursor, parent_page_guards, page));
+            } else if let Ok(next_page_num) = node.search(key) {
+                parent_page_guards.push(page);
+                page_num = next_page_num;
+            } else {
+                unreachable!("this shouldn't happen!");
+            }
+        }
+    }
+
+    /// Return where the given key is in our leaf nodes, or where it would be
+    /// inserted.
+    pub fn search(&self, key: K) -> Result<Cursor, PagerError> {
+        self.search_and_then(
+            &key.encode(),
+            Operation::Insert,
+            |cursor, _parent_page_guards, _page| cursor,
+        )
+    }
+
+    /// Insert the value under the given key, and return the page and cell
+    /// it's inserted into.
+    pub fn insert(&self, key: K, value: &V) -> Result<(usize, usize), String> {
+        let key = key.encode();
+        if key.len() > self.layout.max_key_size {
+            return Err(format!(
+                "key is larger than {} bytes",
+                self.layout.max_key_size
+            ));
+        }
+        let cell = Cell::from_record(&key, value.to_record(), value.is_deleted(), &self.layout);
+
+        self.search_and_then(
+            &key,
+            Operation::Insert,
+            |cursor, parent_page_guards, mut page| {
+                if cursor.key_existed {
+                    return None;
+                };
+
+                let node = page.node.as_ref().unwrap();
+
+                // If the node doesn't have enough space left for our cell, inserting
+                // into it cause it to overflow which mean we need to insert and split.
+                //
+                // TRADEOFF: We are only splitting nodes when it's full.
+                //
+                // However, it could be done better by delaying splitting by moving cell
+                // to sibling nodes when necessary, which is called load balancing.
+                //
+                // This result in higher occupancy and delayed of node splitting.
+                if !node.has_space_for_cell(cell.len()) {
+                    self.concurrent_insert_and_split_node(parent_page_guards, page, &cursor, cell);
+                } else {
+                    let node = page.node.as_mut().unwrap();
+                    node.insert_cell(cell, &cursor);
+
+                    drop(parent_page_guards);
+
+                    drop(page);
+                }
+
+                Some((cursor.page_num, cursor.cell_num))
+            },
+        )
+        .map_err(|err| err.to_string())?
+        .ok_or_else(|| "duplicate key".to_string())
+    }
+
+    /// Delete the value of the given key, and return whether it existed.
+    pub fn delete(&self, key: K) -> Result<bool, PagerError> {
+        self.search_and_then(
+            &key.encode(),
+            Operation::Delete,
+            |cursor, parent_page_guards, mut page| {
+                if cursor.key_existed {
+                    let node = page.node.as_mut().unwrap();
+                    node.delete(cursor.cell_num);
+                    self.concurrent_maybe_merge_nodes(page, parent_page_guards);
+                } else {
+                    drop(parent_page_guards);
+
+                    drop(page);
+                }
+
+                cursor.key_existed
+            },
+        )
+    }
+
+    fn concurrent_insert_and_split_node(
+        &self,
+        parent_page_guards: Vec<WritePageGuard>,
+        mut left_page: WritePageGuard,
+        cursor: &Cursor,
+        cell: Cell,
+    ) {
+        let left_node = left_page.node.as_mut().unwrap();
+        left_node.insert_cell(cell, cursor);
+
+        let right_node = left_node.split();
+
+        // The shortest key separating both nodes, rather than the max key of
+        // our left node, keeps our internal cells small.
+        let separator = K::shortest_separator(&left_node.get_max_key(), right_node.cells[0].key());
+
+        if left_node.is_root {
+            // If left node is root it shouldn't have any parent.
+            assert_eq!(parent_page_guards.len(), 0);
+
+            self.concurrent_create_new_root(left_page, right_node, &separator);
+        } else {
+            self.concurrent_split_node_and_update_parent(
+                parent_page_guards,
+                left_page,
+                right_node,
+                &separator,
+            );
+        }
+    }
+
+    fn concurrent_split_node_and_update_parent(
+        &self,
+        mut parent_page_guards: Vec<WritePageGuard>,
+        mut left_page: WritePageGuard,
+        mut right_node: Node,
+        separator: &[u8],
+    ) {
+        let mut right_page = self.new_page_or_panic();
+        let right_page_id = right_page.page_id.unwrap() as u32;
+        let left_page_id = left_page.page_id.unwrap() as u32;
+        let left_node = left_page.node.as_mut().unwrap();
+
+        right_node.next_leaf_offset = left_node.next_leaf_offset;
+        left_node.next_leaf_offset = right_page_id;
+
+        right_node.parent_offset = left_node.parent_offset;
+        drop(left_page);
+
+        right_page.node = Some(right_node);
+        drop(right_page);
+
+        assert!(!parent_page_guards.is_empty());
+        let mut parent_page = parent_page_guards.pop().unwrap();
+        let parent_node = parent_page.node.as_mut().unwrap();
+
+        // Our right node takes over the separator (or the right child offset)
+        // of our left node, which is larger than every keys of our right node,
+        // while our left node gets a new separator inserted before it.
+        //
+        // Hence, splitting a node only ever adds a cell to our parent, which
+        // is accounted for by Node::internal_might_split.
+        let index = parent_node.internal_search_child_pointer(left_page_id);
+        if index == parent_node.internal_cells.len() {
+            parent_node.right_child_offset = right_page_id;
+        } else {
+            parent_node.internal_cells[index].write_child_pointer(right_page_id);
+        }
+        parent_node.internal_insert(index, InternalCell::new(left_page_id, separator));
+        parent_node.num_of_cells += 1;
+
+        self.concurrent_split_internal_node(parent_page, parent_page_guards);
+    }
+
+    fn concurrent_create_new_root(
+        &self,
+        mut page: WritePageGuard,
+        mut right_node: Node,
+        max_key: &[u8],
+    ) {
+        let mut left_page = self.new_page_or_panic();
+        let left_page_id = left_page.page_id.unwrap() as u32;
+
+        let mut right_page = self.new_page_or_panic();
+        let right_page_id = right_page.page_id.unwrap() as u32;
+
+        let root_page_id = page.page_id.unwrap() as u32;
+        let mut root_node = Node::new(true, NodeType::Internal, self.layout);
+        root_node.num_of_cells += 1;
+        root_node.right_child_offset = right_page_id;
+
+        right_node.parent_offset = root_page_id;
+        right_node.next_leaf_offset = 0;
+
+        let mut left_node = page.node.take().unwrap();
+        left_node.is_root = false;
+        left_node.next_leaf_offset = right_page_id;
+        left_node.parent_offset = root_page_id;
+
+        let cell = InternalCell::new(left_page_id, max_key);
+        root_node.internal_cells.insert(0, cell);
+
+        page.node = Some(root_node);
+        left_page.node = Some(left_node);
+        right_page.node = Some(right_node);
+
+        self.concurrent_update_children_parent_offset(&left_page);
+        drop(left_page);
+
+        self.concurrent_update_children_parent_offset(&right_page);
+        drop(right_page);
+        drop(page);
+    }
+
+    // TRADEOFF (Parent pointer):
+    //
+    // Upon parent pointer changes (due to split/merge), we will need to update
+    // all the childrens parent offset. Our internal nodes can store around 500
+    // child pointer. If we were to update around 250 child nodes parent offset
+    // during a split/merge (since only half of the childrens will be move),
+    // the cost of page in/out and potentially disk I/O will add up.
+    fn update_parent_offset(&self, page_id: usize, parent_page_id: usize) {
+        let mut page = self.fetch_write_page_or_panic(page_id);
+        let child = page.node.as_mut().unwrap();
+        child.parent_offset = parent_page_id as u32;
+        drop(page);
+    }
+
+    fn concurrent_update_children_parent_offset(&self, page: &Page) {
+        let node = page.node.as_ref().unwrap();
+        let parent_page_id = page.page_id.unwrap();
+
+        let mut child_pointers = vec![node.right_child_offset as usize];
+        for cell in &node.internal_cells {
+            child_pointers.push(cell.child_pointer() as usize);
+        }
+
+        child_pointers.retain(|&i| i != 0);
+        for i in child_pointers {
+            self.update_parent_offset(i, parent_page_id);
+        }
+    }
+
+    fn concurrent_split_internal_node(
+        &self,
+        mut left_page: WritePageGuard,
+        mut parent_page_guards: Vec<WritePageGuard>,
+    ) {
+        // Check if our internal node need to be split. If its cells still fit into
+        // our page, no split is required.
+        if !left_page.node.as_ref().unwrap().is_internal_overflow() {
+            drop(parent_page_guards);
+
+            drop(left_page);
+
+            return;
+        }
+
+        let left_node = left_page.node.as_mut().unwrap();
+        let split_at_index = left_node.internal_split_index();
+
+        let mut right_node = Node::new(false, NodeType::Internal, self.layout);
+        right_node.right_child_offset = left_node.right_child_offset;
+        right_node.parent_offset = left_node.parent_offset as u32;
+
+        let ic = left_node.internal_cells.remove(split_at_index);
+        left_node.num_of_cells -= 1;
+        left_node.right_child_offset = ic.child_pointer();
+
+        let remaining_len = left_node.num_of_cells as usize - split_at_index;
+        for i in 0..remaining_len {
+            let ic = left_node.internal_cells.remove(split_at_index);
+            left_node.num_of_cells -= 1;
+            right_node.internal_insert(i, ic);
+            right_node.num_of_cells += 1;
+        }
+
+        let left_node = left_page.node.as_ref().unwrap();
+
+        if left_node.is_root {
+            assert_eq!(parent_page_guards.len(), 0);
+            self.concurrent_create_new_root(left_page, right_node, ic.key());
+        } else {
+            let page_num = left_page.page_id.unwrap();
+
+            assert!(!parent_page_guards.is_empty());
+            let mut parent_page = parent_page_guards.pop().unwrap();
+            let parent = parent_page.node.as_mut().unwrap();
+            let index = parent.internal_search_child_pointer(page_num as u32);
+
+            let mut right_page = self.new_page_or_panic();
+            let right_page_id = right_page.page_id.unwrap() as u32;
+            right_page.is_dirty = true;
+            right_page.node = Some(right_node);
+
+            if parent.num_of_cells == index as u32 {
+                parent.right_child_offset = right_page_id;
+                parent.internal_insert(index, InternalCell::new(page_num as u32, ic.key()));
+                parent.num_of_cells += 1;
+            } else {
+                parent.internal_insert(index, InternalCell::new(page_num as u32, ic.key()));
+
+                let internel_cell = parent.internal_cells.remove(index + 1);
+                parent.internal_insert(
+                    index + 1,
+                    InternalCell::new(right_page_id, internel_cell.key()),
+                );
+                parent.num_of_cells += 1;
+            }
+
+            drop(left_page);
+            self.concurrent_update_children_parent_offset(&right_page);
+            drop(right_page);
+
+            self.concurrent_split_internal_node(parent_page, parent_page_guards);
+        }
+    }
+
+    fn concurrent_maybe_merge_nodes(
+        &self,
+        page: WritePageGuard,
+        parent_page_guards: Vec<WritePageGuard>,
+    ) {
+        let node = page.node.as_ref().unwrap();
+
+        // TRADEOFF: We could leave the node to be underflow.
+        //
+        // We avoid load balancing or even merging because we are hoping
+        // for the subsequent insert or defragmentation to resolve it.
+        //
+        // Study has show that, rebalancing on deletion can be considered harmful.
+        if node.node_type == NodeType::Leaf && node.is_underflow() && !node.is_root {
+            return self.concurrent_merge_leaf_nodes(page, parent_page_guards);
+        }
+
+        drop(parent_page_guards);
+
+        drop(page);
+    }
+
+    fn concurrent_merge_leaf_nodes(
+        &self,
+        page: WritePageGuard,
+        mut parent_page_guards: Vec<WritePageGuard>,
+    ) {
+        let page_id = page.page_id.unwrap();
+        let node = page.node.as_ref().unwrap();
+        let node_cells_len = node.cells.len();
+
+        let parent_page = parent_page_guards.pop().unwrap();
+        let parent = parent_page.node.as_ref().unwrap();
+        let (left_child_pointer, right_child_pointer) = parent.siblings(page_id as u32);
+        debug!("-- merge leaf node {page_id}: {left_child_pointer:?}, {right_child_pointer:?}");
+
+        if let Some(cp) = left_child_pointer {
+            if cp != page_id && cp != 0 {
+                let Ok(left_page) = self.pager.fetch_write_page_guard_with_retry(cp) else {
+                    warn!("-- failed to fetch left neighbour {cp} of {page_id}");
+                    return;
+                };
+                let left_nb = left_page.node.as_ref().unwrap();
+
+                // If merging both result does not exceed MAX, proceed
+                if left_nb.can_merge_with(node) {
+                    debug!("-- merge leaf node {} with its left neighbour...", page_id);
+                    return self.concurrent_do_merge_leaf_nodes(
+                        parent_page,
+                        left_page,
+                        page,
+                        parent_page_guards,
+                    );
+                }
+
+                if node_cells_len == 0 {
+                    warn!(
+                        "-- failed to merge leaf {page_id}, len: {} + {} >= MAX",
+                        node_cells_len,
+                        left_nb.cells.len()
+                    );
+                }
+
+                drop(left_page);
+            } else {
+                warn!("-- fail to merge {page_id}, cp: {cp}");
+            }
+        }
+
+        if let Some(cp) = right_child_pointer {
+            if cp != page_id && cp != 0 {
+                let Ok(right_page) = self.pager.fetch_write_page_guard_with_retry(cp) else {
+                    warn!("-- failed to fetch right neighbour {cp} of {page_id}");
+                    return;
+                };
+                let right_nb = right_page.node.as_ref().unwrap();
+
+                if right_nb.can_merge_with(node) {
+                    debug!("-- merge leaf node {} with its right neighbour...", page_id);
+                    return self.concurrent_do_merge_leaf_nodes(
+                        parent_page,
+                        page,
+                        right_page,
+                        parent_page_guards,
+                    );
+                }
+
+                if node_cells_len == 0 {
+                    warn!(
+                        "-- failed to merge leaf {page_id}, len: {} + {} >= MAX",
+                        node_cells_len,
+                        right_nb.cells.len()
+                    );
+                }
+
+                drop(right_page);
+            } else {
+                warn!("-- fail to merge {page_id}, cp: {cp}");
+            }
+        }
+
+        // Drop parent guards lock
+        drop(parent_page_guards);
+
+        drop(parent_page);
+        drop(page);
+    }
+
+    fn concurrent_do_merge_leaf_nodes(
+        &self,
+        mut parent_page: WritePageGuard,
+        mut left_page: WritePageGuard,
+        mut right_page: WritePageGuard,
+        parent_page_guards: Vec<WritePageGuard>,
+    ) {
+        let right_page_id = right_page.page_id.unwrap();
+        let left_page_id = left_page.page_id.unwrap();
+        // Take the node of right page and left page out of page.
+        //
+        // Free up the pages as we don't need it anymore.
+        let left_node = left_page.node.as_mut().unwrap();
+        let right_node = right_page.node.take().unwrap();
+
+        // Merge the leaf nodes cells
+        for c in right_node.cells {
+            left_node.cells.push(c);
+            left_node.num_of_cells += 1;
+        }
+        left_node
+            .freed_overflow_page_ids
+            .extend(right_node.freed_overflow_page_ids);
+        left_node.next_leaf_offset = right_node.next_leaf_offset;
+        let parent = parent_page.node.as_mut().unwrap();
+
+        if parent.num_of_cells == 1 && parent.is_root {
+            self.concurrent_promote_node_to_root(parent_page, left_page, right_page);
+        } else {
+            self.pager.delete_page_with_write_guard(right_page);
+
+            debug!("-- left_page: {:?}", left_page);
+            drop(left_page);
+
+            let index = parent.internal_search_child_pointer(right_page_id as u32);
+            if index == parent.num_of_cells as usize {
+                // The right_cp is our right child offset
+
+                // Move last internal cell to become the right child offset
+                // if parent.num_of_cells > 1 {
+                debug!("update to right child pointer");
+                let internal_cell = parent.internal_cells.remove(index - 1);
+                parent.num_of_cells -= 1;
+                parent.right_child_offset = internal_cell.child_pointer();
+            } else {
+                debug!("remove index");
+                // Remove the cell of our left node, as we now have one less child after
+                // merge. Our merged node takes over the separator of our right node, which
+                // is larger than every keys of both nodes.
+                //
+                // Unlike writing a new separator, removing a cell never grows our parent.
+                parent.num_of_cells -= 1;
+                parent.internal_cells.remove(index - 1);
+                parent.internal_cells[index - 1].write_child_pointer(left_page_id as u32);
+            }
+
+            debug!("-- parent_page: {:?}", parent_page);
+            debug!("-- merge leaf node (end)\n\n");
+            self.concurrent_merge_internal_nodes(parent_page, parent_page_guards)
+        }
+    }
+
+    fn concurrent_promote_node_to_root(
+        &self,
+        mut parent_page: WritePageGuard,
+        mut left_page: WritePageGuard,
+        right_page: WritePageGuard,
+    ) {
+        // Take left node out of left page as it will be used to replace
+        // the node in our parent.
+        let mut left_node = left_page.node.take().unwrap();
+
+        // Replace the parent.node with our new combined left node
+        left_node.is_root = true;
+        left_node.next_leaf_offset = 0;
+        parent_page.node = Some(left_node);
+
+        self.pager.delete_page_with_write_guard(left_page);
+        self.pager.delete_page_with_write_guard(right_page);
+
+        self.concurrent_update_children_parent_offset(&parent_page);
+        debug!("parent_page: {parent_page:?}");
+        debug!("promote node to root (end)\n\n");
+        drop(parent_page);
+    }
+
+    fn concurrent_merge_internal_nodes(
+        &self,
+        page: WritePageGuard,
+        mut parent_page_guards: Vec<WritePageGuard>,
+    ) {
+        let page_id = page.page_id.unwrap();
+        let node = page.node.as_ref().unwrap();
+
+        // Skip merging internal node if it is more than half full. In our tests,
+        // if it has more than 1 cell. If it's equals to, we will still need to merge.
+        if !node.is_internal_underflow() || node.is_root {
+            drop(parent_page_guards);
+
+            drop(page);
+            return;
+        }
+
+        assert!(!parent_page_guards.is_empty());
+        let parent_page = parent_page_guards.pop().unwrap();
+        let parent = parent_page.node.as_ref().unwrap();
+
+        let (left_child_pointer, right_child_pointer) = parent.siblings(page_id as u32);
+        debug!(
+            "-- merge internal page {}: {left_child_pointer:?}, {right_child_pointer:?}",
+            page.page_id.unwrap()
+        );
+
+        if let Some(cp) = left_child_pointer {
+            if cp != page_id && cp != 0 {
+                let Ok(left_page) = self.pager.fetch_write_page_guard_with_retry(cp) else {
+                    warn!("-- failed to fetch left neighbour {cp} of {page_id}");
+                    return;
+                };
+                let left_nb = left_page.node.as_ref().unwrap();
+
+                // Merging pulls down the separator key from our parent, so the merged
+                // node has one more cell than both nodes combined.
+                if left_nb.can_merge_internal_with(node) {
+                    debug!("-- merge internal node {page_id} with left neighbour");
+                    self.concurrent_do_merge_internal_nodes(
+                        parent_page,
+                        left_page,
+                        page,
+                        parent_page_guards,
+                    );
+                    return;
+                }
+
+                self.steal_from_sibling(parent_page, left_page, page, parent_page_guards);
+                return;
+
+                // drop(left_page);
+            } else {
+                warn!("-- failed to merge internal, cp: {cp}");
+            }
+        }
+
+        if let Some(cp) = right_child_pointer {
+            if cp != page_id && cp != 0 {
+                let Ok(right_page) = self.pager.fetch_write_page_guard_with_retry(cp) else {
+                    warn!("-- failed to fetch right neighbour {cp} of {page_id}");
+                    return;
+                };
+                let right_nb = right_page.node.as_ref().unwrap();
+
+                if right_nb.can_merge_internal_with(node) {
+                    debug!("-- merge internal node {page_id} with right neighbour");
+                    self.concurrent_do_merge_internal_nodes(
+                        parent_page,
+                        page,
+                        right_page,
+                        parent_page_guards,
+                    );
+                    return;
+                }
+
+                self.steal_from_sibling(parent_page, page, right_page, parent_page_guards);
+                return;
+
+                // drop(right_page);
+            } else {
+                warn!("-- failed to merge internal, cp: {cp}");
+            }
+        }
+
+        // Drop parent guards lock
+        drop(parent_page_guards);
+
+        drop(page);
+        drop(parent_page);
+    }
+
+    fn steal_from_sibling(
+        &self,
+        mut parent_page: WritePageGuard,
+        mut left_page: WritePageGuard,
+        mut right_page: WritePageGuard,
+        parent_page_guards: Vec<WritePageGuard>,
+    ) {
+        debug!("-- steal from sibling");
+        let left_page_id = left_page.page_id.unwrap();
+        let left_node = left_page.node.as_mut().unwrap();
+        let right_node = right_page.node.as_mut().unwrap();
+        let parent_node = parent_page.node.as_mut().unwrap();
+        let index = parent_node.internal_search_child_pointer(left_page_id as u32);
+
+        // Our separators are rotated through our parent, which only has space
+        // left for a key as long as the one replaced, see Node::internal_might_split.
+        // Otherwise, we leave our node underflowing.
+        let can_steal_from_right = left_node.can_steal_internal_cell()
+            && right_node
+                .internal_cells
+                .first()
+                .is_some_and(|cell| parent_node.has_space_for_internal_key(index, cell.key()));
+        let can_steal_from_left = right_node.can_steal_internal_cell()
+            && left_node
+                .internal_cells
+                .last()
+                .is_some_and(|cell| parent_node.has_space_for_internal_key(index, cell.key()));
+
+        // Left node have less cell so let's steal from our right node.
+        if can_steal_from_right {
+            debug!("-- steal from right");
+            // Get the parent key that's pointing to the left node
+            let parent_key = parent_node.internal_cells[index].key().to_vec();
+
+            // Move the parent key into internal cell and link it to our one and only right child.
+            // It won't be the most right child anymore as we are going to steal our most right
+            // child from our right siblings.
+            let internal_cell = InternalCell::new(left_node.right_child_offset, &parent_key);
+            left_node.internal_cells.push(internal_cell);
+            left_node.num_of_cells += 1;
+
+            // Remove the first internal cell from our right siblings and make it our own
+            // children.
+            let min_internal_cell = right_node.internal_cells.remove(0);
+            let new_most_right_child_page_id = min_internal_cell.child_pointer();
+            right_node.num_of_cells -= 1;
+            left_node.right_child_offset = new_most_right_child_page_id;
+            debug!("-- right_page: {:?}", right_page);
+            drop(right_page);
+
+            // Update our new children parent offset
+            self.update_parent_offset(
+                new_most_right_child_page_id as usize,
+                left_page.page_id.unwrap(),
+            );
+            debug!("-- left_page: {:?}", left_page);
+            drop(left_page);
+
+            // Replace our parent key with the the node key we steal from right sibling.
+            parent_node.internal_cells[index].write_key(min_internal_cell.key());
+            debug!("-- parent_page: {:?}", parent_page);
+            debug!("-- steal sibling (end)\n\n");
+
+            drop(parent_page_guards);
+
+            drop(parent_page);
+            return;
+        }
+
+        // right node have less cell so let's steal from our left node.
+        if can_steal_from_left {
+            debug!("-- steal from left");
+            // Get parent key that point to the left node, since we are stealing from
+            // our left siblings, we will need the key to create the separator key in our internal
+            // cell.
+            let parent_key = parent_node.internal_cells[index].key().to_vec();
+
+            // Create internal cell using parent key, then steal our left siblings most right child
+            // to become our first child.
+            let internal_cell = InternalCell::new(left_node.right_child_offset, &parent_key);
+            right_node.internal_cells.insert(0, internal_cell);
+            right_node.num_of_cells += 1;
+
+            // Update our new child parent offset.
+            self.update_parent_offset(
+                left_node.right_child_offset as usize,
+                right_page.page_id.unwrap(),
+            );
+            debug!("-- right_page: {:?}", right_page);
+            drop(right_page);
+
+            // Remove our left sibling last internal node as now it has one less child, it don't
+            // need the internal node.
+            let max_internal_cell = left_node.internal_cells.pop().unwrap();
+            left_node.num_of_cells -= 1;
+
+            // Point the removed internal cell children as the left sibling most right child.
+            left_node.right_child_offset = max_internal_cell.child_pointer();
+            debug!("-- left_page: {:?}", left_page);
+            drop(left_page);
+
+            // Update parent key to use the key from the removed internal cell.
+            parent_node.internal_cells[index].write_key(max_internal_cell.key());
+
+            debug!("-- parent: {:?}", parent_page);
+            debug!("-- steal sibling (end)\n\n",);
+
+            drop(parent_page_guards);
+
+            drop(parent_page);
+            return;
+        }
+
+        drop(right_page);
+        drop(left_page);
+
+        // Drop parent guards lock
+        drop(parent_page_guards);
+
+        drop(parent_page);
+    }
+
+    fn concurrent_do_merge_internal_nodes(
+        &self,
+        mut parent_page: WritePageGuard,
+        mut left_page: WritePageGuard,
+        mut right_page: WritePageGuard,
+        parent_page_guards: Vec<WritePageGuard>,
+    ) {
+        debug!("-- concurrent do merge internal node");
+        let right_page_id = right_page.page_id.unwrap();
+        let left_page_id = left_page.page_id.unwrap();
+
+        // Pull down the separator of our left node from our parent, which is
+        // larger than every keys of our left node and smaller than every keys
+        // of our right node.
+        let parent = parent_page.node.as_ref().unwrap();
+        let index = parent.internal_search_child_pointer(left_page_id as u32);
+        let separator = parent.internal_cells[index].key().to_vec();
+
+        let left_node = left_page.node.as_mut().unwrap();
+        left_node
+            .internal_cells
+            .push(InternalCell::new(left_node.right_child_offset, &separator));
+        left_node.num_of_cells += 1;
+
+        let right_node = right_page.node.take().unwrap();
+        let left_node = left_page.node.as_mut().unwrap();
+
+        for c in right_node.internal_cells {
+            left_node.internal_cells.push(c);
+            left_node.num_of_cells += 1;
+        }
+        left_node.right_child_offset = right_node.right_child_offset;
+
+        // Update parent metadata
+        let parent = parent_page.node.as_ref().unwrap();
+
+        if parent.num_of_cells == 1 && parent.is_root {
+            assert!(parent_page_guards.is_empty());
+            self.concurrent_promote_node_to_root(parent_page, left_page, right_page);
+        } else {
+            let parent = parent_page.node.as_mut().unwrap();
+            let parent_right_child_offset = parent.right_child_offset as usize;
+
+            parent.internal_cells.remove(index);
+            parent.num_of_cells -= 1;
+
+            if right_page_id == parent_right_child_offset {
+                debug!("  update parent after merging most right child");
+                parent.right_child_offset = left_page_id as u32;
+            } else {
+                debug!("  update parent after merging child");
+                // Our merged node takes over the separator of our right node, so our
+                // parent never grows.
+                parent.internal_cells[index].write_child_pointer(left_page_id as u32);
+            }
+
+            self.pager.delete_page_with_write_guard(right_page);
+
+            self.concurrent_update_children_parent_offset(&left_page);
+            debug!("-- left_page: {left_page:?}");
+            drop(left_page);
+
+            debug!("-- parent_page: {parent_page:?}");
+            debug!("-- concurrent do merge internal node (end)\n\n");
+            self.concurrent_merge_internal_nodes(parent_page, parent_page_guards);
+        }
+    }
+
+    // ------
+    // Vacuum
+    // ------
+
+    /// Return every entries of the tree in the order of their keys, reading
+    /// a single leaf node at a time, see Catalog::vacuum.
+    pub fn entries(&self) -> impl Iterator<Item = Result<(K, V), PagerError>> + '_ {
+        let mut next_page_id = None;
+        let mut leaf = VecDeque::new();
+        let mut done = false;
+
+        std::iter::from_fn(move || loop {
+            if let Some(entry) = leaf.pop_front() {
+                return Some(Ok(entry));
+            }
+            if done {
+                return None;
+            }
+
+            let page = match next_page_id {
+                None => self.search_page(None),
+                Some(page_id) => self.pager.fetch_scan_page_with_retry(page_id),
+            };
+            let page = match page {
+                Ok(page) => page,
+                Err(err) => {
+                    done = true;
+                    return Some(Err(err));
+                }
+            };
+
+            let node = page.node.as_ref().unwrap();
+            leaf.extend(
+                node.cells
+                    .iter()
+                    .map(|cell| (K::decode(cell.key()), Self::value(cell))),
+            );
+            done = node.next_leaf_offset == 0;
+            next_page_id = Some(node.next_leaf_offset as usize);
+        })
+    }
+
+    // TRADEOFF: Our bulk load only keeps a few nodes per level in memory.
+    //
+    // A node is only written once its parent is known, so every level holds
+    // back the children of up to two parents, and the last two nodes of every
+    // level can still be balanced once our entries run out. Pages are
+    // allocated as nodes are written, so the leaf nodes of our tree are mostly,
+    // but not always, contiguous.
+    /// Build our tree from the entries sorted by key bottom-up, packing as many
+    /// entries into a leaf node as possible, and write it directly to disk,
+    /// dropping the deleted values, see Catalog::vacuum.
+    ///
+    /// Our root page id must be reserved by the caller, while our other pages
+    /// are allocated from our Pager, see Pager::reset_page_allocation.
+    pub fn bulk_load(
+        &self,
+        entries: impl IntoIterator<Item = Result<(K, V), PagerError>>,
+    ) -> Result<(), PagerError> {
+        let mut levels = vec![VecDeque::new()];
+        let mut leaf = Node::new(false, NodeType::Leaf, self.layout);
+        for entry in entries {
+            let (key, value) = entry?;
+            if value.is_deleted() {
+                continue;
+            }
+
+            let cell = Cell::from_record(&key.encode(), value.to_record(), false, &self.layout);
+            if !leaf.has_space_for_cell(cell.len()) {
+                let full =
+                    std::mem::replace(&mut leaf, Node::new(false, NodeType::Leaf, self.layout));
+                // Like concurrent_insert_and_split_node, our leaf nodes are
+                // separated by the shortest keys between them.
+                let separator = K::shortest_separator(full.cells.last().unwrap().key(), cell.key());
+                self.push_bulk_load_node(&mut levels, 0, (None, separator, full))?;
+            }
+
+            leaf.cells.push(cell);
+            leaf.num_of_cells += 1;
+        }
+
+        let separator = leaf
+            .cells
+            .last()
+            .map_or(Vec::new(), |cell| cell.key().to_vec());
+        self.push_bulk_load_node(&mut levels, 0, (None, separator, leaf))?;
+
+        for level in 0.. {
+            let is_top_level = level + 1 == levels.len();
+            if is_top_level && levels[level].len() == 1 {
+                let (_, _, mut root) = levels[level].pop_front().unwrap();
+                root.is_root = true;
+                return self.pager.write_vacuum_page(self.root_page_id, root);
+            }
+
+            // Avoid leaving an underflow leaf node at the end of our tree by
+            // splitting the cells of the last two leaf nodes evenly.
+            let nodes = &mut levels[level];
+            if nodes.back().unwrap().2.node_type == NodeType::Leaf
+                && nodes.back().unwrap().2.is_underflow()
+            {
+                let (page_id, separator, mut last) = nodes.pop_back().unwrap();
+                let (_, left_separator, left) = nodes.back_mut().unwrap();
+                left.cells.append(&mut last.cells);
+                let right = left.split();
+                *left_separator =
+                    K::shortest_separator(left.cells.last().unwrap().key(), right.cells[0].key());
+                nodes.push_back((page_id, separator, right));
+            }
+
+            // Spread the nodes left evenly between one or two parents, so none
+            // of them underflow.
+            let num_of_nodes = nodes.len();
+            let mut first_group = self.bulk_load_group_len(nodes.iter());
+            if first_group == num_of_nodes {
+                self.write_bulk_load_group(&mut levels, level, num_of_nodes, is_top_level)?;
+            } else {
+                first_group = first_group.min(num_of_nodes / 2);
+                while self.bulk_load_group_len(nodes.iter().skip(first_group))
+                    < num_of_nodes - first_group
+                {
+                    first_group += 1;
+                }
+                self.write_bulk_load_group(&mut levels, level, first_group, false)?;
+                let rest = levels[level].len();
+                self.write_bulk_load_group(&mut levels, level, rest, false)?;
+            }
+        }
+
+        unreachable!("our tree always ends with a root node")
+    }
+
+    /// Add a node to its level, writing the nodes at the front of the level
+    /// under a new parent once the rest still needs two parents of their own.
+    fn push_bulk_load_node(
+        &self,
+        levels: &mut Vec<VecDeque<BulkLoadNode>>,
+        level: usize,
+        node: BulkLoadNode,
+    ) -> Result<(), PagerError> {
+        levels[level].push_back(node);
+
+        loop {
+            let nodes = &levels[level];
+            let first_group = self.bulk_load_group_len(nodes.iter());
+            if self.bulk_load_group_len(nodes.iter().skip(first_group)) == nodes.len() - first_group
+            {
+                return Ok(());
+            }
+
+            self.write_bulk_load_group(levels, level, first_group, false)?;
+        }
+    }
+
+    /// The number of nodes at the front of a level that fit under a single
+    /// parent, leaving room for their separators before prefix compression.
+    fn bulk_load_group_len<'n>(&self, nodes: impl Iterator<Item = &'n BulkLoadNode>) -> usize {
+        let mut group_len = 0;
+        let mut space = 0;
+        let mut previous_separator: Option<&[u8]> = None;
+        for (_, separator, _) in nodes {
+            // The last node of a group is the right child of its parent, so
+            // a separator only goes to our parent once a node follows it.
+            if let Some(previous_separator) = previous_separator {
+                space += INTERNAL_NODE_CELL_HEADER_SIZE + previous_separator.len();
+                if group_len > self.layout.internal_max_cells
+                    || space > self.layout.internal_space_for_cells
+                {
+                    break;
+                }
+            }
+
+            group_len += 1;
+            previous_separator = Some(separator);
+        }
+
+        group_len
+    }
+
+    /// Write the nodes at the front of a level under a new parent, which is
+    /// added to the level above.
+    fn write_bulk_load_group(
+        &self,
+        levels: &mut Vec<VecDeque<BulkLoadNode>>,
+        level: usize,
+        group_len: usize,
+        is_root: bool,
+    ) -> Result<(), PagerError> {
+        let mut children: Vec<BulkLoadNode> = levels[level].drain(..group_len).collect();
+        for (page_id, _, _) in children.iter_mut() {
+            page_id.get_or_insert_with(|| self.pager.allocate_page_id());
+        }
+        // Our last leaf node points to the first node of the next group.
+        let next_leaf_page_id = levels[level].front_mut().map_or(0, |(page_id, _, _)| {
+            *page_id.get_or_insert_with(|| self.pager.allocate_page_id()) as u32
+        });
+        let parent_page_id = if is_root {
+            self.root_page_id
+        } else {
+            self.pager.allocate_page_id()
+        };
+
+        let page_ids: Vec<usize> = children
+            .iter()
+            .map(|(page_id, ..)| page_id.unwrap())
+            .collect();
+        let mut parent = Node::new(false, NodeType::Internal, self.layout);
+        let mut parent_separator = Vec::new();
+        for (i, (_, separator, mut child)) in children.into_iter().enumerate() {
+            if i + 1 == group_len {
+                parent.right_child_offset = page_ids[i] as u32;
+            } else {
+                parent
+                    .internal_cells
+                    .push(InternalCell::new(page_ids[i] as u32, &separator));
+            }
+
+            if child.node_type == NodeType::Leaf {
+                child.next_leaf_offset = page_ids
+                    .get(i + 1)
+                    .map_or(next_leaf_page_id, |&page_id| page_id as u32);
+            }
+            child.parent_offset = parent_page_id as u32;
+            self.pager.write_vacuum_page(page_ids[i], child)?;
+            parent_separator = separator;
+        }
+        parent.num_of_cells = parent.internal_cells.len() as u32;
+
+        if levels.len() == level + 1 {
+            levels.push(VecDeque::new());
+        }
+        self.push_bulk_load_node(
+            levels,
+            level + 1,
+            (Some(parent_page_id), parent_separator, parent),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::row::{Row, Value};
+    use crate::storage::PagerOptions;
+
+    #[test]
+    fn build_trees_with_different_values_on_one_pager() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let rows = BPlusTree::<u64, Row>::create(&pager).unwrap();
+        let blobs = BPlusTree::<u32, Vec<u8>>::create(&pager).unwrap();
+        let blob = |i: u32| i.to_be_bytes().repeat(i as usize);
+
+        for i in 1..=50 {
+            let row = Row::new(i, vec![Value::Text(format!("user{i}"))]);
+            assert!(rows.insert(row.id, &row).is_ok());
+            assert!(blobs.insert(i as u32, &blob(i as u32)).is_ok());
+        }
+        let result = rows.insert(1, &Row::new(1, vec![]));
+        assert_eq!(result, Err("duplicate key".to_string()));
+
+        for i in (1..=50).step_by(2) {
+            assert!(blobs.delete(i).unwrap());
+        }
+        assert!(!blobs.delete(1).unwrap());
+
+        let user7 = Row::new(7, vec![Value::Text("user7".to_string())]);
+        assert_eq!(rows.get(7).unwrap(), Some(user7));
+        assert_eq!(rows.values().unwrap().len(), 50);
+        let expected: Vec<Vec<u8>> = (2..=50).step_by(2).map(blob).collect();
+        assert_eq!(blobs.get(3).unwrap(), None);
+        assert_eq!(blobs.values().unwrap(), expected);
+
+        // Both trees are read back from their root page.
+        pager.flush_all_pages().unwrap();
+        let (rows_root_page_id, blobs_root_page_id) = (rows.root_page_id(), blobs.root_page_id());
+        drop(pager);
+
+        let pager = setup_test_pager();
+        let rows = BPlusTree::<u64, Row>::new(&pager, rows_root_page_id);
+        let blobs = BPlusTree::<u32, Vec<u8>>::new(&pager, blobs_root_page_id);
+        assert_eq!(rows.values().unwrap().len(), 50);
+        assert_eq!(blobs.values().unwrap(), expected);
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn build_trees_with_composite_and_signed_keys() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let tenants = BPlusTree::<(u32, i64), Vec<u8>>::create(&pager).unwrap();
+        let balances = BPlusTree::<i64, Vec<u8>>::create(&pager).unwrap();
+
+        // Keys are inserted out of order, so our nodes have to compare them.
+        for id in (-40..40).rev() {
+            for tenant_id in [2, 1] {
+                let value = format!("{tenant_id}:{id}").into_bytes();
+                assert!(tenants.insert((tenant_id, id), &value).is_ok());
+            }
+            assert!(balances
+                .insert(id << 40, &id.to_string().into_bytes())
+                .is_ok());
+        }
+        assert!(tenants.insert((1, -40), &vec![]).is_err());
+
+        for id in (-40..40).step_by(3) {
+            assert!(tenants.delete((2, id)).unwrap());
+        }
+
+        assert_eq!(tenants.get((1, -7)).unwrap(), Some(b"1:-7".to_vec()));
+        assert_eq!(tenants.get((2, -40)).unwrap(), None);
+        let expected: Vec<Vec<u8>> = [1, 2]
+            .into_iter()
+            .flat_map(|tenant_id| (-40..40).map(move |id| (tenant_id, id)))
+            .filter(|&(tenant_id, id)| tenant_id == 1 || (id + 40) % 3 != 0)
+            .map(|(tenant_id, id)| format!("{tenant_id}:{id}").into_bytes())
+            .collect();
+        assert_eq!(tenants.values().unwrap(), expected);
+
+        // Negative keys are ordered before positive ones.
+        assert!(balances.search(-1 << 40).unwrap().key_existed);
+        let expected: Vec<Vec<u8>> = (-40..40).map(|id| id.to_string().into_bytes()).collect();
+        assert_eq!(balances.values().unwrap(), expected);
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn build_trees_with_string_and_binary_keys() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let users = BPlusTree::<String, Vec<u8>>::create(&pager).unwrap();
+        let blobs = BPlusTree::<Vec<u8>, Vec<u8>>::create(&pager).unwrap();
+
+        // Usernames share a long prefix, which is compressed in our internal
+        // nodes, while their separators are truncated.
+        let username = |id: u32| format!("{}-{id:04}", "user".repeat(8));
+        for id in (0..400).rev() {
+            let value = id.to_string().into_bytes();
+            assert!(users.insert(username(id), &value).is_ok());
+            assert!(blobs
+                .insert(vec![0, (id % 7) as u8, 0, id as u8], &value)
+                .is_ok());
+        }
+        assert!(users.insert(username(0), &vec![]).is_err());
+        assert!(users
+            .insert("x".repeat(pager.layout().max_key_size + 1), &vec![])
+            .is_err());
+        assert!(users.to_tree_string().lines().count() > 1);
+
+        for id in (0..400).step_by(3) {
+            assert!(users.delete(username(id)).unwrap());
+        }
+        assert!(!users.delete(username(0)).unwrap());
+
+        assert_eq!(users.get(username(7)).unwrap(), Some(b"7".to_vec()));
+        assert_eq!(users.get(username(9)).unwrap(), None);
+        let expected: Vec<Vec<u8>> = (0..400)
+            .filter(|id| id % 3 != 0)
+            .map(|id| id.to_string().into_bytes())
+            .collect();
+        assert_eq!(users.values().unwrap(), expected);
+
+        for id in (0..400).filter(|id| id % 3 != 0) {
+            assert!(users.delete(username(id)).unwrap());
+        }
+        assert!(users.values().unwrap().is_empty());
+
+        // Bytes are ordered byte by byte, with 0x00 bytes escaped.
+        assert_eq!(blobs.get(vec![0, 3, 0, 10]).unwrap(), Some(b"10".to_vec()));
+        let mut expected: Vec<(u8, u32)> = (0..400).map(|id| ((id % 7) as u8, id)).collect();
+        expected.sort_by_key(|&(group, id)| (group, id as u8));
+        let expected: Vec<Vec<u8>> = expected
+            .into_iter()
+            .map(|(_, id)| id.to_string().into_bytes())
+            .collect();
+        assert_eq!(blobs.values().unwrap(), expected);
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn bulk_load_trees_into_a_vacuumed_database() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let rows = BPlusTree::<u64, Row>::create(&pager).unwrap();
+        for i in 1..=200 {
+            let row = Row::new(i, vec![Value::Text("a".repeat(100))]);
+            assert!(rows.insert(row.id, &row).is_ok());
+        }
+        let large_row = Row::new(
+            201,
+            vec![Value::Blob(vec![7; 3 * pager.layout().page_size])],
+        );
+        assert!(rows.insert(large_row.id, &large_row).is_ok());
+        for i in (1..=200).filter(|i| i % 4 != 0) {
+            rows.delete(i).unwrap();
+        }
+        let users = BPlusTree::<String, Vec<u8>>::create(&pager).unwrap();
+        let username = |id: u32| format!("user{id:04}");
+        for id in 0..300 {
+            assert!(users
+                .insert(username(id), &id.to_string().into_bytes())
+                .is_ok());
+        }
+        pager.flush_all_pages().unwrap();
+        let page_count = pager.header().page_count;
+
+        // Rows left marked as deleted (e.g. by a crashed transaction) are
+        // dropped as well.
+        let cursor = rows.search(4).unwrap();
+        let mut page = pager.fetch_write_page_guard(cursor.page_num).unwrap();
+        page.mark_row_as_deleted(cursor.cell_num);
+        drop(page);
+        let expected_rows = rows.values().unwrap();
+        assert!(expected_rows[0].is_deleted);
+
+        let catalog_root = pager.catalog_root_page_id();
+        let vacuumed = pager.create_vacuum_database().unwrap();
+        vacuumed.reset_page_allocation(3);
+        BPlusTree::<u64, Row>::new(&vacuumed, catalog_root)
+            .bulk_load([])
+            .unwrap();
+        BPlusTree::<u64, Row>::new(&vacuumed, catalog_root + 1)
+            .bulk_load(rows.entries())
+            .unwrap();
+        BPlusTree::<String, Vec<u8>>::new(&vacuumed, catalog_root + 2)
+            .bulk_load(users.entries())
+            .unwrap();
+        pager.replace_with(vacuumed).unwrap();
+        let rows = BPlusTree::<u64, Row>::new(&pager, catalog_root + 1);
+        let users = BPlusTree::<String, Vec<u8>>::new(&pager, catalog_root + 2);
+
+        let header = pager.header();
+        assert!(header.page_count < page_count);
+        assert_eq!(header.freelist_count, 0);
+        let file_len = std::fs::metadata(format!("test-{:?}.db", std::thread::current().id()))
+            .unwrap()
+            .len();
+        assert_eq!(
+            file_len,
+            header.page_count as u64 * pager.layout().page_size as u64
+        );
+        assert_eq!(rows.values().unwrap(), expected_rows[1..]);
+        assert_eq!(users.get(username(42)).unwrap(), Some(b"42".to_vec()));
+
+        // The rebuilt trees are still valid B+ trees after reopening them.
+        let pager = setup_test_pager();
+        let rows = BPlusTree::<u64, Row>::new(&pager, catalog_root + 1);
+        assert_eq!(rows.values().unwrap(), expected_rows[1..]);
+        for row in &expected_rows[1..] {
+            rows.delete(row.id).unwrap();
+        }
+        assert_eq!(rows.values().unwrap(), vec![]);
+        for row in &expected_rows {
+            assert!(rows.insert(row.id, row).is_ok());
+        }
+        assert_eq!(rows.values().unwrap(), expected_rows);
+
+        let users = BPlusTree::<String, Vec<u8>>::new(&pager, catalog_root + 2);
+        for id in (0..300).step_by(2) {
+            assert!(users.delete(username(id)).unwrap());
+        }
+        let expected: Vec<Vec<u8>> = (0..300)
+            .filter(|id| id % 2 != 0)
+            .map(|id| id.to_string().into_bytes())
+            .collect();
+        assert_eq!(users.values().unwrap(), expected);
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn bulk_load_a_tree_of_several_levels() {
+        cleanup_test_db_file();
+        let options = PagerOptions {
+            page_size: 512,
+            ..PagerOptions::default()
+        };
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let pager = Pager::open_with_options(&file, 8, options).unwrap();
+        let tree = BPlusTree::<u32, Vec<u8>>::new(&pager, pager.catalog_root_page_id());
+        pager.reset_page_allocation(1);
+        let value = |i: u32| i.to_string().into_bytes();
+        tree.bulk_load((0..20000).map(|i| Ok((i, value(i)))))
+            .unwrap();
+
+        // Every nodes point to their parent, and only our root is allowed to
+        // go below the minimum number of cells, even at the end of every level.
+        let mut height = 0;
+        let mut level = vec![(tree.root_page_id(), 0)];
+        while !level.is_empty() {
+            height += 1;
+            let mut children = Vec::new();
+            for (page_id, parent_page_id) in level {
+                let page = pager.fetch_read_page_guard(page_id).unwrap();
+                let node = page.node.as_ref().unwrap();
+                assert_eq!(node.is_root, page_id == tree.root_page_id());
+                assert_eq!(node.parent_offset as usize, parent_page_id);
+                if node.node_type == NodeType::Internal {
+                    assert!(node.is_root || node.internal_cells.len() >= node.internal_min_cells());
+                    for cell in &node.internal_cells {
+                        children.push((cell.child_pointer() as usize, page_id));
+                    }
+                    children.push((node.right_child_offset as usize, page_id));
+                } else {
+                    assert!(node.is_root || !node.is_underflow());
+                }
+            }
+            level = children;
+        }
+        assert!(height > 2);
+
+        let expected: Vec<Vec<u8>> = (0..20000).map(value).collect();
+        assert_eq!(tree.values().unwrap(), expected);
+        assert_eq!(tree.get(12345).unwrap(), Some(value(12345)));
+        for i in (0..20000).step_by(2) {
+            assert!(tree.delete(i).unwrap());
+        }
+        assert!(tree.insert(20000, &value(20000)).is_ok());
+        let expected: Vec<Vec<u8>> = (1..=20000)
+            .step_by(2)
+            .map(value)
+            .chain([value(20000)])
+            .collect();
+        assert_eq!(tree.values().unwrap(), expected);
+
+        cleanup_test_db_file();
+    }
+
+    fn setup_test_pager() -> Pager {
+        Pager::new(format!("test-{:?}.db", std::thread::current().id()), 8)
+    }
+
+    fn cleanup_test_db_file() {
+        let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+    }
+}
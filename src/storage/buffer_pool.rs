@@ -0,0 +1,365 @@
+use parking_lot::{Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use super::frames::Frames;
+use super::page::Page;
+use super::replacer::{ReplacementPolicy, Replacer};
+
+// TRADEOFF: Similar to the ParallelBufferPoolManager of BusTub, our buffer
+// pool is split into independent instances, and a page always goes to the
+// instance of its page id modulo the number of instances.
+//
+// Fetching a page only locks the page table, free list and replacer of its
+// instance, so threads working on different pages rarely wait for each other.
+// However, a page can't be placed into the frames of another instance, so an
+// instance might evict its pages while the others still have free frames.
+// Since latch crabbing pins a few pages at once, and every page of a
+// traversal might end up in the same instance, every instance also needs
+// enough frames for a whole traversal, see MIN_INSTANCE_FRAMES.
+//
+// Hence, our buffer pool is only split once every instance gets plenty of
+// frames, e.g. a buffer pool of a few frames keeps a single instance.
+const DEFAULT_INSTANCE_FRAMES: usize = 64;
+const MAX_DEFAULT_BUFFER_POOL_INSTANCES: usize = 16;
+
+// The number of levels our trees reach in practice, e.g. 4 levels hold
+// hundreds of millions of rows with our default page size.
+const MAX_TREE_HEIGHT: usize = 4;
+// Latch crabbing pins a node together with its sibling or its new node on
+// every level that might split or merge.
+const LATCH_CRABBING_PINS: usize = 2;
+
+/// The number of frames every instance of a split buffer pool needs, so a
+/// single operation always finds its frames even if every page it pins goes
+/// to the same instance.
+///
+/// A buffer pool with a single instance is only expected to have a frame,
+/// so a small one can still be chosen, e.g. to test our waits for frames.
+pub const MIN_INSTANCE_FRAMES: usize = MAX_TREE_HEIGHT * LATCH_CRABBING_PINS;
+
+/// The number of instances our buffer pool is split into unless chosen, see
+/// PagerOptions::buffer_pool_instances.
+pub fn default_buffer_pool_instances(pool_size: usize) -> usize {
+    (pool_size / DEFAULT_INSTANCE_FRAMES).clamp(1, MAX_DEFAULT_BUFFER_POOL_INSTANCES)
+}
+
+/// The minimum number of frames of a buffer pool split into the given
+/// number of instances, see MIN_INSTANCE_FRAMES.
+pub fn min_pool_size(num_of_instances: usize) -> usize {
+    if num_of_instances == 1 {
+        1
+    } else {
+        num_of_instances * MIN_INSTANCE_FRAMES
+    }
+}
+
+/// One of the instances of our buffer pool, see Pager::instance.
+#[derive(Debug)]
+pub(super) struct BufferPoolInstance {
+    pub pages: Frames,
+    // The number of frames of this instance. Our frames from this index
+    // onwards are removed by Pager::resize.
+    pool_size: AtomicUsize,
+    // Indexes in our `pages` that are "free", which mean
+    // it is uninitialize.
+    pub free_list: Mutex<Vec<usize>>,
+    // Mapping page id to frame id
+    pub page_table: RwLock<HashMap<usize, usize>>,
+    pub replacer: Box<dyn Replacer>,
+    pub releases: ReleaseQueue,
+}
+
+impl BufferPoolInstance {
+    pub fn new(pool_size: usize, replacement_policy: ReplacementPolicy) -> Self {
+        Self {
+            pages: Frames::new(pool_size),
+            pool_size: AtomicUsize::new(pool_size),
+            free_list: Mutex::new((0..pool_size).rev().collect()),
+            page_table: RwLock::new(HashMap::new()),
+            replacer: replacement_policy.replacer(pool_size),
+            releases: ReleaseQueue::default(),
+        }
+    }
+
+    pub fn pool_size(&self) -> usize {
+        self.pool_size.load(Ordering::Acquire)
+    }
+
+    /// Update our pool size together with our free list, so frames beyond
+    /// our pool size are never free, and empty frames within it always are.
+    pub fn set_pool_size(&self, pool_size: usize) {
+        let page_table = self.page_table.read();
+        let mut free_list = self.free_list.lock();
+        let old_size = self.pool_size.swap(pool_size, Ordering::AcqRel);
+        if pool_size < old_size {
+            free_list.retain(|&frame_id| frame_id < pool_size);
+        } else {
+            // The frames that weren't emptied by a failed resize are still
+            // in our page table.
+            for frame_id in (old_size..pool_size).rev() {
+                if !page_table.values().any(|&used| used == frame_id) {
+                    free_list.push(frame_id);
+                }
+            }
+        }
+    }
+
+    /// Let our replacer evict a frame once its page is no longer pinned.
+    pub fn unpin_frame(&self, frame_id: usize, page: &Page) {
+        if page.is_cold {
+            self.replacer.unpin_cold(frame_id);
+        } else {
+            self.replacer.unpin(frame_id);
+        }
+    }
+
+    /// Give a frame back to our free list, unless it's removed from our
+    /// buffer pool.
+    pub fn free_frame(&self, frame_id: usize) {
+        let mut free_list = self.free_list.lock();
+        if frame_id < self.pool_size() {
+            free_list.push(frame_id);
+        }
+    }
+
+    /// Latch a frame for reading without pinning its page, see FrameLatch.
+    pub fn read_frame(&self, frame_id: usize) -> FrameLatch<'_, RwLockReadGuard<'_, Page>> {
+        FrameLatch::new(self, self.pages.get(frame_id).unwrap().read())
+    }
+
+    pub fn try_read_frame(
+        &self,
+        frame_id: usize,
+    ) -> Option<FrameLatch<'_, RwLockReadGuard<'_, Page>>> {
+        let latch = self.pages.get(frame_id).unwrap().try_read()?;
+        Some(FrameLatch::new(self, latch))
+    }
+
+    /// Latch a frame for writing without pinning its page, see FrameLatch.
+    pub fn write_frame(&self, frame_id: usize) -> FrameLatch<'_, RwLockWriteGuard<'_, Page>> {
+        FrameLatch::new(self, self.pages.get(frame_id).unwrap().write())
+    }
+
+    pub fn try_write_frame(
+        &self,
+        frame_id: usize,
+    ) -> Option<FrameLatch<'_, RwLockWriteGuard<'_, Page>>> {
+        let latch = self.pages.get(frame_id).unwrap().try_write()?;
+        Some(FrameLatch::new(self, latch))
+    }
+}
+
+/// A frame latched without pinning its page, e.g. to write it to disk.
+///
+/// Like our page guards, the threads that failed to fetch the page while
+/// it was latched are woken up once it's dropped.
+pub(super) struct FrameLatch<'a, G> {
+    instance: &'a BufferPoolInstance,
+    // Only taken when the latch is dropped.
+    latch: Option<G>,
+}
+
+impl<'a, G> FrameLatch<'a, G> {
+    fn new(instance: &'a BufferPoolInstance, latch: G) -> Self {
+        Self {
+            instance,
+            latch: Some(latch),
+        }
+    }
+}
+
+impl<G: Deref<Target = Page>> Deref for FrameLatch<'_, G> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.latch.as_ref().unwrap()
+    }
+}
+
+impl<G: DerefMut<Target = Page>> DerefMut for FrameLatch<'_, G> {
+    fn deref_mut(&mut self) -> &mut Page {
+        self.latch.as_mut().unwrap()
+    }
+}
+
+impl<G> Drop for FrameLatch<'_, G> {
+    fn drop(&mut self) {
+        drop(self.latch.take());
+        self.instance.releases.notify();
+    }
+}
+
+// TRADEOFF: Every instance of our buffer pool has its own queue of threads
+// waiting for one of its frames or page latches, and a release only wakes up
+// a single one of them.
+//
+// A thread waits on the instance of the page it failed to fetch, as only the
+// pages of that instance can free a frame for it or release the latch it
+// needs. Waking up every thread on every release made them all fight for the
+// one frame that was released, and made every unpin take a global lock.
+//
+// In exchange, a thread woken up might fail again (e.g. it waits for another
+// latch than the one released), while others that could make progress keep
+// sleeping. Such a thread passes the release on to the next thread that
+// didn't try again since, which costs a wake-up per waiting thread in the
+// worst case, but never leaves a thread sleeping after a release it missed.
+
+/// The threads waiting for a page of an instance of our buffer pool to be
+/// released, see Pager::wait_for_retry.
+#[derive(Debug, Default)]
+pub(super) struct ReleaseQueue {
+    // Incremented whenever a page latch of our instance is released or one
+    // of its frames becomes free, so the threads waiting for it know when
+    // to try again.
+    epoch: AtomicU64,
+    // The number of waiting threads for every epoch they last saw, so the
+    // threads that didn't try again since the last release are known.
+    waiting: Mutex<BTreeMap<u64, usize>>,
+    num_of_waiters: AtomicUsize,
+    released: Condvar,
+}
+
+impl ReleaseQueue {
+    /// The number of releases so far, to be taken before fetching a page.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    /// Wake up a thread waiting for a page, if any.
+    pub fn notify(&self) {
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+
+        // A waiting thread is counted before checking the epoch, so either
+        // it sees ours, or we see it and take the lock once it's waiting.
+        if self.num_of_waiters.load(Ordering::SeqCst) > 0 {
+            let waiting = self.waiting.lock();
+            self.pass_on(&waiting);
+        }
+    }
+
+    /// Block until a page is released after the given epoch, and return
+    /// false if the deadline passed first.
+    pub fn wait(&self, epoch: u64, deadline: Option<Instant>) -> bool {
+        let mut waiting = self.waiting.lock();
+        // We might have been woken up by a release we failed to use.
+        self.pass_on(&waiting);
+        *waiting.entry(epoch).or_default() += 1;
+        self.num_of_waiters.fetch_add(1, Ordering::SeqCst);
+
+        let is_released = loop {
+            if self.epoch() > epoch {
+                break true;
+            }
+            let timed_out = match deadline {
+                Some(deadline) => self.released.wait_until(&mut waiting, deadline).timed_out(),
+                None => {
+                    self.released.wait(&mut waiting);
+                    false
+                }
+            };
+            if timed_out {
+                break self.epoch() > epoch;
+            }
+            if self.epoch() <= epoch {
+                // Woken up by a release we already tried again after.
+                self.pass_on(&waiting);
+            }
+        };
+
+        self.num_of_waiters.fetch_sub(1, Ordering::SeqCst);
+        if let Some(count) = waiting.get_mut(&epoch) {
+            *count -= 1;
+            if *count == 0 {
+                waiting.remove(&epoch);
+            }
+        }
+        if !is_released {
+            // Our deadline might have passed right as we were woken up.
+            self.pass_on(&waiting);
+        }
+
+        is_released
+    }
+
+    /// Wake up the next thread if some didn't try again since the last
+    /// release.
+    fn pass_on(&self, waiting: &BTreeMap<u64, usize>) {
+        if waiting
+            .first_key_value()
+            .is_some_and(|(&seen, _)| seen < self.epoch())
+        {
+            self.released.notify_one();
+        }
+    }
+}
+
+/// Split the frames of our buffer pool evenly between its instances.
+pub(super) fn instance_pool_sizes(pool_size: usize, num_of_instances: usize) -> Vec<usize> {
+    (0..num_of_instances)
+        .map(|i| pool_size / num_of_instances + usize::from(i < pool_size % num_of_instances))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn split_frames_evenly_between_instances() {
+        assert_eq!(instance_pool_sizes(8, 1), vec![8]);
+        assert_eq!(instance_pool_sizes(8, 4), vec![2, 2, 2, 2]);
+        assert_eq!(instance_pool_sizes(10, 4), vec![3, 3, 2, 2]);
+    }
+
+    #[test]
+    fn only_split_buffer_pools_with_enough_frames_per_instance() {
+        assert_eq!(default_buffer_pool_instances(8), 1);
+        assert_eq!(default_buffer_pool_instances(128), 2);
+        assert_eq!(default_buffer_pool_instances(1 << 20), 16);
+        for pool_size in [128, 1024, 1 << 20] {
+            let num_of_instances = default_buffer_pool_instances(pool_size);
+            assert!(pool_size >= min_pool_size(num_of_instances));
+        }
+
+        assert_eq!(min_pool_size(1), 1);
+        assert_eq!(min_pool_size(4), 4 * MIN_INSTANCE_FRAMES);
+    }
+
+    #[test]
+    fn pass_releases_on_to_threads_that_did_not_try_again() {
+        let releases = Arc::new(ReleaseQueue::default());
+        let (woken, woken_up) = mpsc::channel();
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let releases = releases.clone();
+                let woken = woken.clone();
+                thread::spawn(move || {
+                    let deadline = Instant::now() + Duration::from_secs(10);
+                    assert!(releases.wait(0, Some(deadline)));
+                    woken.send(()).unwrap();
+                })
+            })
+            .collect();
+        while releases.num_of_waiters.load(Ordering::SeqCst) < 2 {
+            thread::yield_now();
+        }
+
+        // A release only wakes up a single thread.
+        releases.notify();
+        woken_up.recv().unwrap();
+        assert!(woken_up.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // Until the thread woken up fails to use it and waits again.
+        assert!(!releases.wait(1, Some(Instant::now())));
+        woken_up.recv().unwrap();
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+    }
+}
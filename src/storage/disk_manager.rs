@@ -1,11 +1,193 @@
-use super::pager::PAGE_SIZE;
+use super::mmap::MmapStorage;
+use super::pager::DEFAULT_PAGE_SIZE;
+#[cfg(target_os = "linux")]
+use super::uring::IoUringStorage;
 use std::{
     fs::{File, OpenOptions},
     io::SeekFrom,
     io::{BufReader, Read, Seek, Write},
     path::Path,
-    sync::Mutex,
+    str::FromStr,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
 };
+#[cfg(target_os = "linux")]
+use tracing::warn;
+
+/// Where the pages of our database file are read from and written to,
+/// see StorageBackend.
+pub trait Storage: Send + Sync + std::fmt::Debug {
+    /// The length of our file when it was opened.
+    fn file_len(&self) -> usize;
+
+    fn page_size(&self) -> usize;
+
+    fn set_page_size(&mut self, page_size: usize);
+
+    /// Read len bytes from the given offset of our file, e.g. to read our
+    /// database header before knowing the page size.
+    fn read_at(&self, offset: usize, len: usize) -> Result<Vec<u8>, std::io::Error>;
+
+    fn read_page(&self, page_id: usize) -> Result<Vec<u8>, std::io::Error> {
+        self.read_at(page_id * self.page_size(), self.page_size())
+    }
+
+    fn write_page(&self, page_id: usize, page_bytes: &[u8]) -> Result<(), std::io::Error>;
+
+    /// Write several pages at once, which are written in parallel if our
+    /// backend is able to, see Pager::flush_all_pages.
+    fn write_pages(&self, pages: Vec<(usize, Vec<u8>)>) -> Result<(), std::io::Error> {
+        pages
+            .iter()
+            .try_for_each(|(page_id, page_bytes)| self.write_page(*page_id, page_bytes))
+    }
+
+    /// Wait for our writes to reach the disk, as our synchronous mode asks.
+    fn sync(&self, synchronous: SynchronousMode) -> Result<(), std::io::Error>;
+}
+
+// TRADEOFF: Similar to PRAGMA synchronous of SQLite, our synchronous mode
+// decides when we wait for our writes to reach the disk.
+//
+// - Off never syncs, leaving it to the OS. Our data survives a crash of our
+//   process, but not a power loss.
+// - Normal syncs our log on every append, and our database file whenever
+//   every pages are flushed, with fdatasync. It skips the metadata that isn't
+//   needed to read our data back, e.g. the modification time of our files.
+//   The pages evicted in between might be lost by a power loss, but not the
+//   log records of their changes.
+// - Full also syncs our database file after every page written, and uses
+//   fsync, so our data is on disk once it's written, but each write waits
+//   for the disk.
+//
+// Hence, Normal is our default, as SQLite does in WAL mode.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SynchronousMode {
+    Off,
+    #[default]
+    Normal,
+    Full,
+}
+
+impl SynchronousMode {
+    /// Sync our file, either fully or only its data, as our mode asks.
+    pub(super) fn sync_file(self, file: &File) -> Result<(), std::io::Error> {
+        match self {
+            SynchronousMode::Off => Ok(()),
+            SynchronousMode::Normal => file.sync_data(),
+            SynchronousMode::Full => file.sync_all(),
+        }
+    }
+}
+
+/// A synchronous mode shared by our Pager and our log, so changing it at
+/// runtime (e.g. with .synchronous) applies to both, see Pager::set_synchronous.
+#[derive(Debug, Default, Clone)]
+pub struct SharedSynchronousMode(Arc<AtomicU8>);
+
+impl SharedSynchronousMode {
+    pub fn new(synchronous: SynchronousMode) -> Self {
+        let shared = Self::default();
+        shared.set(synchronous);
+        shared
+    }
+
+    pub fn get(&self) -> SynchronousMode {
+        match self.0.load(Ordering::Acquire) {
+            0 => SynchronousMode::Off,
+            1 => SynchronousMode::Normal,
+            _ => SynchronousMode::Full,
+        }
+    }
+
+    pub fn set(&self, synchronous: SynchronousMode) {
+        let value = match synchronous {
+            SynchronousMode::Off => 0,
+            SynchronousMode::Normal => 1,
+            SynchronousMode::Full => 2,
+        };
+        self.0.store(value, Ordering::Release);
+    }
+}
+
+impl FromStr for SynchronousMode {
+    type Err = String;
+
+    /// Parse one of off, normal or full, or their number, i.e. 0, 1 or 2.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "0" => Ok(SynchronousMode::Off),
+            "normal" | "1" => Ok(SynchronousMode::Normal),
+            "full" | "2" => Ok(SynchronousMode::Full),
+            _ => Err(format!("unknown synchronous mode {s}")),
+        }
+    }
+}
+
+impl std::fmt::Display for SynchronousMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SynchronousMode::Off => write!(f, "off"),
+            SynchronousMode::Normal => write!(f, "normal"),
+            SynchronousMode::Full => write!(f, "full"),
+        }
+    }
+}
+
+// TRADEOFF: Our database file is read and written with system calls unless
+// it's memory-mapped or read and written through io_uring when chosen.
+//
+// A memory-mapped file is read without any system call, straight from the
+// page cache, and reads only share a read latch, so pages missing from our
+// buffer pool are read concurrently. The latch is held exclusively while a
+// page is copied into our mapping, or while our mapping is replaced once our
+// file outgrows it, which would otherwise free the memory a read copies from.
+// However, an I/O error is raised as a SIGBUS instead of being returned, e.g.
+// when the disk is full or our file is truncated by another process.
+//
+// io_uring is only available on Linux (5.6 or later), and might be disabled
+// by seccomp, e.g. inside containers, in which case our file is read and
+// written with system calls instead, see IoUringStorage.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    #[default]
+    File,
+    Mmap,
+    #[cfg(target_os = "linux")]
+    IoUring,
+}
+
+impl StorageBackend {
+    pub fn open(self, path: impl AsRef<Path>) -> Result<Box<dyn Storage>, std::io::Error> {
+        match self {
+            StorageBackend::File => Ok(Box::new(DiskManager::new(path))),
+            StorageBackend::Mmap => Ok(Box::new(MmapStorage::open(path)?)),
+            #[cfg(target_os = "linux")]
+            StorageBackend::IoUring => match IoUringStorage::setup_ring() {
+                Ok(ring) => Ok(Box::new(IoUringStorage::open(path, ring)?)),
+                Err(err) => {
+                    warn!("fail to set up io_uring, falling back to file storage: {err}");
+                    Ok(Box::new(DiskManager::new(path)))
+                }
+            },
+        }
+    }
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    /// Parse one of file, mmap or io_uring (only on Linux).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "file" => Ok(StorageBackend::File),
+            "mmap" => Ok(StorageBackend::Mmap),
+            #[cfg(target_os = "linux")]
+            "io_uring" => Ok(StorageBackend::IoUring),
+            _ => Err(format!("unknown storage backend {s}")),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct DiskManager {
@@ -13,6 +195,14 @@ pub struct DiskManager {
     read_file: Mutex<File>,
     path: String,
     pub file_len: usize,
+    // The page size of our database, which is only known once the
+    // database header is read, see Pager::open.
+    page_size: usize,
+    // How our appends are synced, the pages of our database file are
+    // synced by our Pager instead.
+    synchronous: SharedSynchronousMode,
+    // The number of appends synced so far.
+    synced_appends: AtomicUsize,
 }
 
 impl DiskManager {
@@ -31,13 +221,30 @@ impl DiskManager {
             read_file: Mutex::new(read_file),
             path: path.as_ref().to_str().unwrap().into(),
             file_len,
+            page_size: DEFAULT_PAGE_SIZE,
+            synchronous: SharedSynchronousMode::default(),
+            synced_appends: AtomicUsize::new(0),
         }
     }
 
+    /// Sync our appends as the given mode asks, whenever it changes.
+    pub fn set_synchronous(&mut self, synchronous: SharedSynchronousMode) {
+        self.synchronous = synchronous;
+    }
+
     pub fn append(&self, bytes: &[u8]) -> Result<(), std::io::Error> {
         let mut file = self.write_file.lock().unwrap();
         file.write_all(bytes)?;
-        file.sync_all()
+
+        let synchronous = self.synchronous.get();
+        if synchronous != SynchronousMode::Off {
+            self.synced_appends.fetch_add(1, Ordering::Relaxed);
+        }
+        synchronous.sync_file(&file)
+    }
+
+    pub fn synced_appends(&self) -> usize {
+        self.synced_appends.load(Ordering::Relaxed)
     }
 
     pub fn read_exact(&self, buf: &mut [u8]) {
@@ -48,23 +255,40 @@ impl DiskManager {
         let file = File::open(&self.path).unwrap();
         BufReader::new(file)
     }
+}
+
+impl Storage for DiskManager {
+    fn file_len(&self) -> usize {
+        self.file_len
+    }
 
-    pub fn write_page(&self, page_id: usize, page_bytes: &[u8]) -> Result<(), std::io::Error> {
-        let offset = page_id * PAGE_SIZE;
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn set_page_size(&mut self, page_size: usize) {
+        self.page_size = page_size;
+    }
+
+    fn write_page(&self, page_id: usize, page_bytes: &[u8]) -> Result<(), std::io::Error> {
+        debug_assert_eq!(page_bytes.len(), self.page_size);
+        let offset = page_id * self.page_size;
         let mut write_file = self.write_file.lock().unwrap();
         write_file.seek(SeekFrom::Start(offset as u64))?;
         write_file.write_all(page_bytes)?;
-        // TODO: We probably need to call sync_all(), to ensure that fsync is executed.
+        // Our page is synced by our Pager if needed, see Storage::sync.
         write_file.flush()
     }
 
-    pub fn read_page(&self, page_id: usize) -> Result<[u8; PAGE_SIZE], std::io::Error> {
-        let offset = page_id * PAGE_SIZE;
+    fn sync(&self, synchronous: SynchronousMode) -> Result<(), std::io::Error> {
+        synchronous.sync_file(&self.write_file.lock().unwrap())
+    }
 
+    fn read_at(&self, offset: usize, len: usize) -> Result<Vec<u8>, std::io::Error> {
         // TODO: probably need to handle when offset < file_len
         let mut read_file = self.read_file.lock().unwrap();
         read_file.seek(SeekFrom::Start(offset as u64))?;
-        let mut buffer = [0; PAGE_SIZE];
+        let mut buffer = vec![0; len];
         read_file.read_exact(&mut buffer)?;
         Ok(buffer)
     }
@@ -76,6 +300,7 @@ mod test {
     use std::thread;
 
     use super::*;
+    use crate::storage::mmap::MIN_MAPPING_LEN;
 
     #[test]
     fn append() {
@@ -90,28 +315,42 @@ mod test {
         let _ = std::fs::remove_file(file);
     }
 
+    const BACKENDS: &[StorageBackend] = &[
+        StorageBackend::File,
+        StorageBackend::Mmap,
+        #[cfg(target_os = "linux")]
+        StorageBackend::IoUring,
+    ];
+
+    fn open_storage(file: &str, backend: StorageBackend) -> Arc<dyn Storage> {
+        let _ = std::fs::remove_file(file);
+        backend.open(file).unwrap().into()
+    }
+
     #[test]
     fn read_file_concurrently() {
         let file = format!("test_file_{:?}", std::thread::current().id());
-        let disk_manager = Arc::new(DiskManager::new(&file));
+        for &backend in BACKENDS {
+            let disk_manager = open_storage(&file, backend);
 
-        // Setup file
-        for i in 0..8 {
-            disk_manager.write_page(i, &[i as u8; 4096]).unwrap();
-        }
-
-        // Try 1000 times since concurrency bugs sometime occurs sometimes don't
-        for _ in 0..1000 {
-            let mut handles = vec![];
+            // Setup file
             for i in 0..8 {
-                let disk_manager = disk_manager.clone();
-                let handle = thread::spawn(move || disk_manager.read_page(i).unwrap());
-                handles.push((i, handle));
+                disk_manager.write_page(i, &[i as u8; 4096]).unwrap();
             }
 
-            for (i, handle) in handles {
-                let result = handle.join().unwrap();
-                assert_eq!(result, [i as u8; 4096]);
+            // Try 1000 times since concurrency bugs sometime occurs sometimes don't
+            for _ in 0..1000 {
+                let mut handles = vec![];
+                for i in 0..8 {
+                    let disk_manager = disk_manager.clone();
+                    let handle = thread::spawn(move || disk_manager.read_page(i).unwrap());
+                    handles.push((i, handle));
+                }
+
+                for (i, handle) in handles {
+                    let result = handle.join().unwrap();
+                    assert_eq!(result, [i as u8; 4096]);
+                }
             }
         }
 
@@ -121,23 +360,25 @@ mod test {
     #[test]
     fn write_file_concurrently() {
         let file = format!("test_file_{:?}", std::thread::current().id());
-        let disk_manager = Arc::new(DiskManager::new(&file));
-
-        let mut handles = vec![];
-        for i in 0..8 {
-            let disk_manager = disk_manager.clone();
-            let handle =
-                thread::spawn(move || disk_manager.write_page(i, &[i as u8; 4096]).unwrap());
-            handles.push(handle);
-        }
+        for &backend in BACKENDS {
+            let disk_manager = open_storage(&file, backend);
 
-        for handle in handles {
-            handle.join().unwrap();
-        }
+            let mut handles = vec![];
+            for i in 0..8 {
+                let disk_manager = disk_manager.clone();
+                let handle =
+                    thread::spawn(move || disk_manager.write_page(i, &[i as u8; 4096]).unwrap());
+                handles.push(handle);
+            }
+
+            for handle in handles {
+                handle.join().unwrap();
+            }
 
-        for i in 0..8 {
-            let result = disk_manager.read_page(i).unwrap();
-            assert_eq!(result, [i as u8; 4096]);
+            for i in 0..8 {
+                let result = disk_manager.read_page(i).unwrap();
+                assert_eq!(result, [i as u8; 4096]);
+            }
         }
 
         let _ = std::fs::remove_file(file);
@@ -145,43 +386,85 @@ mod test {
     #[test]
     fn write_and_read_file_concurrently() {
         let file = format!("test_file_{:?}", std::thread::current().id());
-        let disk_manager = Arc::new(DiskManager::new(&file));
+        for &backend in BACKENDS {
+            let disk_manager = open_storage(&file, backend);
 
-        // Setup file
-        for i in 0..8 {
-            disk_manager.write_page(i, &[i as u8; 4096]).unwrap();
-        }
+            // Setup file
+            for i in 0..8 {
+                disk_manager.write_page(i, &[i as u8; 4096]).unwrap();
+            }
 
-        // Read concurrently
-        let mut read_handles = vec![];
-        for i in 0..8 {
-            let disk_manager = disk_manager.clone();
-            let handle = thread::spawn(move || disk_manager.read_page(i).unwrap());
-            read_handles.push((i, handle));
-        }
+            // Read concurrently
+            let mut read_handles = vec![];
+            for i in 0..8 {
+                let disk_manager = disk_manager.clone();
+                let handle = thread::spawn(move || disk_manager.read_page(i).unwrap());
+                read_handles.push((i, handle));
+            }
 
-        // Write concurrently
-        let mut write_handles = vec![];
-        for i in 8..16 {
-            let disk_manager = disk_manager.clone();
-            let handle =
-                thread::spawn(move || disk_manager.write_page(i, &[i as u8; 4096]).unwrap());
-            write_handles.push(handle);
-        }
+            // Write concurrently
+            let mut write_handles = vec![];
+            for i in 8..16 {
+                let disk_manager = disk_manager.clone();
+                let handle =
+                    thread::spawn(move || disk_manager.write_page(i, &[i as u8; 4096]).unwrap());
+                write_handles.push(handle);
+            }
+
+            for handle in write_handles {
+                handle.join().unwrap();
+            }
+
+            for (i, handle) in read_handles {
+                let result = handle.join().unwrap();
+                assert_eq!(result, [i as u8; 4096]);
+            }
 
-        for handle in write_handles {
-            handle.join().unwrap();
+            // Verify write
+            for i in 8..16 {
+                let result = disk_manager.read_page(i).unwrap();
+                assert_eq!(result, [i as u8; 4096]);
+            }
         }
 
-        for (i, handle) in read_handles {
-            let result = handle.join().unwrap();
-            assert_eq!(result, [i as u8; 4096]);
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[test]
+    fn parse_synchronous_mode() {
+        assert_eq!("off".parse(), Ok(SynchronousMode::Off));
+        assert_eq!("Normal".parse(), Ok(SynchronousMode::Normal));
+        assert_eq!("2".parse(), Ok(SynchronousMode::Full));
+        assert_eq!(
+            "3".parse::<SynchronousMode>(),
+            Err("unknown synchronous mode 3".to_string())
+        );
+        assert_eq!(SynchronousMode::Full.to_string(), "full");
+    }
+
+    #[test]
+    fn mmap_storage_grow_and_truncate_file() {
+        let file = format!("test_file_{:?}", std::thread::current().id());
+        let disk_manager = open_storage(&file, StorageBackend::Mmap);
+
+        // Our file outgrows our mapping.
+        let num_of_pages = 2 * MIN_MAPPING_LEN / 4096 + 1;
+        for i in 0..num_of_pages {
+            disk_manager.write_page(i, &[i as u8; 4096]).unwrap();
         }
+        assert_eq!(
+            disk_manager.read_page(num_of_pages - 1).unwrap(),
+            [(num_of_pages - 1) as u8; 4096]
+        );
+        assert!(disk_manager.read_page(num_of_pages).is_err());
+
+        drop(disk_manager);
 
-        // Verify write
-        for i in 8..16 {
-            let result = disk_manager.read_page(i).unwrap();
-            assert_eq!(result, [i as u8; 4096]);
+        // Our pages are read back by either backend.
+        for &backend in BACKENDS {
+            let disk_manager = backend.open(&file).unwrap();
+            assert_eq!(disk_manager.file_len(), num_of_pages * 4096);
+            assert_eq!(disk_manager.read_page(1).unwrap(), [1; 4096]);
         }
 
         let _ = std::fs::remove_file(file);
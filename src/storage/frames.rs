@@ -0,0 +1,78 @@
+use parking_lot::RwLock;
+use std::sync::OnceLock;
+
+use super::page::Page;
+
+// TRADEOFF: Our frames are allocated in segments that never move.
+//
+// Our page latches are borrowed from our Pager while latch crabbing, so we
+// can't store our frames in a Vec that is reallocated when our buffer pool
+// grows. Instead, every segment is allocated once and is twice as large as
+// the previous ones, so looking up a frame only goes through a few segments.
+//
+// The segments are never freed, even when our buffer pool shrinks: the pages
+// of the removed frames are dropped, but not the (small) frames themselves,
+// which are reused if our buffer pool grows again.
+const MAX_SEGMENTS: usize = usize::BITS as usize;
+
+/// The frames of our buffer pool, see Pager::resize.
+#[derive(Debug)]
+pub(super) struct Frames {
+    segments: Vec<OnceLock<Box<[RwLock<Page>]>>>,
+}
+
+impl Frames {
+    pub fn new(num_of_frames: usize) -> Self {
+        let frames = Self {
+            segments: (0..MAX_SEGMENTS).map(|_| OnceLock::new()).collect(),
+        };
+        frames.allocate(num_of_frames);
+        frames
+    }
+
+    /// Allocate new segments until we have at least the given number of
+    /// frames.
+    pub fn allocate(&self, num_of_frames: usize) {
+        for segment in &self.segments {
+            let capacity = self.capacity();
+            if capacity >= num_of_frames {
+                break;
+            }
+
+            segment.get_or_init(|| {
+                let segment_size = std::cmp::max(capacity, num_of_frames - capacity);
+                (0..segment_size)
+                    .map(|_| RwLock::new(Page::new(None)))
+                    .collect()
+            });
+        }
+    }
+
+    /// The number of frames allocated, including the frames that are no
+    /// longer part of our buffer pool.
+    pub fn capacity(&self) -> usize {
+        self.allocated_segments().map(|segment| segment.len()).sum()
+    }
+
+    pub fn get(&self, mut frame_id: usize) -> Option<&RwLock<Page>> {
+        for segment in self.allocated_segments() {
+            if frame_id < segment.len() {
+                return Some(&segment[frame_id]);
+            }
+            frame_id -= segment.len();
+        }
+
+        None
+    }
+
+    /// Iterate through every frames allocated, in the order of their ids.
+    pub fn iter(&self) -> impl Iterator<Item = &RwLock<Page>> {
+        self.allocated_segments().flat_map(|segment| segment.iter())
+    }
+
+    fn allocated_segments(&self) -> impl Iterator<Item = &[RwLock<Page>]> {
+        self.segments
+            .iter()
+            .map_while(|segment| segment.get().map(|segment| &**segment))
+    }
+}
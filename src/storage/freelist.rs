@@ -0,0 +1,99 @@
+use super::page::{verify_checksum, write_checksum, Page, PAGE_HEADER_BYTES};
+use super::pager::PagerError;
+
+// Stored where a node stores its NodeType, see OVERFLOW_PAGE_TYPE.
+pub const FREELIST_TRUNK_PAGE_TYPE: u8 = 3;
+const FREELIST_TRUNK_PAGE_HEADER_SIZE: usize =
+    std::mem::size_of::<u8>() + std::mem::size_of::<u32>() + std::mem::size_of::<u32>();
+
+/// The number of free page ids a trunk page can hold.
+pub fn freelist_trunk_page_max_leaves(page_size: usize) -> usize {
+    (page_size - PAGE_HEADER_BYTES - FREELIST_TRUNK_PAGE_HEADER_SIZE) / std::mem::size_of::<u32>()
+}
+
+// Similar to SQLite, our free pages are tracked by a linked list of trunk
+// pages, starting from DatabaseHeader.freelist_head.
+//
+//   [page header][type: u8][next trunk page id: u32][number of leaves: u32][leaf page id: u32]...
+//
+// Every trunk page holds the page ids of up to freelist_trunk_page_max_leaves
+// free pages (a.k.a. leaf pages), whose content is meaningless. The trunk pages
+// are free pages as well, so they are reused once every leaf pages are used.
+#[derive(Debug, PartialEq)]
+pub struct FreelistTrunkPage {
+    pub next_trunk_page_id: u32,
+    pub leaf_page_ids: Vec<u32>,
+}
+
+impl FreelistTrunkPage {
+    pub fn new(next_trunk_page_id: u32, leaf_page_ids: &[u32]) -> Self {
+        Self {
+            next_trunk_page_id,
+            leaf_page_ids: leaf_page_ids.to_vec(),
+        }
+    }
+
+    pub fn from_bytes(page_id: usize, bytes: &[u8]) -> Result<Self, PagerError> {
+        verify_checksum(page_id, bytes)?;
+
+        let bytes = &bytes[PAGE_HEADER_BYTES..];
+        if bytes[0] != FREELIST_TRUNK_PAGE_TYPE {
+            return Err(PagerError::CorruptedPage(page_id));
+        }
+
+        let next_trunk_page_id = u32::from_le_bytes(bytes[1..5].try_into().unwrap());
+        let num_of_leaves = u32::from_le_bytes(bytes[5..9].try_into().unwrap()) as usize;
+        let leaf_page_ids = bytes[FREELIST_TRUNK_PAGE_HEADER_SIZE..]
+            .chunks_exact(std::mem::size_of::<u32>())
+            .take(num_of_leaves)
+            .map(|id| u32::from_le_bytes(id.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            next_trunk_page_id,
+            leaf_page_ids,
+        })
+    }
+
+    pub fn as_bytes(&self, page_id: usize, page_size: usize) -> Vec<u8> {
+        assert!(self.leaf_page_ids.len() <= freelist_trunk_page_max_leaves(page_size));
+
+        let mut bytes = Page::new(Some(page_id)).header_bytes();
+        bytes.push(FREELIST_TRUNK_PAGE_TYPE);
+        bytes.extend_from_slice(&self.next_trunk_page_id.to_le_bytes());
+        bytes.extend_from_slice(&(self.leaf_page_ids.len() as u32).to_le_bytes());
+        for page_id in &self.leaf_page_ids {
+            bytes.extend_from_slice(&page_id.to_le_bytes());
+        }
+        bytes.resize(page_size, 0);
+        write_checksum(&mut bytes);
+
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::DEFAULT_PAGE_SIZE;
+
+    #[test]
+    fn as_bytes_and_from_bytes() {
+        for page_size in [1024, DEFAULT_PAGE_SIZE, 64 * 1024] {
+            let max_leaves = freelist_trunk_page_max_leaves(page_size) as u32;
+            let leaf_page_ids: Vec<u32> = (0..max_leaves).collect();
+            let page = FreelistTrunkPage::new(9, &leaf_page_ids);
+            let bytes = page.as_bytes(3, page_size);
+            assert_eq!(bytes.len(), page_size);
+            assert_eq!(FreelistTrunkPage::from_bytes(3, &bytes).unwrap(), page);
+        }
+
+        let page = FreelistTrunkPage::new(0, &[]);
+        let mut bytes = page.as_bytes(3, DEFAULT_PAGE_SIZE);
+        assert_eq!(FreelistTrunkPage::from_bytes(3, &bytes).unwrap(), page);
+
+        bytes[PAGE_HEADER_BYTES + 1] ^= 1;
+        let result = FreelistTrunkPage::from_bytes(3, &bytes);
+        assert!(matches!(result, Err(PagerError::CorruptedPage(3))));
+    }
+}
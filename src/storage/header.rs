@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use super::pager::{PagerError, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE, MIN_PAGE_SIZE};
+
+// Similar to SQLite, the very first page of our database file is reserved
+// for the database header (a.k.a. superblock). It is never part of the
+// buffer pool and no B+ tree node is ever stored in it.
+//
+// Since page 0 is always the header, a page id of 0 can also be safely used
+// as a "null" pointer in our nodes (e.g. next_leaf_offset).
+pub const HEADER_PAGE_ID: usize = 0;
+
+// Used to detect if we are opening a file that is not created by us.
+const MAGIC: [u8; 16] = *b"sqlite-rust db\0\0";
+
+// Bump this whenever the on disk format changes in a non backward
+// compatible way.
+pub const FORMAT_VERSION: u32 = 7;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct DatabaseHeader {
+    magic: [u8; 16],
+    pub version: u32,
+    pub page_size: u32,
+
+    // The page id of the root node of our catalog B+ tree, which keep
+    // track of the root page of every other table. Root nodes never move
+    // to another page once created, as we always keep the root in place
+    // when we split or merge it.
+    pub catalog_root_page_id: u32,
+
+    // Total number of pages in the database file, including the header page.
+    pub page_count: u32,
+
+    // The first page of our free page list. 0 indicate an empty list.
+    pub freelist_head: u32,
+    pub freelist_count: u32,
+
+    // The LSN of the last checkpoint. Recovery can start from here
+    // instead of the beginning of the log.
+    pub checkpoint_lsn: u32,
+}
+
+impl DatabaseHeader {
+    pub fn new(page_size: usize) -> Self {
+        Self {
+            magic: MAGIC,
+            version: FORMAT_VERSION,
+            page_size: page_size as u32,
+            catalog_root_page_id: 1,
+            page_count: 2,
+            freelist_head: 0,
+            freelist_count: 0,
+            checkpoint_lsn: 0,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PagerError> {
+        let header: DatabaseHeader =
+            bincode::deserialize(bytes).map_err(|_| PagerError::NotADatabase)?;
+
+        if header.magic != MAGIC {
+            return Err(PagerError::NotADatabase);
+        }
+
+        if header.version != FORMAT_VERSION {
+            return Err(PagerError::UnsupportedVersion(header.version));
+        }
+
+        validate_page_size(header.page_size as usize)?;
+
+        Ok(header)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = bincode::serialize(self).unwrap();
+
+        // Backfill the rest of the page, so our first node
+        // always start at page_size offset.
+        bytes.resize(self.page_size as usize, 0);
+        bytes
+    }
+}
+
+impl Default for DatabaseHeader {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAGE_SIZE)
+    }
+}
+
+/// Similar to SQLite, our page size must be a power of two between
+/// MIN_PAGE_SIZE and MAX_PAGE_SIZE.
+pub fn validate_page_size(page_size: usize) -> Result<(), PagerError> {
+    if page_size.is_power_of_two() && (MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+        Ok(())
+    } else {
+        Err(PagerError::InvalidPageSize(page_size))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn as_bytes_from_bytes() {
+        let mut header = DatabaseHeader::new(DEFAULT_PAGE_SIZE);
+        header.page_count = 10;
+        header.checkpoint_lsn = 5;
+
+        let bytes = header.as_bytes();
+        assert_eq!(bytes.len(), DEFAULT_PAGE_SIZE);
+
+        let from_bytes = DatabaseHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(from_bytes, header);
+    }
+
+    #[test]
+    fn error_when_magic_mismatch() {
+        let mut bytes = DatabaseHeader::new(DEFAULT_PAGE_SIZE).as_bytes();
+        bytes[0] = b'x';
+
+        let result = DatabaseHeader::from_bytes(&bytes);
+        assert!(matches!(result, Err(PagerError::NotADatabase)));
+
+        let result = DatabaseHeader::from_bytes(&[0; DEFAULT_PAGE_SIZE]);
+        assert!(matches!(result, Err(PagerError::NotADatabase)));
+    }
+
+    #[test]
+    fn error_when_version_or_page_size_mismatch() {
+        let mut header = DatabaseHeader::new(DEFAULT_PAGE_SIZE);
+        header.version = FORMAT_VERSION + 1;
+        let result = DatabaseHeader::from_bytes(&header.as_bytes());
+        assert!(
+            matches!(result, Err(PagerError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1)
+        );
+
+        let mut header = DatabaseHeader::new(DEFAULT_PAGE_SIZE);
+        header.page_size = 1000;
+        let result = DatabaseHeader::from_bytes(&header.as_bytes());
+        assert!(matches!(result, Err(PagerError::InvalidPageSize(1000))));
+
+        let mut header = DatabaseHeader::new(DEFAULT_PAGE_SIZE);
+        header.page_size = 2 * MAX_PAGE_SIZE as u32;
+        let result = DatabaseHeader::from_bytes(&header.as_bytes());
+        assert!(matches!(result, Err(PagerError::InvalidPageSize(_))));
+    }
+
+    #[test]
+    fn as_bytes_with_page_size() {
+        for page_size in [MIN_PAGE_SIZE, 16 * 1024, MAX_PAGE_SIZE] {
+            let header = DatabaseHeader::new(page_size);
+            let bytes = header.as_bytes();
+            assert_eq!(bytes.len(), page_size);
+            assert_eq!(DatabaseHeader::from_bytes(&bytes).unwrap(), header);
+        }
+    }
+}
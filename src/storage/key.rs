@@ -0,0 +1,310 @@
+use std::fmt::Debug;
+
+// TRADEOFF: Our keys are memcomparable.
+//
+// Every key is encoded into bytes whose lexicographic order is the order of
+// the key, similar to the keys of MyRocks or CockroachDB. Hence, our nodes
+// only ever compare bytes and never have to know the type of their keys,
+// and a composite key is just the encodings of its columns one after another.
+//
+// However, every key has to be encoded before searching our tree, and
+// decoded when read back from a cell.
+/// A key of a BPlusTree, encoded into the keys of our nodes.
+pub trait Key: Sized + Debug {
+    /// Append the memcomparable encoding of the key to the bytes.
+    fn encode_into(&self, bytes: &mut Vec<u8>);
+
+    /// Decode a key from the start of the bytes, and return the bytes left.
+    fn decode_from(bytes: &[u8]) -> (Self, &[u8]);
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.encode_into(&mut bytes);
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        Self::decode_from(bytes).0
+    }
+
+    /// Return a key between the encoded left key (inclusive) and right key
+    /// (exclusive), used as separator in our internal nodes.
+    ///
+    /// Variable-length keys return the shortest separator they can, while
+    /// the rest keep the left key, as their truncated encoding can't be
+    /// decoded anymore, see BPlusTree::to_tree_string.
+    fn shortest_separator(left: &[u8], _right: &[u8]) -> Vec<u8> {
+        left.to_vec()
+    }
+}
+
+/// Return the shortest prefix of the right key that is larger than the left
+/// key, or the left key if the right key is its only such prefix.
+///
+/// Our encodings are prefix-free, i.e. no key is the prefix of another key,
+/// so the prefix is always smaller than the right key.
+fn truncate_separator(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let common_prefix_len = left
+        .iter()
+        .zip(right)
+        .take_while(|(left, right)| left == right)
+        .count();
+
+    if right.len() > common_prefix_len + 1 {
+        right[..=common_prefix_len].to_vec()
+    } else {
+        left.to_vec()
+    }
+}
+
+// Unsigned integers are stored in big-endian, so their most significant
+// byte is compared first.
+macro_rules! unsigned_key {
+    ($($ty:ty),*) => {
+        $(
+            impl Key for $ty {
+                fn encode_into(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode_from(bytes: &[u8]) -> (Self, &[u8]) {
+                    let (key, rest) = bytes.split_at(std::mem::size_of::<$ty>());
+                    (<$ty>::from_be_bytes(key.try_into().unwrap()), rest)
+                }
+            }
+        )*
+    };
+}
+
+unsigned_key!(u32, u64);
+
+// Signed integers have their sign bit flipped, so negative numbers are
+// ordered before positive ones.
+macro_rules! signed_key {
+    ($($ty:ty => $unsigned:ty),*) => {
+        $(
+            impl Key for $ty {
+                fn encode_into(&self, bytes: &mut Vec<u8>) {
+                    (*self as $unsigned ^ (1 << (<$unsigned>::BITS - 1))).encode_into(bytes);
+                }
+
+                fn decode_from(bytes: &[u8]) -> (Self, &[u8]) {
+                    let (key, rest) = <$unsigned>::decode_from(bytes);
+                    ((key ^ (1 << (<$unsigned>::BITS - 1))) as $ty, rest)
+                }
+            }
+        )*
+    };
+}
+
+signed_key!(i32 => u32, i64 => u64);
+
+// TRADEOFF: Variable-length keys are escaped and terminated.
+//
+// Every 0x00 byte is escaped into 0x00 0xFF, and the key is terminated by
+// 0x00 0x01, which is smaller than any other byte following a 0x00. Hence, a
+// key is ordered before every longer key it's a prefix of, and a string can
+// be followed by other columns in a composite key. However, keys full of
+// 0x00 bytes take up to twice their size.
+const ESCAPE: u8 = 0x00;
+const ESCAPED_ESCAPE: u8 = 0xFF;
+const TERMINATOR: u8 = 0x01;
+
+impl Key for Vec<u8> {
+    fn encode_into(&self, bytes: &mut Vec<u8>) {
+        for &byte in self {
+            bytes.push(byte);
+            if byte == ESCAPE {
+                bytes.push(ESCAPED_ESCAPE);
+            }
+        }
+        bytes.extend_from_slice(&[ESCAPE, TERMINATOR]);
+    }
+
+    fn decode_from(bytes: &[u8]) -> (Self, &[u8]) {
+        let mut key = Vec::new();
+        let mut i = 0;
+
+        // The separators of our internal nodes are truncated, so we stop
+        // at the end of our bytes as well.
+        while i < bytes.len() {
+            if bytes[i] != ESCAPE {
+                key.push(bytes[i]);
+                i += 1;
+            } else if bytes.get(i + 1) == Some(&ESCAPED_ESCAPE) {
+                key.push(ESCAPE);
+                i += 2;
+            } else {
+                i = (i + 2).min(bytes.len());
+                break;
+            }
+        }
+
+        (key, &bytes[i..])
+    }
+
+    fn shortest_separator(left: &[u8], right: &[u8]) -> Vec<u8> {
+        truncate_separator(left, right)
+    }
+}
+
+impl Key for String {
+    fn encode_into(&self, bytes: &mut Vec<u8>) {
+        self.as_bytes().to_vec().encode_into(bytes);
+    }
+
+    fn decode_from(bytes: &[u8]) -> (Self, &[u8]) {
+        let (key, rest) = Vec::<u8>::decode_from(bytes);
+
+        // A truncated separator might end in the middle of a character,
+        // which is left out.
+        let key = String::from_utf8(key).unwrap_or_else(|err| {
+            let valid_up_to = err.utf8_error().valid_up_to();
+            let mut key = err.into_bytes();
+            key.truncate(valid_up_to);
+            String::from_utf8(key).unwrap()
+        });
+        (key, rest)
+    }
+
+    fn shortest_separator(left: &[u8], right: &[u8]) -> Vec<u8> {
+        truncate_separator(left, right)
+    }
+}
+
+// A composite key, e.g. (tenant_id, id), which is ordered by its first
+// column, then by its second column, and so on.
+macro_rules! tuple_key {
+    ($($ty:ident $key:ident),+) => {
+        impl<$($ty: Key),+> Key for ($($ty,)+) {
+            fn encode_into(&self, bytes: &mut Vec<u8>) {
+                let ($($key,)+) = self;
+                $($key.encode_into(bytes);)+
+            }
+
+            fn decode_from(bytes: &[u8]) -> (Self, &[u8]) {
+                $(let ($key, bytes) = $ty::decode_from(bytes);)+
+                (($($key,)+), bytes)
+            }
+
+            // Only our last column is truncated, once every other columns
+            // are equal, so our separators can still be decoded.
+            #[allow(unused_assignments)]
+            fn shortest_separator(left: &[u8], right: &[u8]) -> Vec<u8> {
+                let (mut left_rest, mut right_rest) = (left, right);
+                $(
+                    let (_, left_next) = $ty::decode_from(left_rest);
+                    let (_, right_next) = $ty::decode_from(right_rest);
+                    let left_column = &left_rest[..left_rest.len() - left_next.len()];
+                    let right_column = &right_rest[..right_rest.len() - right_next.len()];
+
+                    if left_column != right_column {
+                        if !left_next.is_empty() {
+                            return left.to_vec();
+                        }
+
+                        let mut separator = left[..left.len() - left_rest.len()].to_vec();
+                        separator.extend($ty::shortest_separator(left_column, right_column));
+                        return separator;
+                    }
+                    (left_rest, right_rest) = (left_next, right_next);
+                )+
+
+                left.to_vec()
+            }
+        }
+    };
+}
+
+tuple_key!(A a, B b);
+tuple_key!(A a, B b, C c);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encoded_in_order<K: Key + Ord>(a: K, b: K) -> bool {
+        a.cmp(&b) == a.encode().cmp(&b.encode())
+    }
+
+    fn round_trip<K: Key + PartialEq>(key: K) -> bool {
+        K::decode(&key.encode()) == key
+    }
+
+    quickcheck! {
+        fn integer_keys_are_memcomparable(a: i64, b: i64) -> bool {
+            encoded_in_order(a, b)
+                && encoded_in_order(a as u64, b as u64)
+                && encoded_in_order(a as i32, b as i32)
+                && round_trip(a)
+                && round_trip(a as i32)
+        }
+
+        fn composite_keys_are_memcomparable(a: (u32, i64, u64), b: (u32, i64, u64)) -> bool {
+            encoded_in_order(a, b)
+                && encoded_in_order((a.0, a.1), (b.0, b.1))
+                && round_trip(a)
+        }
+    }
+
+    fn separates<K: Key + Ord + Clone>(a: &K, b: &K) -> bool {
+        let (left, right) = if a < b { (a, b) } else { (b, a) };
+        let separator = K::shortest_separator(&left.encode(), &right.encode());
+
+        a == b
+            || (left.encode() <= separator
+                && separator < right.encode()
+                && separator.len() <= left.encode().len()
+                && &K::decode(&separator) <= right)
+    }
+
+    quickcheck! {
+        fn variable_length_keys_are_memcomparable(a: Vec<u8>, b: Vec<u8>) -> bool {
+            let (x, y) = (String::from_utf8_lossy(&a).into_owned(), String::from_utf8_lossy(&b).into_owned());
+            encoded_in_order(a.clone(), b.clone())
+                && encoded_in_order(x.clone(), y.clone())
+                && encoded_in_order((a.clone(), 1u32), (b.clone(), 0u32))
+                && round_trip(a.clone())
+                && round_trip((x.clone(), a.clone()))
+                && separates(&a, &b)
+                && separates(&x, &y)
+                && separates(&(1u32, x), &(1u32, y))
+        }
+    }
+
+    #[test]
+    fn shortest_separators() {
+        let separator = |left: &str, right: &str| {
+            String::decode(&String::shortest_separator(
+                &left.to_string().encode(),
+                &right.to_string().encode(),
+            ))
+        };
+        assert_eq!(separator("alice", "bob"), "b");
+        assert_eq!(separator("alice", "alicia"), "alici");
+        assert_eq!(separator("al", "alice"), "ali");
+        assert_eq!(separator("alice", "alicf"), "alicf");
+
+        // Only the last column of our composite keys is truncated.
+        let left = (1u32, "alice".to_string()).encode();
+        let right = (1u32, "bob".to_string()).encode();
+        assert_eq!(
+            <(u32, String)>::decode(&<(u32, String)>::shortest_separator(&left, &right)),
+            (1, "b".to_string())
+        );
+        let right = (2u32, "bob".to_string()).encode();
+        assert_eq!(<(u32, String)>::shortest_separator(&left, &right), left);
+        assert_eq!(
+            u64::shortest_separator(&255u64.encode(), &256u64.encode()),
+            255u64.encode()
+        );
+    }
+
+    #[test]
+    fn signed_keys_are_ordered_before_zero() {
+        assert!((-1i64).encode() < 0i64.encode());
+        assert!(i64::MIN.encode() < (-1i64).encode());
+        assert_eq!(0u32.encode(), vec![0, 0, 0, 0]);
+        assert_eq!(0i32.encode(), vec![0x80, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,129 @@
+use memmap2::{MmapMut, MmapOptions};
+use parking_lot::{RwLock, RwLockWriteGuard};
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use super::disk_manager::{Storage, SynchronousMode};
+use super::pager::DEFAULT_PAGE_SIZE;
+
+// Our file is mapped with some room to grow, so appending pages doesn't
+// replace our mapping every time.
+pub(super) const MIN_MAPPING_LEN: usize = 1024 * 1024;
+
+#[derive(Debug)]
+struct Mapping {
+    mmap: MmapMut,
+    // The length of our file, which is usually shorter than our mapping.
+    // Touching the mapping beyond the end of our file raises a SIGBUS.
+    file_len: usize,
+}
+
+/// A database file memory-mapped into our address space, see StorageBackend.
+///
+/// Pages are read by copying them out of our mapping, while writes copy
+/// them into it and msync them.
+#[derive(Debug)]
+pub struct MmapStorage {
+    file: File,
+    // Reads and msync only need a read latch, while writes hold the write
+    // latch to copy a page into our mapping or to replace it.
+    mapping: RwLock<Mapping>,
+    // The length of our file when it was opened.
+    file_len: usize,
+    page_size: usize,
+}
+
+impl MmapStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let file_len = file.metadata()?.len() as usize;
+        let mmap = Self::map(&file, std::cmp::max(file_len, MIN_MAPPING_LEN))?;
+
+        Ok(Self {
+            file,
+            mapping: RwLock::new(Mapping { mmap, file_len }),
+            file_len,
+            page_size: DEFAULT_PAGE_SIZE,
+        })
+    }
+
+    fn map(file: &File, len: usize) -> Result<MmapMut, Error> {
+        // SAFETY: Our file is only modified through our mapping or while
+        // holding our write latch, and we never touch our mapping beyond the
+        // end of our file. Other processes modifying our file at the same
+        // time are unsupported, like with DiskManager.
+        unsafe { MmapOptions::new().len(len).map_mut(file) }
+    }
+
+    /// Grow our file (and our mapping if needed) to the given length.
+    fn grow(
+        &self,
+        mapping: &mut RwLockWriteGuard<'_, Mapping>,
+        file_len: usize,
+    ) -> Result<(), Error> {
+        if file_len <= mapping.file_len {
+            return Ok(());
+        }
+
+        if file_len > mapping.mmap.len() {
+            let mmap_len = std::cmp::max(file_len, mapping.mmap.len() * 2);
+            mapping.mmap = Self::map(&self.file, mmap_len)?;
+        }
+        self.file.set_len(file_len as u64)?;
+        mapping.file_len = file_len;
+        Ok(())
+    }
+}
+
+impl Storage for MmapStorage {
+    fn file_len(&self) -> usize {
+        self.file_len
+    }
+
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn set_page_size(&mut self, page_size: usize) {
+        self.page_size = page_size;
+    }
+
+    fn read_at(&self, offset: usize, len: usize) -> Result<Vec<u8>, Error> {
+        let mapping = self.mapping.read();
+        if offset + len > mapping.file_len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+
+        Ok(mapping.mmap[offset..offset + len].to_vec())
+    }
+
+    fn write_page(&self, page_id: usize, page_bytes: &[u8]) -> Result<(), Error> {
+        debug_assert_eq!(page_bytes.len(), self.page_size);
+        let offset = page_id * self.page_size;
+        let mut mapping = self.mapping.write();
+        self.grow(&mut mapping, offset + page_bytes.len())?;
+        mapping.mmap[offset..offset + page_bytes.len()].copy_from_slice(page_bytes);
+
+        // Like DiskManager, our page is only handed over to the OS, and is
+        // synced by our Pager if needed, see Storage::sync.
+        let mapping = RwLockWriteGuard::downgrade(mapping);
+        mapping.mmap.flush_async_range(offset, page_bytes.len())
+    }
+
+    fn sync(&self, synchronous: SynchronousMode) -> Result<(), Error> {
+        // Our mapping shares the page cache of our file, so its pages are
+        // synced together with our file.
+        synchronous.sync_file(&self.file)
+    }
+}
@@ -1,14 +1,31 @@
+mod btree;
+mod buffer_pool;
 mod disk_manager;
+mod frames;
+mod freelist;
+mod header;
+mod key;
+mod mmap;
 mod node;
+mod overflow;
 mod page;
+mod page_guard;
 mod pager;
+mod replacer;
+mod stats;
+#[cfg(target_os = "linux")]
+mod uring;
 
 // Reexport so we can refer it from other mod
 // as crate::storage::DiskManager instead of
 // crate::storage::disk_manager::DiskManager
 pub use self::{
-    disk_manager::DiskManager,
-    node::{Node, NodeType, LEAF_NODE_CELL_SIZE},
+    btree::{BPlusTree, Cursor, Record},
+    disk_manager::{DiskManager, SharedSynchronousMode, Storage, StorageBackend, SynchronousMode},
+    key::Key,
+    node::{Node, NodeType},
     page::Page,
     pager::*,
+    replacer::ReplacementPolicy,
+    stats::PagerStats,
 };
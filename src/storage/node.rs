@@ -1,7 +1,6 @@
 use super::page::PAGE_HEADER_BYTES;
-use super::{Cursor, PAGE_SIZE};
-use crate::row::{Row, ROW_SIZE};
-use crate::BigArray;
+use super::{Cursor, Key, DEFAULT_PAGE_SIZE};
+use crate::row::Row;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Copy, Clone)]
@@ -27,130 +26,425 @@ impl From<u8> for NodeType {
     }
 }
 
-const MAX_NODE_SIZE: usize = PAGE_SIZE - PAGE_HEADER_BYTES;
 pub const COMMON_NODE_HEADER_SIZE: usize =
     std::mem::size_of::<NodeType>() + std::mem::size_of::<bool>() + std::mem::size_of::<u32>();
 
 pub const LEAF_NODE_HEADER_SIZE: usize =
     COMMON_NODE_HEADER_SIZE + std::mem::size_of::<u32>() + std::mem::size_of::<u32>();
-const LEAF_NODE_SPACE_FOR_CELLS: usize = MAX_NODE_SIZE - LEAF_NODE_HEADER_SIZE;
 
-const LEAF_NODE_KEY_SIZE: usize = std::mem::size_of::<u32>();
-const LEAF_NODE_VALUE_SIZE: usize = ROW_SIZE;
-pub const LEAF_NODE_CELL_SIZE: usize = LEAF_NODE_KEY_SIZE + LEAF_NODE_VALUE_SIZE;
-pub const LEAF_NODE_MAX_CELLS: usize = LEAF_NODE_SPACE_FOR_CELLS / LEAF_NODE_CELL_SIZE;
-pub const LEAF_NODE_RIGHT_SPLIT_COUNT: usize = (LEAF_NODE_MAX_CELLS + 1) / 2;
-pub const LEAF_NODE_LEFT_SPLIT_COUNT: usize =
-    (LEAF_NODE_MAX_CELLS + 1) - LEAF_NODE_RIGHT_SPLIT_COUNT;
+// Our keys are variable-length memcomparable bytes (see Key) prefixed with
+// their length, in both our leaf and internal cells.
+const KEY_LENGTH_SIZE: usize = std::mem::size_of::<u16>();
+const LEAF_NODE_FLAGS_SIZE: usize = std::mem::size_of::<u8>();
+
+// TRADEOFF: Our leaf nodes are slotted pages.
+//
+//   [header][slot 0][slot 1]...[free space]...[cell 1][cell 0]
+//
+// Every slot is an (offset, length) pair, relative to the end of our header,
+// pointing to a variable-length cell stored at the end of the page. The slots
+// are sorted by key, while the cells can be anywhere in the page.
+//
+// As we always serialize the whole node from our in memory cells, the cells
+// are packed together every time a page is written. Hence, unlike SQLite,
+// we don't need to keep track of freeblocks or defragment our pages.
+const LEAF_NODE_SLOT_SIZE: usize = std::mem::size_of::<u16>() + std::mem::size_of::<u16>();
+const LEAF_NODE_OVERFLOW_POINTER_SIZE: usize = std::mem::size_of::<u32>();
+
+const CELL_DELETED: u8 = 1;
+const CELL_OVERFLOW: u8 = 1 << 1;
 
 pub const INTERNAL_NODE_RIGHT_CHILD_SIZE: usize = std::mem::size_of::<u32>();
 pub const INTERNAL_NODE_NUM_KEYS_SIZE: usize = std::mem::size_of::<u32>();
 pub const INTERNAL_NODE_HEADER_SIZE: usize =
     COMMON_NODE_HEADER_SIZE + INTERNAL_NODE_RIGHT_CHILD_SIZE + INTERNAL_NODE_NUM_KEYS_SIZE;
-pub const INTERNAL_NODE_CELL_SIZE: usize = std::mem::size_of::<u32>() + std::mem::size_of::<u32>();
-// const INTERNAL_NODE_SPACE_FOR_CELLS: usize = MAX_NODE_SIZE - INTERNAL_NODE_HEADER_SIZE;
-// pub const INTERNAL_NODE_MAX_CELLS: usize = INTERNAL_NODE_SPACE_FOR_CELLS / INTERNAL_NODE_CELL_SIZE;
 
-// Hardcoded to 3 for testing
-pub const INTERNAL_NODE_MAX_CELLS: usize = 3;
+// TRADEOFF: The keys of our internal nodes are prefix compressed.
+//
+//   [child pointer][shared length][suffix length][suffix]
+//
+// Every internal cell only stores the part of its key that isn't shared with
+// the key of the previous cell, similar to the front coding of LevelDB blocks.
+// As neighbouring separators usually share a long prefix (e.g. the usernames
+// of a tenant), our string-keyed trees keep a high fan-out.
+//
+// Keys are only compressed in the page, so our nodes are searched without
+// decompressing anything. However, the space taken by a cell depends on its
+// previous cell, see Node::internal_used_space.
+//
+// Only our internal cells are compressed. Our leaf cells are independent
+// slots, which are binary searched and rewritten in place without reading
+// the cells before them, so a leaf node holds exactly as many string keys as
+// without compression. Compressing them would need restart points like the
+// ones of LevelDB blocks. Since our internal nodes are a small part of a
+// tree, string keys still take most of their uncompressed space on disk.
+pub const INTERNAL_NODE_CELL_HEADER_SIZE: usize =
+    std::mem::size_of::<u32>() + KEY_LENGTH_SIZE + KEY_LENGTH_SIZE;
+
+// Most of our tests rely on having a tiny tree to exercise split and merge
+// with a handful of rows. Hence, we cap the capacity of our nodes in tests.
+#[cfg(test)]
+const TEST_LEAF_NODE_MAX_CELLS: usize = 13;
+#[cfg(test)]
+const TEST_INTERNAL_NODE_MAX_CELLS: usize = 3;
+
+// The sizes of a node that depend on the page size of our database, which is
+// only known once the database header is read. Similar to the usableSize,
+// maxLocal and minLocal that SQLite computes for every database.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NodeLayout {
+    pub page_size: usize,
+    // The size of a node, which is the page minus our page header.
+    pub node_size: usize,
+    pub leaf_space_for_cells: usize,
+
+    // Limit the size of a cell so that a leaf node can always hold at least 4 cells,
+    // which guarantees that both nodes of a split have enough space for their cells.
+    pub leaf_max_cell_size: usize,
+
+    // Limit the size of an encoded key so that a leaf cell always has room for
+    // the start of its record, and an internal node for at least a dozen keys.
+    pub max_key_size: usize,
+
+    // Records larger than max_record_size (which depends on the size of their key)
+    // are spilled into overflow pages, keeping only their first bytes in the cell,
+    // similar to the minLocal of SQLite.
+    //
+    // Keeping a small part of the record locally leaves space for other rows in
+    // the leaf, while still allowing most reads of small values to avoid following
+    // the overflow chain.
+    pub leaf_min_local_payload: usize,
+
+    // Leaf nodes are split and merged based on their free space, so the number
+    // of cells they can hold depends on the size of our rows, up to the number of
+    // rows without any values that fit in a node.
+    pub leaf_max_cells: usize,
+
+    // Internal nodes are also split and merged based on their free space, as our
+    // keys are variable-length. Hence, internal_max_cells is only reached with the
+    // smallest keys, unless capped in our tests.
+    pub internal_space_for_cells: usize,
+    pub internal_max_cell_size: usize,
+    pub internal_max_cells: usize,
+}
+
+impl NodeLayout {
+    pub fn new(page_size: usize) -> Self {
+        let node_size = page_size - PAGE_HEADER_BYTES;
+        let leaf_space_for_cells = node_size - LEAF_NODE_HEADER_SIZE;
+        let leaf_max_cell_size = leaf_space_for_cells / 4 - LEAF_NODE_SLOT_SIZE;
+        let max_key_size = leaf_max_cell_size / 4;
+        let max_record_size_of_max_key =
+            leaf_max_cell_size - KEY_LENGTH_SIZE - max_key_size - LEAF_NODE_FLAGS_SIZE;
+
+        // Our smallest keys (see Key) are 2 bytes long, e.g. an empty string,
+        // while the suffix of an internal cell is at least a byte long.
+        let min_key_size = 2;
+        let leaf_max_cells = leaf_space_for_cells
+            / (LEAF_NODE_SLOT_SIZE
+                + KEY_LENGTH_SIZE
+                + min_key_size
+                + LEAF_NODE_FLAGS_SIZE
+                + std::mem::size_of::<u16>());
+        let internal_space_for_cells = node_size - INTERNAL_NODE_HEADER_SIZE;
+        let internal_max_cells = internal_space_for_cells / (INTERNAL_NODE_CELL_HEADER_SIZE + 1);
+
+        #[cfg(test)]
+        let (leaf_max_cells, internal_max_cells) = (
+            leaf_max_cells.min(TEST_LEAF_NODE_MAX_CELLS),
+            internal_max_cells.min(TEST_INTERNAL_NODE_MAX_CELLS),
+        );
+
+        Self {
+            page_size,
+            node_size,
+            leaf_space_for_cells,
+            leaf_max_cell_size,
+            max_key_size,
+            // Small enough for the cell of any key to spill its record.
+            leaf_min_local_payload: max_record_size_of_max_key / 4,
+            leaf_max_cells,
+            internal_space_for_cells,
+            internal_max_cell_size: INTERNAL_NODE_CELL_HEADER_SIZE + max_key_size,
+            internal_max_cells,
+        }
+    }
 
-// We have to define a custom type in order to have a  define
-// serde attributes in Vec<T>.
+    /// The largest record stored in the cell of a key of the given size
+    /// without overflowing.
+    pub fn max_record_size(&self, key_size: usize) -> usize {
+        self.leaf_max_cell_size - KEY_LENGTH_SIZE - key_size - LEAF_NODE_FLAGS_SIZE
+    }
+}
+
+impl Default for NodeLayout {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAGE_SIZE)
+    }
+}
+
+// A leaf cell is made of the length of the key, the key, a flags byte (whether
+// the row is deleted or overflows) and the record of the row (see Row::to_bytes).
 //
-// See: https://github.com/serde-rs/serde/issues/723
+// When the record overflows, the cell only holds the first
+// NodeLayout.leaf_min_local_payload bytes of the record, followed by the page id
+// of the first overflow page holding the rest of it.
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
-pub struct Cell(#[serde(with = "BigArray")] [u8; LEAF_NODE_CELL_SIZE]);
+pub struct Cell {
+    bytes: Vec<u8>,
+
+    // The rest of an overflowing record. It's loaded from the overflow pages by
+    // our Pager when the node is read from disk, hence, reading a row from a
+    // node never has to go through the Pager.
+    #[serde(skip)]
+    overflow: Vec<u8>,
+}
 
+// An internal cell is made of the child pointer and a separator key, which is
+// larger than or equal to every key of the child, but smaller than the keys of
+// the next child, see Key::shortest_separator.
 #[derive(Serialize, Deserialize, PartialEq, Clone)]
-pub struct InternalCell([u8; INTERNAL_NODE_CELL_SIZE]);
+pub struct InternalCell {
+    child_pointer: u32,
+    key: Vec<u8>,
+}
+
+/// The length of the prefix shared by both keys.
+fn shared_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// The number of bytes taken in a page by the internal cells of the keys,
+/// once prefix compressed.
+fn internal_space_of<'k>(keys: impl IntoIterator<Item = &'k [u8]>) -> usize {
+    let mut previous_key: &[u8] = &[];
+    keys.into_iter()
+        .map(|key| {
+            let size =
+                INTERNAL_NODE_CELL_HEADER_SIZE + key.len() - shared_prefix_len(previous_key, key);
+            previous_key = key;
+            size
+        })
+        .sum()
+}
 
 impl Cell {
-    pub fn key(&self) -> u32 {
-        let key_bytes = &self.0[0..4];
-        bincode::deserialize(key_bytes).unwrap()
+    pub fn new(row: &Row, layout: &NodeLayout) -> Self {
+        Self::from_record(&row.id.encode(), row.to_bytes(), row.is_deleted, layout)
+    }
+
+    /// A cell holding the given record under the given encoded key, see
+    /// BPlusTree::insert.
+    pub fn from_record(key: &[u8], record: Vec<u8>, is_deleted: bool, layout: &NodeLayout) -> Self {
+        let mut bytes = Vec::with_capacity(KEY_LENGTH_SIZE + key.len() + LEAF_NODE_FLAGS_SIZE);
+        bytes.extend_from_slice(&(key.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(key);
+        bytes.push(0);
+
+        let mut cell = Self {
+            bytes,
+            overflow: Vec::new(),
+        };
+        cell.write_record(record, is_deleted, layout);
+        cell
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            bytes: bytes.to_vec(),
+            overflow: Vec::new(),
+        }
+    }
+
+    fn key_len(&self) -> usize {
+        u16::from_le_bytes([self.bytes[0], self.bytes[1]]) as usize
+    }
+
+    /// The encoded key of the cell, see Key.
+    pub fn key(&self) -> &[u8] {
+        &self.bytes[KEY_LENGTH_SIZE..self.flags_offset()]
+    }
+
+    fn flags_offset(&self) -> usize {
+        KEY_LENGTH_SIZE + self.key_len()
+    }
+
+    /// The part of the record stored in the cell.
+    fn local_payload(&self) -> &[u8] {
+        let offset = self.flags_offset() + LEAF_NODE_FLAGS_SIZE;
+        if self.has_overflow() {
+            &self.bytes[offset..self.bytes.len() - LEAF_NODE_OVERFLOW_POINTER_SIZE]
+        } else {
+            &self.bytes[offset..]
+        }
+    }
+
+    /// The number of bytes taken by the cell in a page.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// The number of bytes taken by the cell of the row in a page.
+    pub fn size_of(row: &Row, layout: &NodeLayout) -> usize {
+        let key_size = row.id.encode().len();
+        let record_size = row.encoded_len();
+        let payload_size = if record_size <= layout.max_record_size(key_size) {
+            record_size
+        } else {
+            layout.leaf_min_local_payload + LEAF_NODE_OVERFLOW_POINTER_SIZE
+        };
+
+        KEY_LENGTH_SIZE + key_size + LEAF_NODE_FLAGS_SIZE + payload_size
+    }
+
+    fn flags(&self) -> u8 {
+        self.bytes[self.flags_offset()]
     }
 
-    pub fn value(&self) -> &[u8] {
-        let offset = LEAF_NODE_KEY_SIZE;
-        &self.0[offset..offset + LEAF_NODE_VALUE_SIZE]
+    pub fn is_deleted(&self) -> bool {
+        self.flags() & CELL_DELETED != 0
     }
 
-    fn write_key(&mut self, key: u32) {
-        for (i, byte) in key.to_le_bytes().into_iter().enumerate() {
-            self.0[i] = byte;
+    pub fn has_overflow(&self) -> bool {
+        self.flags() & CELL_OVERFLOW != 0
+    }
+
+    /// The whole record of the cell, including the part stored in overflow
+    /// pages.
+    pub fn record(&self) -> Vec<u8> {
+        let mut record = self.local_payload().to_vec();
+        record.extend_from_slice(&self.overflow);
+        record
+    }
+
+    pub fn row(&self) -> Row {
+        let id = u64::decode(self.key());
+        let row = if self.has_overflow() {
+            Row::from_bytes(id, &self.record())
+        } else {
+            Row::from_bytes(id, self.local_payload())
+        };
+
+        Row {
+            is_deleted: self.is_deleted(),
+            ..row
         }
     }
 
     pub fn mark_as_deleted(&mut self) {
-        let offset = LEAF_NODE_KEY_SIZE;
-        self.0[offset + ROW_SIZE - 1] = 1;
+        let offset = self.flags_offset();
+        self.bytes[offset] |= CELL_DELETED;
     }
 
     pub fn mark_as_undeleted(&mut self) {
-        let offset = LEAF_NODE_KEY_SIZE;
-        self.0[offset + ROW_SIZE - 1] = 0;
+        let offset = self.flags_offset();
+        self.bytes[offset] &= !CELL_DELETED;
+    }
+
+    /// The page id of the first overflow page of the cell, if any.
+    ///
+    /// A page id of 0 means the overflow pages haven't been written yet.
+    pub fn overflow_page_id(&self) -> Option<u32> {
+        if !self.has_overflow() {
+            return None;
+        }
+
+        let offset = self.bytes.len() - LEAF_NODE_OVERFLOW_POINTER_SIZE;
+        Some(u32::from_le_bytes(self.bytes[offset..].try_into().unwrap()))
+    }
+
+    pub fn set_overflow_page_id(&mut self, page_id: u32) {
+        assert!(self.has_overflow());
+
+        let offset = self.bytes.len() - LEAF_NODE_OVERFLOW_POINTER_SIZE;
+        self.bytes[offset..].clone_from_slice(&page_id.to_le_bytes());
+    }
+
+    /// The part of the record stored in overflow pages.
+    pub fn overflow(&self) -> &[u8] {
+        &self.overflow
+    }
+
+    pub fn set_overflow(&mut self, overflow: Vec<u8>) {
+        self.overflow = overflow;
     }
 
     // TRADEOFF: We are a clustered table.
     //
     // Where our rows is not stored in a separate heap file but together
     // with the B+ Tree file.
-    pub fn write_value(&mut self, row: &Row) {
-        let offset = LEAF_NODE_KEY_SIZE;
-        let row_in_bytes = bincode::serialize(row).unwrap();
-
-        self.0[offset..(ROW_SIZE + offset)].clone_from_slice(&row_in_bytes[..ROW_SIZE]);
-    }
+    fn write_record(&mut self, mut record: Vec<u8>, is_deleted: bool, layout: &NodeLayout) {
+        let flags_offset = self.flags_offset();
 
-    pub fn update(&mut self, columns: &Vec<String>, new_row: &Row) {
-        let offset = LEAF_NODE_KEY_SIZE;
-        let row_in_bytes = &self.0[offset..(ROW_SIZE + offset)];
-        let mut row: Row = bincode::deserialize(row_in_bytes).unwrap();
+        self.bytes.truncate(flags_offset + LEAF_NODE_FLAGS_SIZE);
+        self.bytes[flags_offset] = if is_deleted { CELL_DELETED } else { 0 };
 
-        for column in columns {
-            row.update(column, new_row);
+        if record.len() <= layout.max_record_size(self.key_len()) {
+            self.bytes.append(&mut record);
+            self.overflow = Vec::new();
+        } else {
+            self.bytes[flags_offset] |= CELL_OVERFLOW;
+            self.overflow = record.split_off(layout.leaf_min_local_payload);
+            self.bytes.append(&mut record);
+            self.bytes.extend_from_slice(&0u32.to_le_bytes());
         }
-
-        self.write_value(&row);
     }
 }
 
 impl std::fmt::Debug for Cell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.key())
+        write!(f, "{:?}", self.key())
     }
 }
 
 impl InternalCell {
-    pub fn new(pointer: u32, key: u32) -> Self {
-        let mut cell = Self([0; INTERNAL_NODE_CELL_SIZE]);
-        cell.write_child_pointer(pointer);
-        cell.write_key(key);
-        cell
+    pub fn new(pointer: u32, key: &[u8]) -> Self {
+        Self {
+            child_pointer: pointer,
+            key: key.to_vec(),
+        }
+    }
+
+    /// Read the internal cell at the start of the bytes, whose key shares a
+    /// prefix with the key of the previous cell, and return the bytes left.
+    fn from_bytes<'b>(bytes: &'b [u8], previous_key: &[u8]) -> (Self, &'b [u8]) {
+        let child_pointer = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+        let shared_len = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+        let suffix_len = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        let (suffix, rest) = bytes[INTERNAL_NODE_CELL_HEADER_SIZE..].split_at(suffix_len);
+
+        let mut key = previous_key[..shared_len].to_vec();
+        key.extend_from_slice(suffix);
+        (Self { child_pointer, key }, rest)
+    }
+
+    /// Append the cell to the bytes, only storing the part of its key not
+    /// shared with the key of the previous cell.
+    fn write_to(&self, bytes: &mut Vec<u8>, previous_key: &[u8]) {
+        let shared_len = shared_prefix_len(previous_key, &self.key);
+        let suffix = &self.key[shared_len..];
+
+        bytes.extend_from_slice(&self.child_pointer.to_le_bytes());
+        bytes.extend_from_slice(&(shared_len as u16).to_le_bytes());
+        bytes.extend_from_slice(&(suffix.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(suffix);
     }
 
     pub fn child_pointer(&self) -> u32 {
-        let bytes = &self.0[0..4];
-        bincode::deserialize(bytes).unwrap()
+        self.child_pointer
     }
 
     pub fn write_child_pointer(&mut self, pointer: u32) {
-        for (i, byte) in pointer.to_le_bytes().into_iter().enumerate() {
-            self.0[i] = byte;
-        }
+        self.child_pointer = pointer;
     }
 
-    pub fn key(&self) -> u32 {
-        let bytes = &self.0[4..8];
-        bincode::deserialize(bytes).unwrap()
+    /// The encoded key of the cell, see Key.
+    pub fn key(&self) -> &[u8] {
+        &self.key
     }
 
-    pub fn write_key(&mut self, key: u32) {
-        let mut j = 4;
-        for i in key.to_le_bytes() {
-            self.0[j] = i;
-            j += 1;
-        }
+    pub fn write_key(&mut self, key: &[u8]) {
+        self.key = key.to_vec();
     }
 }
 
@@ -158,7 +452,7 @@ impl std::fmt::Debug for InternalCell {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "InternalCell(key: {}, child_pointer: {})",
+            "InternalCell(key: {:?}, child_pointer: {})",
             self.key(),
             self.child_pointer()
         )
@@ -193,28 +487,36 @@ pub struct Node {
     pub internal_cells: Vec<InternalCell>,
 
     pub has_initialize: bool,
+
+    // The first overflow page of the cells deleted or replaced since the node
+    // was last written. Our Pager free their overflow pages once it's done
+    // with the node (in mem only).
+    #[serde(skip)]
+    pub freed_overflow_page_ids: Vec<u32>,
+
+    // The sizes of our node for the page size of our database (in mem only).
+    #[serde(skip)]
+    pub layout: NodeLayout,
 }
 
 #[allow(dead_code)]
-pub fn print_constant() {
+pub fn print_constant(layout: &NodeLayout) {
     println!(
         "
-    ROW_SIZE: {ROW_SIZE},
     COMMON_NODE_HEADER_SIZE: {COMMON_NODE_HEADER_SIZE},
     LEAF_NODE_HEADER_SIZE: {LEAF_NODE_HEADER_SIZE},
-    LEAF_NODE_CELL_SIZE: {LEAF_NODE_CELL_SIZE},
-    LEAF_NODE_SPACE_FOR_CELLS: {LEAF_NODE_SPACE_FOR_CELLS},
-    LEAF_NODE_MAX_CELLS: {LEAF_NODE_MAX_CELLS},
+    LEAF_NODE_SLOT_SIZE: {LEAF_NODE_SLOT_SIZE},
+    KEY_LENGTH_SIZE: {KEY_LENGTH_SIZE},
+
+    INTERNAL_NODE_HEADER_SIZE: {INTERNAL_NODE_HEADER_SIZE},
 
-    LEAF_NODE_KEY_SIZE: {LEAF_NODE_KEY_SIZE},
-    LEAF_NODE_VALUE_SIZE: {LEAF_NODE_VALUE_SIZE},
-    MAX_NODE_SIZE: {MAX_NODE_SIZE},
+    {layout:#?}
     "
     );
 }
 
 impl Node {
-    pub fn new(is_root: bool, node_type: NodeType) -> Self {
+    pub fn new(is_root: bool, node_type: NodeType, layout: NodeLayout) -> Self {
         Node {
             node_type,
             is_root,
@@ -225,10 +527,12 @@ impl Node {
             has_initialize: true,
             cells: Vec::new(),
             internal_cells: Vec::new(),
+            freed_overflow_page_ids: Vec::new(),
+            layout,
         }
     }
 
-    pub fn root() -> Self {
+    pub fn root(layout: NodeLayout) -> Self {
         Node {
             node_type: NodeType::Leaf,
             is_root: true,
@@ -239,17 +543,19 @@ impl Node {
             has_initialize: true,
             cells: Vec::new(),
             internal_cells: Vec::new(),
+            freed_overflow_page_ids: Vec::new(),
+            layout,
         }
     }
 
-    pub fn uninitialize() -> Self {
-        let mut node = Self::new(true, NodeType::Leaf);
+    pub fn uninitialize(layout: NodeLayout) -> Self {
+        let mut node = Self::new(true, NodeType::Leaf, layout);
         node.has_initialize = false;
         node
     }
 
-    pub fn new_from_bytes(bytes: &[u8]) -> Self {
-        let mut node = Node::uninitialize();
+    pub fn new_from_bytes(bytes: &[u8], layout: NodeLayout) -> Self {
+        let mut node = Node::uninitialize(layout);
         node.set_common_header(&bytes[0..COMMON_NODE_HEADER_SIZE]);
 
         if node.node_type == NodeType::Leaf {
@@ -269,30 +575,50 @@ impl Node {
         let mut bytes = self.header();
 
         if self.node_type == NodeType::Leaf {
-            for c in &self.cells {
-                let mut cell_bytes = bincode::serialize(c).unwrap();
-                bytes.append(&mut cell_bytes);
+            // Cells are written from the end of the page, with their slots
+            // written right after our header.
+            let mut cell_bytes = vec![0; self.layout.leaf_space_for_cells];
+            let mut offset = self.layout.leaf_space_for_cells;
+
+            for (i, c) in self.cells.iter().enumerate() {
+                offset -= c.len();
+                cell_bytes[offset..offset + c.len()].clone_from_slice(&c.bytes);
+
+                let slot = i * LEAF_NODE_SLOT_SIZE;
+                cell_bytes[slot..slot + 2].clone_from_slice(&(offset as u16).to_le_bytes());
+                cell_bytes[slot + 2..slot + 4].clone_from_slice(&(c.len() as u16).to_le_bytes());
             }
+
+            assert!(
+                self.cells.len() * LEAF_NODE_SLOT_SIZE <= offset,
+                "leaf node overflow"
+            );
+            bytes.append(&mut cell_bytes);
         } else {
+            let mut previous_key: &[u8] = &[];
             for c in &self.internal_cells {
-                let mut cell_bytes = bincode::serialize(c).unwrap();
-                bytes.append(&mut cell_bytes);
+                c.write_to(&mut bytes, previous_key);
+                previous_key = c.key();
             }
+            assert!(
+                bytes.len() <= self.layout.node_size,
+                "internal node overflow"
+            );
         }
 
         // Outdated a bit:
         // Okay, we need to backfill the space because we are assuming
-        // per page is always with PAGE_SIZE.
+        // per page is always with our page size.
         //
         // If we didn't fill up the space, what would happen is when we read
         // from file, we will not have an accurate number of pages because file with
-        // PAGE_SIZE might contain multiple pages. In theory, you can still keep
+        // our page size might contain multiple pages. In theory, you can still keep
         // track of the number of pages in the file, tricky part would then be,
         // how do we identify the page offset of each page? We will have to read each
         // page to find out the next page offset.
         //
         // So long story short, let's just backfill the space...
-        let remaining_space = MAX_NODE_SIZE - bytes.len();
+        let remaining_space = self.layout.node_size - bytes.len();
         let mut vec = vec![0; remaining_space];
         bytes.append(&mut vec);
 
@@ -327,38 +653,28 @@ impl Node {
     }
 
     pub fn set_leaf_cells(&mut self, cell_bytes: &[u8]) {
-        // The reason we can't use bincode to directly deserialize our bytes
-        // into Vec<Cell> is because Vec<Cell> binary format will includes a
-        // u64 (8 bytes) column that (seems like) representing the length of the
-        // Vec<Cell>.
-        //
-        // However, when we persist a cell to disk, we are only writing Cell
-        // binary format and we are tracking our own number of cells by using a
-        // separate column with a u32 (4 bytes) data type.
-        //
-        // Hence, to deserialize with bincode directly into Vec<Cell>,  we append
-        // the num_of_cells as 8 bytes into our bytes first.
-        let max_size = self.num_of_cells as usize * LEAF_NODE_CELL_SIZE;
-        let mut bytes = (self.num_of_cells as u64).to_le_bytes().to_vec();
-        let cell_bytes = &mut cell_bytes[0..max_size].to_vec();
-        bytes.append(cell_bytes);
-        self.cells = bincode::deserialize_from(&bytes[..]).unwrap();
-
-        // Alternatively, we could just deserialize a cell bytes into Cell
-        // and insert into Vec<Cell> manually.
-        // for i in 0..self.num_of_cells as usize {
-        //     let offset = i * LEAF_NODE_CELL_SIZE;
-        //     let cell = bincode::deserialize(&bytes[offset..offset + LEAF_NODE_CELL_SIZE]).unwrap();
-        //     self.cells.insert(i, cell);
-        // }
-    }
-
-    pub fn set_internal_cells(&mut self, cell_bytes: &[u8]) {
-        let max_size = self.num_of_cells as usize * INTERNAL_NODE_CELL_SIZE;
-        let mut bytes = (self.num_of_cells as u64).to_le_bytes().to_vec();
-        let cell_bytes = &mut cell_bytes[0..max_size].to_vec();
-        bytes.append(cell_bytes);
-        self.internal_cells = bincode::deserialize_from(&bytes[..]).unwrap();
+        self.cells = (0..self.num_of_cells as usize)
+            .map(|i| {
+                let slot = &cell_bytes[i * LEAF_NODE_SLOT_SIZE..(i + 1) * LEAF_NODE_SLOT_SIZE];
+                let offset = u16::from_le_bytes([slot[0], slot[1]]) as usize;
+                let len = u16::from_le_bytes([slot[2], slot[3]]) as usize;
+
+                Cell::from_bytes(&cell_bytes[offset..offset + len])
+            })
+            .collect();
+    }
+
+    pub fn set_internal_cells(&mut self, mut cell_bytes: &[u8]) {
+        self.internal_cells = Vec::with_capacity(self.num_of_cells as usize);
+        for _ in 0..self.num_of_cells {
+            let previous_key = self
+                .internal_cells
+                .last()
+                .map_or(&[][..], InternalCell::key);
+            let (cell, rest) = InternalCell::from_bytes(cell_bytes, previous_key);
+            cell_bytes = rest;
+            self.internal_cells.push(cell);
+        }
     }
 
     pub fn header(&self) -> Vec<u8> {
@@ -391,27 +707,28 @@ impl Node {
         result
     }
 
-    pub fn get_max_key(&self) -> u32 {
+    pub fn get_max_key(&self) -> Vec<u8> {
         match self.node_type {
             NodeType::Leaf => {
                 let cell = &self.cells[self.num_of_cells as usize - 1];
-                cell.key()
+                cell.key().to_vec()
             }
             NodeType::Internal => {
                 let internal_cell = &self.internal_cells[self.num_of_cells as usize - 1];
-                internal_cell.key()
+                internal_cell.key().to_vec()
             }
         }
     }
 
-    pub fn search(&self, key: u32) -> Result<usize, usize> {
+    /// Search the encoded key, comparing keys byte-wise, see Key.
+    pub fn search(&self, key: &[u8]) -> Result<usize, usize> {
         if self.node_type == NodeType::Leaf {
-            return self.cells.binary_search_by(|cell| cell.key().cmp(&key));
+            return self.cells.binary_search_by(|cell| cell.key().cmp(key));
         }
 
         let index = match self
             .internal_cells
-            .binary_search_by(|cell| cell.key().cmp(&key))
+            .binary_search_by(|cell| cell.key().cmp(key))
         {
             Ok(index) => index,
             Err(index) => index,
@@ -431,42 +748,139 @@ impl Node {
     }
 
     pub fn get_row(&self, cell_num: usize) -> Option<Row> {
-        self.cells.get(cell_num).map(|cell| {
-            let bytes = cell.value();
-            bincode::deserialize(bytes).unwrap()
-        })
+        self.cells.get(cell_num).map(Cell::row)
     }
 
     pub fn get(&self, cell_num: usize) -> Row {
-        let bytes = self.cells[cell_num].value();
-        bincode::deserialize(bytes).unwrap()
+        self.cells[cell_num].row()
     }
 
     pub fn insert(&mut self, row: &Row, cursor: &Cursor) {
-        let num_of_cells = self.num_of_cells as usize;
+        self.insert_cell(Cell::new(row, &self.layout), cursor);
+    }
 
-        // Make room for new cell.
-        //
-        // Else, the current cell at cell_num will be override by
-        // new cell.
-        if cursor.cell_num < num_of_cells {
-            self.cells
-                .insert(cursor.cell_num, Cell([0; LEAF_NODE_CELL_SIZE]));
+    pub fn insert_cell(&mut self, cell: Cell, cursor: &Cursor) {
+        self.num_of_cells += 1;
+        self.cells.insert(cursor.cell_num, cell);
+    }
+
+    /// Update the values at the given indexes with the values of new_row.
+    ///
+    /// Return false without modifying the node if the updated row
+    /// doesn't fit in the node anymore.
+    pub fn update(&mut self, cell_num: usize, indexes: &[usize], new_row: &Row) -> bool {
+        let Some(cell) = self.cells.get(cell_num) else {
+            return false;
+        };
+
+        let mut row = cell.row();
+        for index in indexes {
+            row.update(*index, new_row);
         }
 
-        if self.cells.get(cursor.cell_num).is_none() {
-            self.cells
-                .insert(cursor.cell_num, Cell([0; LEAF_NODE_CELL_SIZE]))
+        let new_cell = Cell::new(&row, &self.layout);
+        if self.free_space() + cell.len() < new_cell.len() {
+            return false;
         }
 
-        self.num_of_cells += 1;
-        self.cells[cursor.cell_num].write_key(row.id);
-        self.cells[cursor.cell_num].write_value(row);
+        let old_cell = std::mem::replace(&mut self.cells[cell_num], new_cell);
+        self.free_overflow(&old_cell);
+        true
+    }
+
+    fn free_overflow(&mut self, cell: &Cell) {
+        if let Some(page_id) = cell.overflow_page_id().filter(|&page_id| page_id != 0) {
+            self.freed_overflow_page_ids.push(page_id);
+        }
+    }
+
+    fn used_space(&self) -> usize {
+        self.cells
+            .iter()
+            .map(|c| c.len() + LEAF_NODE_SLOT_SIZE)
+            .sum()
+    }
+
+    fn free_space(&self) -> usize {
+        self.layout.leaf_space_for_cells - self.used_space()
+    }
+
+    /// Whether the row can be inserted into this leaf node without splitting it.
+    pub fn has_space_for(&self, row: &Row) -> bool {
+        self.has_space_for_cell(Cell::size_of(row, &self.layout))
+    }
+
+    /// Whether a cell of the given size can be inserted into this leaf node
+    /// without splitting it.
+    pub fn has_space_for_cell(&self, cell_size: usize) -> bool {
+        self.cells.len() < self.layout.leaf_max_cells
+            && cell_size + LEAF_NODE_SLOT_SIZE <= self.free_space()
+    }
+
+    /// Whether inserting any row into this leaf node might cause it to split.
+    pub fn might_split(&self) -> bool {
+        !self.has_space_for_cell(self.layout.leaf_max_cell_size)
+    }
+
+    /// Whether the leaf node is less than half full and should be merged
+    /// with one of its siblings.
+    pub fn is_underflow(&self) -> bool {
+        self.cells.len() <= self.layout.leaf_max_cells / 2
+            && self.used_space() <= self.layout.leaf_space_for_cells / 2
+    }
+
+    /// Whether deleting the given key might cause this leaf node to be merged.
+    pub fn might_underflow(&self, key: &[u8]) -> bool {
+        match self.search(key) {
+            Ok(index) => {
+                self.cells.len() - 1 <= self.layout.leaf_max_cells / 2
+                    && self.used_space() - self.cells[index].len() - LEAF_NODE_SLOT_SIZE
+                        <= self.layout.leaf_space_for_cells / 2
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Whether the cells of both leaf nodes fit into a single node.
+    pub fn can_merge_with(&self, other: &Node) -> bool {
+        self.cells.len() + other.cells.len() <= self.layout.leaf_max_cells
+            && self.used_space() + other.used_space() <= self.layout.leaf_space_for_cells
+    }
+
+    /// Move the upper half of the cells of an overflowed leaf node
+    /// into a new node.
+    ///
+    /// TRADEOFF: We split our cells in the middle, rather than splitting
+    /// at the middle of our used space, and only move the split point when
+    /// either half doesn't fit into a node.
+    ///
+    /// It keeps the number of rows between nodes even, which our tests rely on.
+    pub fn split(&mut self) -> Node {
+        let mut split_at = self.cells.len() - self.cells.len() / 2;
+        let space_for_cells = self.layout.leaf_space_for_cells;
+        let space_of =
+            |cells: &[Cell]| -> usize { cells.iter().map(|c| c.len() + LEAF_NODE_SLOT_SIZE).sum() };
+
+        while space_of(&self.cells[..split_at]) > space_for_cells {
+            split_at -= 1;
+        }
+
+        while space_of(&self.cells[split_at..]) > space_for_cells {
+            split_at += 1;
+        }
+
+        let mut right_node = Node::new(false, self.node_type, self.layout);
+        right_node.cells = self.cells.split_off(split_at);
+        right_node.num_of_cells = right_node.cells.len() as u32;
+        self.num_of_cells = self.cells.len() as u32;
+
+        right_node
     }
 
     pub fn delete(&mut self, cell_num: usize) {
         if self.node_type == NodeType::Leaf {
-            self.cells.remove(cell_num);
+            let cell = self.cells.remove(cell_num);
+            self.free_overflow(&cell);
             self.num_of_cells -= 1;
         } else {
             unimplemented!("implement delete for internal node")
@@ -477,12 +891,99 @@ impl Node {
         self.internal_cells.insert(index, cell);
     }
 
-    /// Return the index of the given key.
-    pub fn internal_search(&self, key: u32) -> usize {
-        match self.internal_cells.binary_search_by(|c| c.key().cmp(&key)) {
-            Ok(index) => index,
-            Err(index) => index,
+    fn internal_used_space(&self) -> usize {
+        internal_space_of(self.internal_cells.iter().map(InternalCell::key))
+    }
+
+    /// Whether the internal node has more cells than fit into a page, and
+    /// must be split.
+    pub fn is_internal_overflow(&self) -> bool {
+        self.internal_cells.len() > self.layout.internal_max_cells
+            || self.internal_used_space() > self.layout.internal_space_for_cells
+    }
+
+    /// Whether inserting any key into this internal node might cause it to split.
+    pub fn internal_might_split(&self) -> bool {
+        self.internal_cells.len() + 1 > self.layout.internal_max_cells
+            || self.internal_used_space() + self.layout.internal_max_cell_size
+                > self.layout.internal_space_for_cells
+    }
+
+    /// The number of cells below which an internal node steals a cell from
+    /// its sibling, see BPlusTree::steal_from_sibling.
+    pub fn internal_min_cells(&self) -> usize {
+        (self.layout.internal_max_cells / 2).max(1)
+    }
+
+    /// Whether the internal node is at most half full and should be merged
+    /// with one of its siblings.
+    pub fn is_internal_underflow(&self) -> bool {
+        self.internal_cells.len() <= self.internal_min_cells()
+            && self.internal_used_space() <= self.layout.internal_space_for_cells / 2
+    }
+
+    /// Whether removing any key from this internal node might cause it to
+    /// be merged.
+    pub fn internal_might_underflow(&self) -> bool {
+        !self.internal_cells.is_empty()
+            && self.internal_cells.len() - 1 <= self.internal_min_cells()
+            && self
+                .internal_used_space()
+                .saturating_sub(self.layout.internal_max_cell_size)
+                <= self.layout.internal_space_for_cells / 2
+    }
+
+    /// Whether the internal node has less than internal_min_cells cells,
+    /// and space left for a cell stolen from one of its siblings.
+    pub fn can_steal_internal_cell(&self) -> bool {
+        self.internal_cells.len() < self.internal_min_cells()
+            && self.internal_used_space() + self.layout.internal_max_cell_size
+                <= self.layout.internal_space_for_cells
+    }
+
+    /// Whether the key of the internal cell at the given index can be
+    /// replaced with the given key without overflowing the node.
+    pub fn has_space_for_internal_key(&self, index: usize, key: &[u8]) -> bool {
+        let keys =
+            self.internal_cells
+                .iter()
+                .enumerate()
+                .map(|(i, c)| if i == index { key } else { c.key() });
+
+        internal_space_of(keys) <= self.layout.internal_space_for_cells
+    }
+
+    /// Return the index of the cell moved up to the parent when splitting an
+    /// overflowed internal node, with the cells before and after it going to
+    /// the left and right nodes.
+    ///
+    /// Like Node::split, we split our cells in the middle, unless either half
+    /// doesn't fit into a node.
+    pub fn internal_split_index(&self) -> usize {
+        let mut split_at = self.internal_cells.len() / 2;
+        let space_for_cells = self.layout.internal_space_for_cells;
+        let space_of =
+            |cells: &[InternalCell]| -> usize { internal_space_of(cells.iter().map(|c| c.key())) };
+
+        while space_of(&self.internal_cells[..split_at]) > space_for_cells {
+            split_at -= 1;
+        }
+
+        while space_of(&self.internal_cells[split_at + 1..]) > space_for_cells {
+            split_at += 1;
         }
+
+        split_at
+    }
+
+    /// Whether the cells of both internal nodes fit into a single node,
+    /// together with the key pulled down from their parent.
+    pub fn can_merge_internal_with(&self, other: &Node) -> bool {
+        self.internal_cells.len() + other.internal_cells.len() < self.layout.internal_max_cells
+            && self.internal_used_space()
+                + other.internal_used_space()
+                + self.layout.internal_max_cell_size
+                <= self.layout.internal_space_for_cells
     }
 
     /// Return the index of the given child_pointer.
@@ -497,13 +998,6 @@ impl Node {
         self.internal_cells.len()
     }
 
-    pub fn update_internal_key(&mut self, old_key: u32, new_key: u32) {
-        let index = self.internal_search(old_key);
-        if index < self.internal_cells.len() {
-            self.internal_cells[index].write_key(new_key);
-        }
-    }
-
     pub fn siblings(&self, child_offset: u32) -> (Option<usize>, Option<usize>) {
         let index = self.internal_search_child_pointer(child_offset);
 
@@ -547,6 +1041,236 @@ mod test {
 
     #[test]
     fn basic() {
-        print_constant();
+        print_constant(&NodeLayout::default());
+    }
+
+    #[test]
+    fn internal_node_at_full_capacity() {
+        // The capacity of 64-bit keys outside of our tests, which is limited by
+        // our space rather than NodeLayout.internal_max_cells.
+        let layout = NodeLayout {
+            internal_max_cells: usize::MAX,
+            ..NodeLayout::default()
+        };
+
+        let mut node = Node::new(true, NodeType::Internal, layout);
+        while !node.internal_might_split() {
+            let i = node.internal_cells.len();
+            let key = (i as u64 * 10).encode();
+            node.internal_insert(i, InternalCell::new(i as u32 + 2, &key));
+            node.num_of_cells += 1;
+        }
+        let max_cells = node.internal_cells.len();
+        node.right_child_offset = max_cells as u32 + 2;
+        assert!(!node.is_internal_overflow());
+
+        // Our keys share their first bytes, so more of them fit than without
+        // prefix compression.
+        let uncompressed_cell_size = INTERNAL_NODE_CELL_HEADER_SIZE + 8;
+        assert!(max_cells > layout.internal_space_for_cells / uncompressed_cell_size);
+
+        let bytes = node.to_bytes();
+        assert_eq!(bytes.len(), layout.node_size);
+
+        let from_bytes = Node::new_from_bytes(&bytes, layout);
+        assert_eq!(from_bytes.num_of_cells, max_cells as u32);
+        assert_eq!(from_bytes.right_child_offset, node.right_child_offset);
+        assert_eq!(from_bytes.internal_cells, node.internal_cells);
+        assert_eq!(from_bytes.search(&95u64.encode()), Ok(12));
+    }
+
+    #[test]
+    fn internal_node_holds_more_string_keys_once_prefix_compressed() {
+        let layout = NodeLayout {
+            internal_max_cells: usize::MAX,
+            ..NodeLayout::default()
+        };
+        let key = |i: usize| format!("tenant-42/user-{i:05}").encode();
+
+        let mut node = Node::new(true, NodeType::Internal, layout);
+        while !node.internal_might_split() {
+            let i = node.internal_cells.len();
+            node.internal_insert(i, InternalCell::new(i as u32 + 2, &key(i)));
+            node.num_of_cells += 1;
+        }
+        let max_cells = node.internal_cells.len();
+        assert!(!node.is_internal_overflow());
+
+        // Only the last digits of our keys are stored, so our page holds
+        // several times more keys than without prefix compression.
+        let uncompressed_cell_size = INTERNAL_NODE_CELL_HEADER_SIZE + key(0).len();
+        let uncompressed_max_cells = layout.internal_space_for_cells / uncompressed_cell_size;
+        assert!(max_cells > 2 * uncompressed_max_cells);
+
+        let from_bytes = Node::new_from_bytes(&node.to_bytes(), layout);
+        assert_eq!(from_bytes.internal_cells, node.internal_cells);
+        assert_eq!(from_bytes.search(&key(100)), Ok(102));
+    }
+
+    #[test]
+    fn internal_node_split_by_space() {
+        let layout = NodeLayout {
+            internal_max_cells: usize::MAX,
+            ..NodeLayout::default()
+        };
+        let mut node = Node::new(true, NodeType::Internal, layout);
+        let long_key = |i: usize| format!("{i:03}{}", "a".repeat(200)).encode();
+
+        // A few long keys at the end of many short keys.
+        for i in 0..100 {
+            node.internal_insert(i, InternalCell::new(i as u32, &format!("{i:03}").encode()));
+        }
+        for i in 100..120 {
+            node.internal_insert(i, InternalCell::new(i as u32, &long_key(i)));
+        }
+        node.num_of_cells = node.internal_cells.len() as u32;
+        assert!(node.is_internal_overflow());
+
+        // Splitting in the middle would leave the long keys overflowing our
+        // right node.
+        let split_at = node.internal_split_index();
+        assert_eq!(split_at, 100);
+        let space_of = |cells: &[InternalCell]| internal_space_of(cells.iter().map(|c| c.key()));
+        assert!(space_of(&node.internal_cells[..split_at]) <= layout.internal_space_for_cells);
+        assert!(space_of(&node.internal_cells[split_at + 1..]) <= layout.internal_space_for_cells);
+    }
+
+    fn text_row(id: u64, len: usize) -> Row {
+        Row::new(id, vec![crate::row::Value::Text("a".repeat(len))])
+    }
+
+    fn leaf_with_rows(rows: &[Row]) -> Node {
+        leaf_with_rows_and_layout(rows, NodeLayout::default())
+    }
+
+    fn leaf_with_rows_and_layout(rows: &[Row], layout: NodeLayout) -> Node {
+        let mut node = Node::new(true, NodeType::Leaf, layout);
+        for (i, row) in rows.iter().enumerate() {
+            let cursor = Cursor {
+                page_num: 0,
+                cell_num: i,
+                key_existed: false,
+                end_of_table: true,
+            };
+            node.insert(row, &cursor);
+        }
+        node
+    }
+
+    #[test]
+    fn slotted_leaf_to_bytes_and_from_bytes() {
+        let rows = vec![text_row(1, 10), text_row(2, 500), text_row(3, 0)];
+        let mut node = leaf_with_rows(&rows);
+        node.cells[1].mark_as_deleted();
+
+        let bytes = node.to_bytes();
+        assert_eq!(bytes.len(), node.layout.node_size);
+
+        let node = Node::new_from_bytes(&bytes, node.layout);
+        assert_eq!(node.num_of_cells, 3);
+        assert_eq!(node.get(0), rows[0]);
+        assert!(node.get(1).is_deleted);
+        assert_eq!(node.get(1).values, rows[1].values);
+        assert_eq!(node.get(2), rows[2]);
+    }
+
+    #[test]
+    fn layout_follows_page_size() {
+        let small = NodeLayout::new(1024);
+        let large = NodeLayout::new(64 * 1024);
+        assert_eq!(small.node_size, 1024 - PAGE_HEADER_BYTES);
+        let key_size = std::mem::size_of::<u64>();
+        assert!(small.max_record_size(key_size) < NodeLayout::default().max_record_size(key_size));
+        assert!(large.max_record_size(key_size) > NodeLayout::default().max_record_size(key_size));
+
+        // The same rows overflow in small pages, but not in large pages.
+        let rows = vec![text_row(1, 10), text_row(2, 1000)];
+        for layout in [small, large] {
+            let node = leaf_with_rows_and_layout(&rows, layout);
+            assert_eq!(node.cells[1].has_overflow(), layout == small);
+
+            let bytes = node.to_bytes();
+            assert_eq!(bytes.len(), layout.node_size);
+            let mut from_bytes = Node::new_from_bytes(&bytes, layout);
+            from_bytes.cells[1].set_overflow(node.cells[1].overflow().to_vec());
+            assert_eq!(from_bytes.get(0), rows[0]);
+            assert_eq!(from_bytes.get(1), rows[1]);
+        }
+    }
+
+    #[test]
+    fn split_and_merge_by_free_space() {
+        // Large rows fill up a node long before it reaches NodeLayout.leaf_max_cells.
+        let rows: Vec<Row> = (1..=7).map(|i| text_row(i, 500)).collect();
+        let mut node = leaf_with_rows(&rows);
+        assert!(node.num_of_cells < node.layout.leaf_max_cells as u32);
+        assert!(!node.has_space_for(&text_row(8, 500)));
+        assert!(node.has_space_for(&text_row(8, 10)));
+        assert!(node.might_split());
+
+        node.insert(
+            &text_row(8, 500),
+            &Cursor {
+                page_num: 0,
+                cell_num: 7,
+                key_existed: false,
+                end_of_table: true,
+            },
+        );
+        let right = node.split();
+        assert_eq!(node.num_of_cells, 4);
+        assert_eq!(right.num_of_cells, 4);
+        assert_eq!(right.get(0).id, 5);
+
+        assert!(!node.is_underflow());
+        assert!(!node.can_merge_with(&right));
+        assert!(node.might_underflow(&1u64.encode()));
+
+        node.delete(0);
+        assert!(node.is_underflow());
+        assert!(node.can_merge_with(&right));
+    }
+
+    #[test]
+    fn overflowing_cell() {
+        let layout = NodeLayout::default();
+        let row = text_row(1, 3 * layout.max_record_size(std::mem::size_of::<u64>()));
+        let mut node = leaf_with_rows(std::slice::from_ref(&row));
+        let cell = &node.cells[0];
+        assert!(cell.has_overflow());
+        assert_eq!(cell.len(), Cell::size_of(&row, &layout));
+        assert!(cell.len() < layout.leaf_max_cell_size);
+        assert_eq!(cell.overflow_page_id(), Some(0));
+        assert_eq!(node.get(0), row);
+
+        // Only the local payload and the overflow page id are stored in the node.
+        node.cells[0].set_overflow_page_id(5);
+        let mut from_bytes = Node::new_from_bytes(&node.to_bytes(), layout);
+        assert_eq!(from_bytes.cells[0].overflow_page_id(), Some(5));
+        assert!(from_bytes.cells[0].overflow().is_empty());
+
+        from_bytes.cells[0].set_overflow(node.cells[0].overflow().to_vec());
+        assert_eq!(from_bytes.get(0), row);
+
+        // The overflow pages of removed cells are tracked so they can be freed.
+        assert!(node.update(0, &[0], &text_row(1, 10)));
+        assert_eq!(node.freed_overflow_page_ids, vec![5]);
+        assert!(!node.cells[0].has_overflow());
+    }
+
+    #[test]
+    fn update_checks_free_space() {
+        let rows: Vec<Row> = (1..=7).map(|i| text_row(i, 500)).collect();
+        let mut node = leaf_with_rows(&rows);
+        let max_record_size = node.layout.max_record_size(std::mem::size_of::<u64>());
+
+        // Growing a row beyond the free space of the node is rejected.
+        assert!(!node.update(1, &[0], &text_row(2, max_record_size - 7)));
+        assert_eq!(node.get(1), rows[1]);
+
+        // Until other rows make room for it.
+        assert!(node.update(0, &[0], &text_row(1, 10)));
+        assert!(node.update(1, &[0], &text_row(2, max_record_size - 7)));
+        assert_eq!(node.get(1), text_row(2, max_record_size - 7));
     }
 }
@@ -0,0 +1,93 @@
+use super::page::{verify_checksum, write_checksum, Page, PAGE_HEADER_BYTES};
+use super::pager::PagerError;
+
+// Stored where a node stores its NodeType, so an overflow page can never be
+// mistaken for a B+ tree node.
+pub const OVERFLOW_PAGE_TYPE: u8 = 2;
+const OVERFLOW_PAGE_HEADER_SIZE: usize =
+    std::mem::size_of::<u8>() + std::mem::size_of::<u32>() + std::mem::size_of::<u16>();
+
+/// The number of bytes of a record stored in every overflow page.
+pub fn overflow_page_payload_size(page_size: usize) -> usize {
+    page_size - PAGE_HEADER_BYTES - OVERFLOW_PAGE_HEADER_SIZE
+}
+
+// Similar to SQLite, the part of a record that doesn't fit in a leaf cell is
+// stored in a linked list of overflow pages.
+//
+//   [page header][type: u8][next page id: u32][payload length: u16][payload]
+//
+// where a next page id of 0 marks the end of the chain.
+#[derive(Debug, PartialEq)]
+pub struct OverflowPage {
+    pub next_page_id: u32,
+    pub payload: Vec<u8>,
+}
+
+impl OverflowPage {
+    pub fn new(next_page_id: u32, payload: &[u8]) -> Self {
+        Self {
+            next_page_id,
+            payload: payload.to_vec(),
+        }
+    }
+
+    pub fn from_bytes(page_id: usize, bytes: &[u8]) -> Result<Self, PagerError> {
+        verify_checksum(page_id, bytes)?;
+
+        // A valid page of another type means our chain is broken.
+        let bytes = &bytes[PAGE_HEADER_BYTES..];
+        if bytes[0] != OVERFLOW_PAGE_TYPE {
+            return Err(PagerError::CorruptedPage(page_id));
+        }
+
+        let next_page_id = u32::from_le_bytes(bytes[1..5].try_into().unwrap());
+        let len = u16::from_le_bytes([bytes[5], bytes[6]]) as usize;
+        let payload = bytes[OVERFLOW_PAGE_HEADER_SIZE..OVERFLOW_PAGE_HEADER_SIZE + len].to_vec();
+
+        Ok(Self {
+            next_page_id,
+            payload,
+        })
+    }
+
+    pub fn as_bytes(&self, page_id: usize, page_size: usize) -> Vec<u8> {
+        assert!(self.payload.len() <= overflow_page_payload_size(page_size));
+
+        // Overflow pages share the same page header as our nodes.
+        let mut bytes = Page::new(Some(page_id)).header_bytes();
+        bytes.push(OVERFLOW_PAGE_TYPE);
+        bytes.extend_from_slice(&self.next_page_id.to_le_bytes());
+        bytes.extend_from_slice(&(self.payload.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes.resize(page_size, 0);
+        write_checksum(&mut bytes);
+
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::DEFAULT_PAGE_SIZE;
+
+    #[test]
+    fn as_bytes_and_from_bytes() {
+        for page_size in [1024, DEFAULT_PAGE_SIZE, 64 * 1024] {
+            let payload = vec![1; overflow_page_payload_size(page_size)];
+            let page = OverflowPage::new(7, &payload);
+            let bytes = page.as_bytes(3, page_size);
+            assert_eq!(bytes.len(), page_size);
+            assert_eq!(OverflowPage::from_bytes(3, &bytes).unwrap(), page);
+        }
+
+        let page = OverflowPage::new(0, b"hello");
+        let mut bytes = page.as_bytes(3, DEFAULT_PAGE_SIZE);
+        assert_eq!(OverflowPage::from_bytes(3, &bytes).unwrap(), page);
+
+        bytes[PAGE_HEADER_BYTES + 10] ^= 1;
+        let result = OverflowPage::from_bytes(3, &bytes);
+        assert!(matches!(result, Err(PagerError::CorruptedPage(3))));
+    }
+}
@@ -1,15 +1,45 @@
 use serde::{Deserialize, Serialize};
 
-use super::node::Node;
+use super::node::{Node, NodeLayout};
+use super::pager::PagerError;
 use crate::row::Row;
 
+const PAGE_CHECKSUM_SIZE: usize = std::mem::size_of::<u32>();
+
 // Since bincode serialize Option<usize> as [0, 0, 0, 0, 0]
 //                                           -  ----------
 //                                           ^       ^
 //                                        Option   usize
 //
 // Hence, we need to add one more byte.
-pub const PAGE_HEADER_BYTES: usize = 1 + std::mem::size_of::<usize>() + std::mem::size_of::<u32>();
+pub const PAGE_HEADER_BYTES: usize =
+    PAGE_CHECKSUM_SIZE + 1 + std::mem::size_of::<usize>() + std::mem::size_of::<u32>();
+
+// TRADEOFF: Every page starts with a CRC32C checksum of the rest of the page.
+//
+//   [checksum: u32][page id: Option<usize>][lsn: u32][body]
+//
+// Similar to the data checksums of PostgreSQL, it allows us to detect torn
+// writes (a crash in the middle of writing a page) and bit rot when a page is
+// read, instead of deserializing garbage. It costs us 4 bytes per page and a
+// checksum computation every time a page is read or written.
+//
+// Our header page isn't checksummed, as it's validated by its magic instead.
+/// Compute the checksum of the page and store it in its first bytes.
+pub fn write_checksum(bytes: &mut [u8]) {
+    let checksum = crc32c::crc32c(&bytes[PAGE_CHECKSUM_SIZE..]);
+    bytes[..PAGE_CHECKSUM_SIZE].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Verify the checksum of a page read from disk.
+pub fn verify_checksum(page_id: usize, bytes: &[u8]) -> Result<(), PagerError> {
+    let expected = u32::from_le_bytes(bytes[..PAGE_CHECKSUM_SIZE].try_into().unwrap());
+    if crc32c::crc32c(&bytes[PAGE_CHECKSUM_SIZE..]) == expected {
+        Ok(())
+    } else {
+        Err(PagerError::CorruptedPage(page_id))
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Page {
@@ -26,6 +56,13 @@ pub struct Page {
     pub is_dirty: bool,
     #[serde(skip)]
     pub pin_count: usize,
+    // Read ahead of a sequential scan, and not fetched since then.
+    #[serde(skip)]
+    pub is_prefetched: bool,
+    // Only read by sequential scans since it's in our buffer pool, so it's
+    // evicted first, see Replacer::unpin_cold.
+    #[serde(skip)]
+    pub is_cold: bool,
 }
 
 impl Page {
@@ -35,19 +72,26 @@ impl Page {
             lsn: 0,
             is_dirty: false,
             pin_count: 0,
+            is_prefetched: false,
+            is_cold: false,
             node: None,
         }
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Self {
-        let header_bytes = &bytes[..PAGE_HEADER_BYTES];
-        let mut page: Page = bincode::deserialize(header_bytes).unwrap();
+    pub fn from_bytes(page_id: usize, bytes: &[u8]) -> Result<Self, PagerError> {
+        verify_checksum(page_id, bytes)?;
 
+        let header_bytes = &bytes[PAGE_CHECKSUM_SIZE..PAGE_HEADER_BYTES];
+        let mut page: Page =
+            bincode::deserialize(header_bytes).map_err(|_| PagerError::CorruptedPage(page_id))?;
+
+        // Our pages are always read and written as a whole, so the size of
+        // the page is the page size of our database.
         let body_bytes = &bytes[PAGE_HEADER_BYTES..];
-        let node = Node::new_from_bytes(body_bytes);
+        let node = Node::new_from_bytes(body_bytes, NodeLayout::new(bytes.len()));
         page.node = Some(node);
 
-        page
+        Ok(page)
     }
 
     pub fn deallocate(&mut self) {
@@ -55,6 +99,8 @@ impl Page {
         self.node = None;
         self.is_dirty = false;
         self.pin_count = 0;
+        self.is_prefetched = false;
+        self.is_cold = false;
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
@@ -63,11 +109,20 @@ impl Page {
         assert!(self.page_id.is_some());
         assert!(self.node.is_some());
 
-        let mut header_bytes = bincode::serialize(&self).unwrap();
+        let mut bytes = self.header_bytes();
         let mut body_bytes = self.node.as_ref().unwrap().to_bytes();
 
-        header_bytes.append(&mut body_bytes);
-        header_bytes
+        bytes.append(&mut body_bytes);
+        write_checksum(&mut bytes);
+        bytes
+    }
+
+    /// Our page header, with the checksum left to be written once the
+    /// rest of the page is written, see write_checksum.
+    pub fn header_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0; PAGE_CHECKSUM_SIZE];
+        bytes.append(&mut bincode::serialize(&self).unwrap());
+        bytes
     }
 
     // TRADEOFF: We are always cloning/copying the row values
@@ -102,28 +157,27 @@ impl Page {
             })
     }
 
-    pub fn update_row(&mut self, slot_num: usize, new_row: &Row, columns: &Vec<String>) -> bool {
+    /// Update the values at the given indexes of Row.values, see Schema::value_indexes.
+    pub fn update_row(&mut self, slot_num: usize, new_row: &Row, indexes: &[usize]) -> bool {
         self.node
             .as_mut()
-            .and_then(|node| node.get_mut_cell(slot_num))
-            .map_or(false, |cell| {
-                cell.update(columns, new_row);
-                true
-            })
+            .map_or(false, |node| node.update(slot_num, indexes, new_row))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::storage::{Cursor, NodeType};
+    use crate::row::Value;
+    use crate::storage::{Cursor, NodeType, DEFAULT_PAGE_SIZE};
+    use std::str::FromStr;
 
     #[test]
     fn deallocate() {
         let mut page = Page::new(Some(1));
         page.pin_count = 2;
         page.is_dirty = true;
-        page.node = Some(Node::new(true, NodeType::Internal));
+        page.node = Some(Node::new(true, NodeType::Internal, NodeLayout::default()));
         page.deallocate();
 
         assert_eq!(page.page_id, None);
@@ -135,7 +189,7 @@ mod test {
     #[test]
     fn test_as_bytes_from_bytes() {
         let mut page = Page::new(Some(0));
-        let mut node = Node::new(true, NodeType::Leaf);
+        let mut node = Node::new(true, NodeType::Leaf, NodeLayout::default());
         assert_eq!(page.get_row(0), None);
 
         let cursor = Cursor {
@@ -144,25 +198,67 @@ mod test {
             end_of_table: false,
             key_existed: false,
         };
-        let row = Row::new("1", "name", "email").unwrap();
+        let row = Row::from_str("1 name email").unwrap();
         node.insert(&row, &cursor);
         page.node = Some(node);
         page.lsn = 10;
 
         let bytes = page.as_bytes();
-        let from_byte_page = Page::from_bytes(&bytes);
+        let from_byte_page = Page::from_bytes(0, &bytes).unwrap();
 
         // struct is equal
         // assert_eq!(from_byte_page, page);
 
         // bytes is equal
         assert_eq!(bytes, from_byte_page.as_bytes());
+        assert_eq!(bytes.len(), DEFAULT_PAGE_SIZE);
+    }
+
+    #[test]
+    fn as_bytes_from_bytes_with_page_size() {
+        let mut page = Page::new(Some(3));
+        let mut node = Node::new(true, NodeType::Leaf, NodeLayout::new(1024));
+        let cursor = Cursor {
+            page_num: 3,
+            cell_num: 0,
+            end_of_table: false,
+            key_existed: false,
+        };
+        node.insert(&Row::from_str("1 name email").unwrap(), &cursor);
+        page.node = Some(node);
+
+        let bytes = page.as_bytes();
+        assert_eq!(bytes.len(), 1024);
+
+        let from_byte_page = Page::from_bytes(3, &bytes).unwrap();
+        assert_eq!(from_byte_page.node.as_ref().unwrap().layout.page_size, 1024);
+        assert_eq!(from_byte_page.get_row(0), page.get_row(0));
+    }
+
+    #[test]
+    fn error_when_checksum_mismatch() {
+        let mut page = Page::new(Some(3));
+        page.node = Some(Node::new(true, NodeType::Leaf, NodeLayout::default()));
+        let bytes = page.as_bytes();
+        assert!(Page::from_bytes(3, &bytes).is_ok());
+
+        // A single flipped bit anywhere in the page is detected.
+        for offset in [0, PAGE_HEADER_BYTES - 1, bytes.len() - 1] {
+            let mut corrupted = bytes.clone();
+            corrupted[offset] ^= 1;
+            let result = Page::from_bytes(3, &corrupted);
+            assert!(matches!(result, Err(PagerError::CorruptedPage(3))));
+        }
+
+        // So are pages that are never written, e.g. after a torn write.
+        let result = Page::from_bytes(3, &[0; DEFAULT_PAGE_SIZE]);
+        assert!(matches!(result, Err(PagerError::CorruptedPage(3))));
     }
 
     #[test]
     fn get_row() {
         let mut page = Page::new(Some(0));
-        let mut node = Node::new(true, NodeType::Leaf);
+        let mut node = Node::new(true, NodeType::Leaf, NodeLayout::default());
         assert_eq!(page.get_row(0), None);
 
         let cursor = Cursor {
@@ -171,7 +267,7 @@ mod test {
             end_of_table: false,
             key_existed: false,
         };
-        let row = Row::new("1", "name", "email").unwrap();
+        let row = Row::from_str("1 name email").unwrap();
         node.insert(&row, &cursor);
         page.node = Some(node);
         let row = page.get_row(0);
@@ -184,7 +280,7 @@ mod test {
     #[test]
     fn mark_row_as_deleted_and_undeleted() {
         let mut page = Page::new(Some(0));
-        let mut node = Node::new(true, NodeType::Leaf);
+        let mut node = Node::new(true, NodeType::Leaf, NodeLayout::default());
         assert!(!page.mark_row_as_deleted(0));
 
         let cursor = Cursor {
@@ -193,7 +289,7 @@ mod test {
             end_of_table: false,
             key_existed: false,
         };
-        let row = Row::new("1", "name", "email").unwrap();
+        let row = Row::from_str("1 name email").unwrap();
         node.insert(&row, &cursor);
         page.node = Some(node);
         assert!(page.mark_row_as_deleted(0));
@@ -205,4 +301,34 @@ mod test {
         let row = page.get_row(0).unwrap();
         assert!(!row.is_deleted);
     }
+
+    #[test]
+    fn update_row() {
+        let mut page = Page::new(Some(0));
+        let mut node = Node::new(true, NodeType::Leaf, NodeLayout::default());
+
+        let cursor = Cursor {
+            page_num: 0,
+            cell_num: 0,
+            end_of_table: false,
+            key_existed: false,
+        };
+        let row = Row::from_str("1 name email").unwrap();
+        node.insert(&row, &cursor);
+        page.node = Some(node);
+
+        let new_row = Row::from_str("1 john john@email.com").unwrap();
+        assert!(page.update_row(0, &new_row, &[1]));
+        let row = page.get_row(0).unwrap();
+        assert_eq!(row.to_string(), "(1, name, john@email.com)");
+
+        // The updated row doesn't fit into a cell anymore and overflows.
+        let new_row = Row::new(1, vec![Value::Blob(vec![0; 2000]), Value::Null]);
+        assert!(page.update_row(0, &new_row, &[0]));
+        let row = page.get_row(0).unwrap();
+        assert_eq!(row.values[0], Value::Blob(vec![0; 2000]));
+        assert_eq!(row.values[1].to_string(), "john@email.com");
+
+        assert!(!page.update_row(1, &new_row, &[0]));
+    }
 }
@@ -0,0 +1,166 @@
+use parking_lot::{RwLockUpgradableReadGuard, RwLockWriteGuard};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use super::page::Page;
+use super::pager::Pager;
+
+// TRADEOFF: Our page guards unpin their page when they are dropped.
+//
+// A page fetched from our Pager used to be unpinned by hand, with the caller
+// telling whether the page was modified. Forgetting to unpin a page (e.g. on
+// an early return) leaked a frame of our buffer pool forever, and passing the
+// wrong dirty flag lost writes.
+//
+// A WritePageGuard considers its page dirty as soon as the page is borrowed
+// mutably, even if it isn't actually modified. We might write a few clean
+// pages to disk, which is cheaper than losing a write.
+
+/// A page fetched for reading, unpinned once the guard is dropped.
+pub struct ReadPageGuard<'a> {
+    pager: &'a Pager,
+    // Only taken when the guard is dropped.
+    page: Option<RwLockUpgradableReadGuard<'a, Page>>,
+}
+
+impl<'a> ReadPageGuard<'a> {
+    pub(super) fn new(pager: &'a Pager, page: RwLockUpgradableReadGuard<'a, Page>) -> Self {
+        Self {
+            pager,
+            page: Some(page),
+        }
+    }
+}
+
+impl Deref for ReadPageGuard<'_> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.page.as_ref().unwrap()
+    }
+}
+
+impl fmt::Debug for ReadPageGuard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.deref().fmt(f)
+    }
+}
+
+impl Drop for ReadPageGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(page) = self.page.take() {
+            self.pager.unpin_page_with_read_guard(page);
+        }
+    }
+}
+
+/// A page fetched for writing, unpinned once the guard is dropped, and
+/// marked as dirty if it was borrowed mutably.
+pub struct WritePageGuard<'a> {
+    pager: &'a Pager,
+    // Only taken when the guard is dropped or the page is deleted.
+    page: Option<RwLockWriteGuard<'a, Page>>,
+    is_dirty: bool,
+}
+
+impl<'a> WritePageGuard<'a> {
+    pub(super) fn new(pager: &'a Pager, page: RwLockWriteGuard<'a, Page>) -> Self {
+        Self {
+            pager,
+            page: Some(page),
+            is_dirty: false,
+        }
+    }
+
+    /// Take the page latch out of the guard, so it's no longer unpinned
+    /// on drop, e.g. when the page is deleted instead.
+    pub(super) fn into_inner(mut self) -> RwLockWriteGuard<'a, Page> {
+        self.page.take().unwrap()
+    }
+}
+
+impl Deref for WritePageGuard<'_> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.page.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for WritePageGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Page {
+        self.is_dirty = true;
+        self.page.as_mut().unwrap()
+    }
+}
+
+impl fmt::Debug for WritePageGuard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.deref().fmt(f)
+    }
+}
+
+impl Drop for WritePageGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(page) = self.page.take() {
+            self.pager.unpin_page_with_write_guard(page, self.is_dirty);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unpin_pages_when_guards_are_dropped() {
+        let pager = setup_test_pager();
+
+        let page = pager.fetch_read_page_guard(1).unwrap();
+        assert_eq!(page.pin_count, 1);
+        drop(page);
+
+        let page = pager.fetch_write_page_guard(1).unwrap();
+        assert_eq!(page.pin_count, 1);
+        drop(page);
+
+        let page = pager.fetch_read_page_guard(1).unwrap();
+        assert_eq!(page.pin_count, 1);
+        drop(page);
+
+        cleanup_test_db_file();
+    }
+
+    #[test]
+    fn mark_pages_as_dirty_only_when_borrowed_mutably() {
+        let pager = setup_test_pager();
+
+        let page = pager.fetch_write_page_guard(1).unwrap();
+        assert!(page.node.is_some());
+        drop(page);
+        assert!(!pager.fetch_read_page_guard(1).unwrap().is_dirty);
+
+        let mut page = pager.fetch_write_page_guard(1).unwrap();
+        page.lsn += 1;
+        drop(page);
+        assert!(pager.fetch_read_page_guard(1).unwrap().is_dirty);
+
+        cleanup_test_db_file();
+    }
+
+    // A pager over an existing database, so its pages are read from disk
+    // and aren't dirty.
+    fn setup_test_pager() -> Pager {
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let _ = std::fs::remove_file(&file);
+        let pager = Pager::new(&file, 8);
+        pager.flush_all_pages().unwrap();
+        drop(pager);
+
+        Pager::new(&file, 8)
+    }
+
+    fn cleanup_test_db_file() {
+        let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
+    }
+}
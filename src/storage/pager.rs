@@ -1,143 +1,289 @@
 use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard, RwLockWriteGuard};
 use std::collections::HashMap;
-use std::path::Path;
-use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 
-use super::node::{
-    InternalCell, Node, INTERNAL_NODE_MAX_CELLS, LEAF_NODE_LEFT_SPLIT_COUNT, LEAF_NODE_MAX_CELLS,
-    LEAF_NODE_RIGHT_SPLIT_COUNT,
+use super::buffer_pool::{
+    default_buffer_pool_instances, instance_pool_sizes, min_pool_size, BufferPoolInstance,
+    FrameLatch, MIN_INSTANCE_FRAMES,
 };
-use crate::row::Row;
-use crate::storage::{DiskManager, NodeType, Page};
-use std::time::Instant;
-
-pub const PAGE_SIZE: usize = 4096;
-const SLEEP_MS: u64 = 10;
-const MAX_RETRY: usize = 3000 / SLEEP_MS as usize;
-
-#[derive(PartialEq, Eq)]
-pub enum Operation {
-    Insert,
-    Delete,
+use super::freelist::{
+    freelist_trunk_page_max_leaves, FreelistTrunkPage, FREELIST_TRUNK_PAGE_TYPE,
+};
+use super::header::{validate_page_size, DatabaseHeader, HEADER_PAGE_ID};
+use super::node::{Node, NodeLayout};
+use super::overflow::{overflow_page_payload_size, OverflowPage, OVERFLOW_PAGE_TYPE};
+use super::page::PAGE_HEADER_BYTES;
+use super::page_guard::{ReadPageGuard, WritePageGuard};
+use super::replacer::ReplacementPolicy;
+use super::stats::{PagerCounters, PagerStats};
+use crate::storage::{
+    NodeType, Page, SharedSynchronousMode, Storage, StorageBackend, SynchronousMode,
+};
+
+// The page size of a new database, unless another page size is chosen
+// when the database is created, see Pager::open_with_options.
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+pub const MIN_PAGE_SIZE: usize = 512;
+// Our slots and overflow pages store offsets and lengths as u16.
+pub const MAX_PAGE_SIZE: usize = 64 * 1024;
+
+// TRADEOFF: Pager::flush_all_pages writes our pages in batches, so they are
+// written in parallel by a backend like io_uring.
+//
+// The pages of a batch stay latched until the whole batch is written, so a
+// larger batch keeps more threads waiting for its pages for longer.
+const FLUSH_BATCH_SIZE: usize = 32;
+
+// A page to be written by Pager::write_batch, together with its latch.
+type PageWrite<'a> = ((usize, Vec<u8>), FrameLatch<'a, RwLockWriteGuard<'a, Page>>);
+
+/// Our write-ahead log, as seen by our pager, see Pager::attach_log.
+pub trait WriteAheadLog: fmt::Debug + Send + Sync {
+    /// The lsn up to which our log is on disk.
+    fn flushed_lsn(&self) -> u32;
+    /// Write our log to disk up to at least the given lsn.
+    fn flush_until(&self, lsn: u32);
 }
 
 #[derive(Debug)]
-pub struct Cursor {
-    pub page_num: usize,
-    pub cell_num: usize,
-    pub key_existed: bool,
-    pub end_of_table: bool,
+pub enum PagerError {
+    NoFreePageAvailable,
+    FailToAcquirePageLock,
+    NotADatabase,
+    UnsupportedVersion(u32),
+    InvalidPageSize(usize),
+    CorruptedPage(usize),
+    // The minimum number of frames of our buffer pool, see
+    // MIN_INSTANCE_FRAMES.
+    TooFewFrames(usize),
+    // Pages of our buffer pool are pinned or latched while they're about to
+    // be discarded, see Pager::replace_with.
+    PagesInUse,
+    Io(std::io::Error),
 }
 
-#[derive(Debug)]
-struct PageMetadata {
-    frame_id: usize,
-    last_accessed_at: Instant,
+impl PagerError {
+    /// Whether the operation might succeed if we try again later, e.g. once
+    /// other threads unpin their pages or release their latches, see
+    /// Pager::wait_for_page.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            PagerError::NoFreePageAvailable | PagerError::FailToAcquirePageLock
+        )
+    }
 }
 
-impl PageMetadata {
-    pub fn new(frame_id: usize) -> Self {
-        Self {
-            frame_id,
-            last_accessed_at: Instant::now(),
+impl std::fmt::Display for PagerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PagerError::NoFreePageAvailable => write!(f, "no free page available"),
+            PagerError::FailToAcquirePageLock => write!(f, "fail to acquire page lock"),
+            PagerError::NotADatabase => write!(f, "file is not a database"),
+            PagerError::UnsupportedVersion(version) => {
+                write!(f, "unsupported database format version {version}")
+            }
+            PagerError::InvalidPageSize(page_size) => write!(
+                f,
+                "invalid page size {page_size}, expected a power of two between {MIN_PAGE_SIZE} and {MAX_PAGE_SIZE}"
+            ),
+            PagerError::CorruptedPage(page_id) => {
+                write!(f, "database page {page_id} is corrupted")
+            }
+            PagerError::TooFewFrames(min_pool_size) => write!(
+                f,
+                "expect at least {min_pool_size} frames, {MIN_INSTANCE_FRAMES} per buffer pool instance"
+            ),
+            PagerError::PagesInUse => write!(f, "database pages are still in use"),
+            PagerError::Io(err) => write!(f, "{err}"),
         }
     }
 }
 
-// TRADEOFF: We are using the most naive replacement policies.
-//
-// We are replacing pages by considering the recency of a page instead
-// of frequency of access.
-//
-// This might not be the best for our database system. For example,
-// a root node will be the most frequently accessed page, however, it is
-// also the very first page that we would always access.
-//
-// Hence, it can be contradicting sometime to replace based on recency.
-//
-// So, a better algorithms will be using Least Frequencyly Used (LFU)
-// replacement policies.
-#[derive(Debug)]
-struct LRUReplacer {
-    // We are using Vec instead of HashMap as the size
-    // of the Vec is limited. Hence, a linear search
-    // would not caused much performance problem as well?
-    //
-    // And it's a bit easier to deal with Vec than
-    // HashMap for the time being.
-    page_table: RwLock<Vec<PageMetadata>>,
+impl From<std::io::Error> for PagerError {
+    fn from(err: std::io::Error) -> Self {
+        PagerError::Io(err)
+    }
+}
+
+/// The options used to open our database, see Pager::open_with_options.
+#[derive(Debug, Clone, Copy)]
+pub struct PagerOptions {
+    // Only used when the database is created.
+    pub page_size: usize,
+    pub replacement_policy: ReplacementPolicy,
+    // The number of independent instances our frames are split between,
+    // or None to derive it from our pool size, see BufferPoolInstance.
+    pub buffer_pool_instances: Option<usize>,
+    // How long to wait for a frame or a page latch before giving up with
+    // PagerError::NoFreePageAvailable, or forever if None, see
+    // DEFAULT_FRAME_WAIT_TIMEOUT.
+    pub frame_wait_timeout: Option<Duration>,
+    // Disabled if None, see Pager::start_background_writer.
+    pub background_writer: Option<BackgroundWriterOptions>,
+    // The number of leaf pages read ahead of a sequential scan, or 0 to
+    // disable it, see Pager::start_read_ahead.
+    pub read_ahead: usize,
+    pub storage: StorageBackend,
+    // When our database file is synced, see Pager::set_synchronous.
+    pub synchronous: SynchronousMode,
 }
 
-impl LRUReplacer {
-    pub fn new(pool_size: usize) -> Self {
+impl Default for PagerOptions {
+    fn default() -> Self {
         Self {
-            page_table: RwLock::new(Vec::with_capacity(pool_size)),
+            page_size: DEFAULT_PAGE_SIZE,
+            replacement_policy: ReplacementPolicy::default(),
+            buffer_pool_instances: None,
+            frame_wait_timeout: Some(DEFAULT_FRAME_WAIT_TIMEOUT),
+            background_writer: Some(BackgroundWriterOptions::default()),
+            read_ahead: DEFAULT_READ_AHEAD,
+            storage: StorageBackend::default(),
+            synchronous: SynchronousMode::default(),
         }
     }
+}
 
-    /// Return frame metadata that are accessed least recently
-    /// as compared to the other frame.
-    pub fn victim(&self) -> Option<PageMetadata> {
-        let mut page_table = self.page_table.write();
-        page_table.sort_by(|a, b| b.last_accessed_at.cmp(&a.last_accessed_at));
-        page_table.pop()
-    }
+// TRADEOFF: Similar to the busy timeout of SQLite, a thread gives up waiting
+// for a frame or a page latch after a while.
+//
+// Our latches are always taken from the root down and our frames are only
+// pinned for the duration of an operation, so a thread waiting this long
+// means every frame is pinned by threads waiting for each other, e.g. when
+// our buffer pool is too small for the number of threads latch crabbing.
+// Returning an error is better than hanging forever, even if a split or
+// merge has to panic on it.
+pub const DEFAULT_FRAME_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// TRADEOFF: Similar to the background writer of PostgreSQL, we only write a
+// few dirty pages at a time.
+//
+// Writing every dirty pages at once would stall the threads waiting for the
+// latches of these pages, while writing them one by one would take too long
+// to catch up with the pages made dirty in the meantime.
+#[derive(Debug, Clone, Copy)]
+pub struct BackgroundWriterOptions {
+    // How long our background writer sleeps between two batches.
+    pub interval: Duration,
+    // The maximum number of pages written per batch.
+    pub batch_size: usize,
+}
 
-    /// This should be called after our Pager place the page into
-    /// our memory. Here, pin a frame means removing it from our
-    /// replacer. I guess this prevent it from the page being
-    /// evicted
-    pub fn pin(&self, frame_id: usize) {
-        let mut page_table = self.page_table.write();
-        if let Some(index) = page_table.iter().position(|md| md.frame_id == frame_id) {
-            page_table.remove(index);
+impl Default for BackgroundWriterOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(200),
+            batch_size: 32,
         }
     }
-
-    /// This should be called by our Pager when the page pin_count
-    /// becomes 0. Here, unpin a frame means adding it to our
-    /// replacer. This allow the page to be evicted.
-    pub fn unpin(&self, frame_id: usize) {
-        let mut page_table = self.page_table.write();
-        page_table.push(PageMetadata::new(frame_id));
-    }
 }
 
 #[derive(Debug)]
-pub enum PagerError {
-    NoFreePageAvailable,
-    FailToAcquirePageLock,
+struct BackgroundWriter {
+    // Dropped to stop our background writer.
+    stop: mpsc::Sender<()>,
+    handle: JoinHandle<()>,
 }
 
-// TRADEOFF: This isn't exactly a Pager or Buffer Pool manager.
+// TRADEOFF: Similar to the read-ahead of the Linux page cache, a scan is
+// detected when a leaf page is fetched right after the leaf page before it,
+// and the next few leaf pages are then read by another thread.
+//
+// Only the last leaf page fetched is remembered, so two scans running at the
+// same time are rarely detected. However, point lookups never trigger a
+// read-ahead, and we don't need to know which thread is scanning.
 //
-// Since, we includes the B+ tree operations here in this module as well.
-// This isn't the best structure for our code. Ideally, the logic of B+ tree
-// operations should be move to a separate module and it would use Pager
-// to access page as needed.
+// The leaf pages read ahead go to free frames first, and then evict a frame
+// like any other miss, e.g. the cold pages of the scan itself. Reading ahead
+// farther than a few pages would evict the pages read ahead before our scan
+// gets to them.
+pub const DEFAULT_READ_AHEAD: usize = 4;
+
+#[derive(Debug)]
+struct ReadAhead {
+    // Dropped to stop reading ahead.
+    requests: mpsc::Sender<usize>,
+    handle: JoinHandle<()>,
+}
+
+/// Our buffer pool manager, which reads and writes the pages of our
+/// database, see BPlusTree for the trees stored in them.
 #[derive(Debug)]
 pub struct Pager {
-    disk_manager: DiskManager,
-    replacer: LRUReplacer,
-    pages: Arc<Vec<RwLock<Page>>>,
+    // Replaced once a vacuum rebuilt our database into another file, see
+    // Pager::replace_with.
+    disk_manager: RwLock<Box<dyn Storage>>,
+    path: PathBuf,
+    storage: StorageBackend,
+    // Shared with our log, see Pager::shared_synchronous.
+    synchronous: SharedSynchronousMode,
+    // Our buffer pool, a page always goes to the instance of its page id.
+    instances: Vec<BufferPoolInstance>,
+    resize_lock: Mutex<()>,
     next_page_id: AtomicUsize,
-    // Indexes in our `pages` that are "free", which mean
-    // it is uninitialize.
-    free_list: Mutex<Vec<usize>>,
-    // Mapping page id to frame id
-    page_table: Arc<RwLock<HashMap<usize, usize>>>,
-
-    flushed_lsn: Option<AtomicU32>,
+    // In memory copy of our database header (page 0).
+    header: Mutex<DatabaseHeader>,
+    // Page ids of the pages that are no longer used, which are reused before
+    // growing our database file. It's persisted into our freelist trunk pages
+    // together with our header.
+    free_page_ids: Mutex<Vec<usize>>,
+    // The sizes of our nodes for the page size of our database.
+    layout: NodeLayout,
+    frame_wait_timeout: Option<Duration>,
+    background_writer_options: Option<BackgroundWriterOptions>,
+    background_writer: Mutex<Option<BackgroundWriter>>,
+    // The frame our background writer continues from, so every frames get
+    // their turn even if it always stops early.
+    next_frame_to_write: AtomicUsize,
+    read_ahead_depth: usize,
+    read_ahead: Mutex<Option<ReadAhead>>,
+    // Held while reading ahead, and by a vacuum for its whole duration, see
+    // Pager::pause_read_ahead.
+    read_ahead_lock: RwLock<()>,
+    // The next leaf page of the last leaf page fetched for reading, or 0.
+    next_sequential_leaf: AtomicUsize,
+    counters: PagerCounters,
+
+    // Our write-ahead log, or None without a log.
+    log: RwLock<Option<Arc<dyn WriteAheadLog>>>,
 }
 
 impl Pager {
     pub fn new(path: impl AsRef<Path>, pool_size: usize) -> Pager {
-        // Initialize free list.
-        let mut free_list = Vec::with_capacity(pool_size);
-        for i in (0..pool_size).rev() {
-            free_list.push(i);
+        Self::open(path, pool_size).unwrap_or_else(|err| panic!("fail to open database: {err}"))
+    }
+
+    pub fn open(path: impl AsRef<Path>, pool_size: usize) -> Result<Pager, PagerError> {
+        Self::open_with_options(path, pool_size, PagerOptions::default())
+    }
+
+    /// Open the database at the given path, creating it with the page size
+    /// of our options if it doesn't exist yet.
+    ///
+    /// Similar to SQLite, the page size of an existing database can't be
+    /// changed, so the page size stored in its header is used instead.
+    pub fn open_with_options(
+        path: impl AsRef<Path>,
+        pool_size: usize,
+        options: PagerOptions,
+    ) -> Result<Pager, PagerError> {
+        let page_size = options.page_size;
+        let num_of_instances = options
+            .buffer_pool_instances
+            .unwrap_or_else(|| default_buffer_pool_instances(pool_size));
+        assert!(
+            num_of_instances > 0,
+            "expect at least one buffer pool instance"
+        );
+        if pool_size < min_pool_size(num_of_instances) {
+            return Err(PagerError::TooFewFrames(min_pool_size(num_of_instances)));
         }
 
         // Initialize pages.
@@ -148,49 +294,224 @@ impl Pager {
         // safe, we can't just use RefCell, we need to use a RwLock, which means we need
         // to lock the whole "B Tree"...
         //
-        // Hence, for the sake of simplicity, I'll preallocate empty page first....
-        let mut pages = Vec::with_capacity(pool_size);
-        for _ in 0..pool_size {
-            pages.push(RwLock::new(Page::new(None)));
+        // Hence, we preallocate empty pages first, and only add more of them when
+        // our buffer pool is resized, see Frames.
+        let instances = instance_pool_sizes(pool_size, num_of_instances)
+            .into_iter()
+            .map(|pool_size| BufferPoolInstance::new(pool_size, options.replacement_policy))
+            .collect();
+
+        let path = path.as_ref();
+        let mut disk_manager = options.storage.open(path)?;
+        let header = if disk_manager.file_len() == 0 {
+            validate_page_size(page_size)?;
+            disk_manager.set_page_size(page_size);
+            Self::create_database(&*disk_manager, page_size, options.synchronous)?
+        } else {
+            // Our header page is at least MIN_PAGE_SIZE bytes, whatever our page size is.
+            let bytes = disk_manager.read_at(0, MIN_PAGE_SIZE)?;
+            let header = DatabaseHeader::from_bytes(&bytes)?;
+            disk_manager.set_page_size(header.page_size as usize);
+            header
+        };
+        let page_size = header.page_size as usize;
+        let free_page_ids = Self::read_freelist(&*disk_manager, &header)?;
+
+        // Our header is only persisted when we flush, so it's possible for
+        // pages to be written to disk during eviction without having the
+        // page_count in our header updated.
+        let next_page_id = std::cmp::max(
+            header.page_count as usize,
+            disk_manager.file_len() / page_size,
+        );
+
+        Ok(Pager {
+            disk_manager: RwLock::new(disk_manager),
+            path: path.to_path_buf(),
+            storage: options.storage,
+            synchronous: SharedSynchronousMode::new(options.synchronous),
+            instances,
+            resize_lock: Mutex::new(()),
+            next_page_id: AtomicUsize::new(next_page_id),
+            header: Mutex::new(header),
+            free_page_ids: Mutex::new(free_page_ids),
+            layout: NodeLayout::new(page_size),
+            frame_wait_timeout: options.frame_wait_timeout,
+            background_writer_options: options.background_writer,
+            background_writer: Mutex::new(None),
+            next_frame_to_write: AtomicUsize::new(0),
+            read_ahead_depth: options.read_ahead,
+            read_ahead: Mutex::new(None),
+            read_ahead_lock: RwLock::new(()),
+            next_sequential_leaf: AtomicUsize::new(0),
+            counters: PagerCounters::default(),
+            log: RwLock::new(None),
+        })
+    }
+
+    /// Write the header page and an empty catalog root node to a newly
+    /// created database file.
+    fn create_database(
+        disk_manager: &dyn Storage,
+        page_size: usize,
+        synchronous: SynchronousMode,
+    ) -> Result<DatabaseHeader, PagerError> {
+        let header = DatabaseHeader::new(page_size);
+        disk_manager.write_page(HEADER_PAGE_ID, &header.as_bytes())?;
+
+        let root_page_id = header.catalog_root_page_id as usize;
+        let mut root_page = Page::new(Some(root_page_id));
+        root_page.node = Some(Node::root(NodeLayout::new(page_size)));
+        disk_manager.write_page(root_page_id, &root_page.as_bytes())?;
+        disk_manager.sync(synchronous)?;
+
+        Ok(header)
+    }
+
+    pub fn header(&self) -> DatabaseHeader {
+        self.header.lock().clone()
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.layout.page_size
+    }
+
+    /// The sizes of our nodes for the page size of our database.
+    pub(super) fn layout(&self) -> NodeLayout {
+        self.layout
+    }
+
+    pub fn synchronous(&self) -> SynchronousMode {
+        self.synchronous.get()
+    }
+
+    /// Change when our database file and our log are synced from now on,
+    /// see SynchronousMode.
+    pub fn set_synchronous(&self, synchronous: SynchronousMode) {
+        self.synchronous.set(synchronous);
+    }
+
+    /// Our synchronous mode, to be given to our log so it follows our
+    /// changes, see LogManager::with_synchronous.
+    pub fn shared_synchronous(&self) -> SharedSynchronousMode {
+        self.synchronous.clone()
+    }
+
+    /// Sync our database file once every pages are flushed.
+    fn sync_database_file(&self) -> Result<(), PagerError> {
+        Ok(self.disk_manager.read().sync(self.synchronous())?)
+    }
+
+    /// Sync the pages we just wrote, if our synchronous mode syncs every
+    /// page written.
+    fn sync_page_writes(&self) -> Result<(), PagerError> {
+        if self.synchronous() == SynchronousMode::Full {
+            self.disk_manager.read().sync(SynchronousMode::Full)?;
         }
 
-        let disk_manager = DiskManager::new(path);
-        let next_page_id = disk_manager.file_len / PAGE_SIZE;
+        Ok(())
+    }
 
-        Pager {
-            disk_manager,
-            replacer: LRUReplacer::new(pool_size),
-            pages: Arc::new(pages),
-            next_page_id: AtomicUsize::new(next_page_id),
-            free_list: Mutex::new(free_list),
-            page_table: Arc::new(RwLock::new(HashMap::new())),
-            flushed_lsn: None,
+    pub fn catalog_root_page_id(&self) -> usize {
+        self.header.lock().catalog_root_page_id as usize
+    }
+
+    pub fn flush_header(&self) -> Result<(), PagerError> {
+        let mut header = self.header.lock();
+        self.write_freelist(&mut header)?;
+        header.page_count = self.next_page_id.load(Ordering::Acquire) as u32;
+        self.disk_manager
+            .read()
+            .write_page(HEADER_PAGE_ID, &header.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Read the page ids of every free pages, including the freelist
+    /// trunk pages themselves.
+    fn read_freelist(
+        disk_manager: &dyn Storage,
+        header: &DatabaseHeader,
+    ) -> Result<Vec<usize>, PagerError> {
+        let mut free_page_ids = Vec::with_capacity(header.freelist_count as usize);
+        let mut trunk_page_id = header.freelist_head;
+
+        while trunk_page_id != 0 {
+            let bytes = disk_manager.read_page(trunk_page_id as usize)?;
+            let trunk = FreelistTrunkPage::from_bytes(trunk_page_id as usize, &bytes)?;
+
+            free_page_ids.push(trunk_page_id as usize);
+            free_page_ids.extend(trunk.leaf_page_ids.iter().map(|&id| id as usize));
+            trunk_page_id = trunk.next_trunk_page_id;
+        }
+
+        Ok(free_page_ids)
+    }
+
+    /// Write our free page ids into freelist trunk pages, in the same
+    /// order as they are read by Pager::read_freelist.
+    fn write_freelist(&self, header: &mut DatabaseHeader) -> Result<(), PagerError> {
+        let free_page_ids = self.free_page_ids.lock();
+        let mut next_trunk_page_id = 0;
+
+        // The first page of every chunk is used as the trunk page of the others.
+        for chunk in free_page_ids
+            .chunks(freelist_trunk_page_max_leaves(self.page_size()) + 1)
+            .rev()
+        {
+            let trunk_page_id = chunk[0];
+            let leaf_page_ids: Vec<u32> = chunk[1..].iter().map(|&id| id as u32).collect();
+            let trunk = FreelistTrunkPage::new(next_trunk_page_id, &leaf_page_ids);
+            self.disk_manager.read().write_page(
+                trunk_page_id,
+                &trunk.as_bytes(trunk_page_id, self.page_size()),
+            )?;
+
+            next_trunk_page_id = trunk_page_id as u32;
         }
+
+        header.freelist_head = next_trunk_page_id;
+        header.freelist_count = free_page_ids.len() as u32;
+
+        Ok(())
     }
 
-    fn new_page(&self) -> Option<RwLockWriteGuard<Page>> {
-        let mut page_table = self.page_table.write();
+    pub(super) fn new_page(&self) -> Result<WritePageGuard<'_>, PagerError> {
+        // Our page id decides which instance our page goes to.
+        let page_id = self.allocate_page_id();
+        self.wait_for_page(page_id, || self.try_new_page(page_id))
+            .inspect_err(|_| {
+                // Our page id is reused by the next page instead.
+                self.free_page_ids.lock().push(page_id);
+            })
+    }
+
+    fn try_new_page(&self, page_id: usize) -> Result<WritePageGuard<'_>, PagerError> {
+        let instance = self.instance(page_id);
+        let mut page_table = instance.page_table.write();
 
         // Pop unused page index from free list.
-        let mut free_list = self.free_list.lock();
+        let mut free_list = instance.free_list.lock();
         let frame_id = free_list
             .pop()
-            .or_else(|| self.replacer.victim().map(|md| md.frame_id));
+            .or_else(|| self.evict(instance, &mut page_table));
         drop(free_list);
 
         if let Some(frame_id) = frame_id {
-            let unlock_page = self.pages.get(frame_id).unwrap();
+            let unlock_page = instance.pages.get(frame_id).unwrap();
             let mut page = unlock_page.write();
 
             // Check if page is dirty. Flush page to disk
             // if needed
             if page.is_dirty {
                 let dirty_page_id = page.page_id.unwrap();
-                self.flush_write_page(dirty_page_id, &page);
+                if let Err(err) = self.flush_write_page(dirty_page_id, &mut page) {
+                    // Our victim stays in our buffer pool until it's written.
+                    instance.unpin_frame(frame_id, &page);
+                    return Err(err);
+                }
             }
 
-            let page_id = self.next_page_id.fetch_add(1, Ordering::Acquire);
-
             // Update page table
             page_table.retain(|_, &mut fid| fid != frame_id);
             page_table.insert(page_id, frame_id);
@@ -198,74 +519,713 @@ impl Pager {
             // Reset page
             page.is_dirty = false;
             page.pin_count = 0;
+            page.is_prefetched = false;
+            page.is_cold = false;
             page.page_id = Some(page_id);
             page.node = None;
 
-            if page_id == 0 {
-                page.node = Some(Node::root());
-            }
-
             page.pin_count += 1;
-            self.replacer.pin(frame_id);
+            instance.replacer.set_page(frame_id, page_id);
+            instance.replacer.pin(frame_id);
             drop(page_table);
 
-            Some(page)
+            Ok(WritePageGuard::new(self, page))
         } else {
             drop(page_table);
+            Err(PagerError::NoFreePageAvailable)
+        }
+    }
 
-            let duration = std::time::Duration::from_millis(SLEEP_MS);
-            std::thread::sleep(duration);
+    pub fn flush_write_page(&self, page_id: usize, page: &mut Page) -> Result<(), PagerError> {
+        let bytes = self.page_bytes_to_write(page)?;
+        self.disk_manager.read().write_page(page_id, &bytes)?;
+        self.sync_page_writes()
+    }
 
-            self.new_page()
+    /// Serialize a page to be written to disk, once the overflow pages of
+    /// its node are written.
+    ///
+    /// Following the WAL rule, our log is flushed up to the last change of
+    /// the page first, so every page written can be recovered after a crash.
+    fn page_bytes_to_write(&self, page: &mut Page) -> Result<Vec<u8>, PagerError> {
+        self.flush_log_until(page.lsn);
+        if page.is_dirty {
+            PagerCounters::increment(&self.counters.dirty_writes);
         }
+        if let Some(node) = page.node.as_mut() {
+            self.write_overflow_pages(node)?;
+        }
+
+        Ok(page.as_bytes())
     }
 
-    pub fn flush_write_page(&self, page_id: usize, page: &RwLockWriteGuard<Page>) {
-        // TODO (Recovery): Check page_lsn and flushed_lsn before flushing to disk.
-        //
-        // This is to ensure that all of the logs that lead to the changes of the
-        // page is flushed to disk. Thus, enabling recovery if crash happens.
-        let bytes = page.as_bytes();
-        self.disk_manager.write_page(page_id, &bytes).unwrap();
+    /// Write every pages of our buffer pool and our header to disk.
+    ///
+    /// Our pages stay dirty until they're written, so the pages left after
+    /// an error are written again by the next flush.
+    pub fn flush_all_pages(&self) -> Result<(), PagerError> {
+        let mut batch = Vec::with_capacity(FLUSH_BATCH_SIZE);
+        for (instance, frame_id) in self.frames() {
+            // We never wait for a latch while holding the latches of our
+            // batch, as the thread holding it might be waiting for ours.
+            let mut page = match instance.try_write_frame(frame_id) {
+                Some(page) => page,
+                None => {
+                    self.write_batch(&mut batch)?;
+                    instance.write_frame(frame_id)
+                }
+            };
+
+            // Frames of deleted pages (e.g. after a merge) can be anywhere
+            // in our buffer pool, not only at the end of it.
+            if page.page_id.is_none() || page.node.is_none() {
+                continue;
+            }
+
+            let page_id = page.page_id.unwrap();
+            let bytes = self.page_bytes_to_write(&mut page)?;
+            batch.push(((page_id, bytes), page));
+            if batch.len() == FLUSH_BATCH_SIZE {
+                self.write_batch(&mut batch)?;
+            }
+        }
+        self.write_batch(&mut batch)?;
+
+        // Flushed last, as writing overflow pages might allocate new pages.
+        self.flush_header()?;
+        self.sync_database_file()
+    }
+
+    /// Write a batch of pages at once, and release their latches once
+    /// they're written, so they can't be evicted and read back from disk
+    /// before that.
+    fn write_batch(&self, batch: &mut Vec<PageWrite>) -> Result<(), PagerError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let (pages, mut latches): (Vec<_>, Vec<_>) = batch.drain(..).unzip();
+        self.disk_manager.read().write_pages(pages)?;
+        self.sync_page_writes()?;
+        for page in latches.iter_mut() {
+            page.is_dirty = false;
+        }
+        drop(latches);
+
+        Ok(())
     }
 
-    pub fn flush_all_pages(&self) {
-        for page in self.pages.iter() {
-            let page = page.read();
-            if page.page_id.is_none() {
+    // -----------------
+    // Background Writer
+    // -----------------
+    //
+    // Without a background writer, a dirty page only reaches the disk when
+    // it's evicted or when every pages are flushed at exit, so a crash loses
+    // every changes since our database is opened.
+
+    /// Start writing our dirty pages in the background if enabled by our
+    /// options, until our pager is dropped.
+    ///
+    /// Our writer only holds a weak reference to our pager, so it doesn't
+    /// prevent our pager from being dropped.
+    pub fn start_background_writer(pager: &Arc<Pager>) {
+        let Some(options) = pager.background_writer_options else {
+            return;
+        };
+        let mut background_writer = pager.background_writer.lock();
+        if background_writer.is_some() {
+            return;
+        }
+
+        let (stop, stopped) = mpsc::channel::<()>();
+        let pager: Weak<Pager> = Arc::downgrade(pager);
+        let handle = thread::Builder::new()
+            .name("background-writer".to_string())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(options.interval) {
+                    let Some(pager) = pager.upgrade() else {
+                        break;
+                    };
+                    match pager.write_dirty_pages(options.batch_size) {
+                        Ok(0) => {}
+                        Ok(written) => {
+                            let remaining = pager.dirty_page_count();
+                            debug!(
+                                "background writer wrote {written} dirty pages, {remaining} left"
+                            );
+                        }
+                        // Our pages stay dirty, so they're written again later.
+                        Err(err) => warn!("background writer failed to write a page: {err}"),
+                    }
+                }
+            })
+            .expect("fail to start background writer");
+
+        *background_writer = Some(BackgroundWriter { stop, handle });
+    }
+
+    /// Write up to batch_size dirty pages that aren't used by any thread,
+    /// and return the number of pages written.
+    ///
+    /// Following the WAL rule, a page isn't written before our log is
+    /// flushed up to the last change of the page.
+    pub fn write_dirty_pages(&self, batch_size: usize) -> Result<usize, PagerError> {
+        let num_of_frames = self.pool_size();
+        let mut written = 0;
+
+        for _ in 0..num_of_frames {
+            if written == batch_size {
                 break;
             }
 
-            if page.node.is_some() {
-                let bytes = page.as_bytes();
-                self.disk_manager
-                    .write_page(page.page_id.unwrap(), &bytes)
-                    .unwrap();
+            let index = self.next_frame_to_write.fetch_add(1, Ordering::Relaxed) % num_of_frames;
+            let (instance, frame_id) = self.frame_at(index);
+            // A latched page is being used, so we don't wait for it.
+            let Some(mut page) = instance.try_write_frame(frame_id) else {
+                continue;
+            };
+
+            let is_writable = page.is_dirty
+                && page.pin_count == 0
+                && page.node.is_some()
+                && self.is_log_flushed(page.lsn);
+            if let (true, Some(page_id)) = (is_writable, page.page_id) {
+                self.flush_write_page(page_id, &mut page)?;
+                page.is_dirty = false;
+                written += 1;
+            }
+        }
+
+        Ok(written)
+    }
+
+    /// The number of pages of our buffer pool not written to disk yet.
+    pub fn dirty_page_count(&self) -> usize {
+        let mut count = 0;
+        for (instance, frame_id) in self.frames() {
+            count += usize::from(instance.read_frame(frame_id).is_dirty);
+        }
+
+        count
+    }
+
+    /// Follow the WAL rule with the given log from now on, so a page is
+    /// never written to disk before the log records of its changes.
+    pub fn attach_log(&self, log: Arc<dyn WriteAheadLog>) {
+        *self.log.write() = Some(log);
+    }
+
+    /// Whether our log is on disk up to the given lsn, which is always the
+    /// case without a log.
+    fn is_log_flushed(&self, lsn: u32) -> bool {
+        self.log
+            .read()
+            .as_ref()
+            .is_none_or(|log| lsn <= log.flushed_lsn())
+    }
+
+    /// Write our log to disk up to the given lsn unless it already is.
+    fn flush_log_until(&self, lsn: u32) {
+        if let Some(log) = self.log.read().as_ref() {
+            if lsn > log.flushed_lsn() {
+                log.flush_until(lsn);
             }
         }
     }
 
-    pub fn delete_page_with_write_guard(&self, mut page: RwLockWriteGuard<Page>) -> bool {
+    // ----------
+    // Read-ahead
+    // ----------
+    //
+    // A scan walks through our leaf pages one at a time, and every leaf page
+    // missing from our buffer pool is read from disk while the scan waits.
+    // Instead, the leaf pages after the current one are read by another
+    // thread while the scan goes through the rows of the current one.
+
+    /// Start reading ahead of our sequential scans if enabled by our
+    /// options, until our pager is dropped.
+    ///
+    /// Like our background writer, it only holds a weak reference to our
+    /// pager.
+    pub fn start_read_ahead(pager: &Arc<Pager>) {
+        if pager.read_ahead_depth == 0 {
+            return;
+        }
+        let mut read_ahead = pager.read_ahead.lock();
+        if read_ahead.is_some() {
+            return;
+        }
+
+        let (requests, received) = mpsc::channel::<usize>();
+        let pager: Weak<Pager> = Arc::downgrade(pager);
+        let handle = thread::Builder::new()
+            .name("read-ahead".to_string())
+            .spawn(move || {
+                while let Ok(mut page_id) = received.recv() {
+                    // Only the latest request matters if we fall behind.
+                    while let Ok(next_page_id) = received.try_recv() {
+                        page_id = next_page_id;
+                    }
+                    let Some(pager) = pager.upgrade() else {
+                        break;
+                    };
+                    pager.read_ahead(page_id);
+                }
+            })
+            .expect("fail to start read-ahead");
+
+        *read_ahead = Some(ReadAhead { requests, handle });
+    }
+
+    /// Read the leaf page with the given page id and the leaf pages after
+    /// it into our buffer pool, up to our read-ahead depth, and return the
+    /// number of pages read from disk.
+    ///
+    /// Nothing is read while our read-ahead is paused.
+    pub fn read_ahead(&self, mut page_id: usize) -> usize {
+        let Some(_read_ahead) = self.read_ahead_lock.try_read() else {
+            return 0;
+        };
+        let mut read = 0;
+
+        for _ in 0..self.read_ahead_depth {
+            let Some((next_leaf_offset, was_read)) = self.prefetch_page(page_id) else {
+                break;
+            };
+            read += usize::from(was_read);
+            if next_leaf_offset == 0 {
+                break;
+            }
+            page_id = next_leaf_offset;
+        }
+
+        read
+    }
+
+    /// Read a leaf page into a free or evicted frame unless it's already in
+    /// our buffer pool, and return its next leaf page and whether it was read
+    /// from disk.
+    ///
+    /// None is returned when our leaf chain can't be followed, e.g. when
+    /// every frame is pinned, or while the page is used.
+    fn prefetch_page(&self, page_id: usize) -> Option<(usize, bool)> {
+        let instance = self.instance(page_id);
+        let page_table = instance.page_table.upgradable_read();
+
+        if let Some(&frame_id) = page_table.get(&page_id) {
+            let page = instance.try_read_frame(frame_id)?;
+            let next_leaf_offset = page
+                .node
+                .as_ref()
+                .filter(|node| node.node_type == NodeType::Leaf)
+                .map(|node| node.next_leaf_offset as usize);
+            drop(page);
+            drop(page_table);
+
+            return next_leaf_offset.map(|next_leaf_offset| (next_leaf_offset, false));
+        }
+
+        // The page might have been deleted since its previous leaf page
+        // pointed to it, and deleting a page needs our page table.
+        if self.free_page_ids.lock().contains(&page_id) {
+            return None;
+        }
+
+        let mut page_table = RwLockUpgradableReadGuard::upgrade(page_table);
+        let mut free_list = instance.free_list.lock();
+        let frame_id = free_list
+            .pop()
+            .or_else(|| self.evict(instance, &mut page_table))?;
+        drop(free_list);
+        let mut page = instance.write_frame(frame_id);
+        if let Some(evicted_page_id) = page.page_id {
+            if page.is_dirty && self.flush_write_page(evicted_page_id, &mut page).is_err() {
+                // Our scan reads the page itself, and our victim stays in
+                // our buffer pool until it's written.
+                instance.unpin_frame(frame_id, &page);
+                return None;
+            }
+            page_table.remove(&evicted_page_id);
+            page.deallocate();
+        }
+
+        let page_from_disk = self.disk_manager.read().read_page(page_id).ok();
+        let page_from_disk = page_from_disk
+            .and_then(|bytes| Page::from_bytes(page_id, &bytes).ok())
+            .filter(|page| {
+                page.node
+                    .as_ref()
+                    .is_some_and(|node| node.node_type == NodeType::Leaf)
+            })
+            .and_then(|mut page| {
+                self.read_overflow_pages(page.node.as_mut().unwrap()).ok()?;
+                Some(page)
+            });
+        let Some(page_from_disk) = page_from_disk else {
+            // Our scan reads the page again and reports the error if any.
+            drop(page);
+            instance.free_frame(frame_id);
+            return None;
+        };
+
+        page.lsn = page_from_disk.lsn;
+        page.page_id = Some(page_id);
+        page.node = page_from_disk.node;
+        page.is_dirty = false;
+        page.pin_count = 0;
+        page.is_prefetched = true;
+        page.is_cold = true;
+        let next_leaf_offset = page.node.as_ref().unwrap().next_leaf_offset as usize;
+        page_table.insert(page_id, frame_id);
+
+        // Our page can be evicted as soon as it's read.
+        instance.replacer.set_page(frame_id, page_id);
+        instance.replacer.pin(frame_id);
+        instance.unpin_frame(frame_id, &page);
+        drop(page);
+        drop(page_table);
+        PagerCounters::increment(&self.counters.read_ahead);
+
+        Some((next_leaf_offset, true))
+    }
+
+    /// Read ahead once a leaf page is fetched right after its previous leaf
+    /// page.
+    fn detect_sequential_scan(&self, page: &Page) {
+        let Some(node) = page.node.as_ref() else {
+            return;
+        };
+        if node.node_type != NodeType::Leaf {
+            return;
+        }
+
+        let next_leaf_offset = node.next_leaf_offset as usize;
+        let expected_page_id = self
+            .next_sequential_leaf
+            .swap(next_leaf_offset, Ordering::Relaxed);
+        if next_leaf_offset != 0 && page.page_id == Some(expected_page_id) {
+            if let Some(read_ahead) = self.read_ahead.lock().as_ref() {
+                let _ = read_ahead.requests.send(next_leaf_offset);
+            }
+        }
+    }
+
+    /// Count the first fetch of a page read ahead.
+    fn record_read_ahead_hit(&self, page: &mut Page) {
+        if page.is_prefetched {
+            page.is_prefetched = false;
+            PagerCounters::increment(&self.counters.read_ahead_hits);
+        }
+    }
+
+    // -----------------------
+    // Resizing our buffer pool
+    // -----------------------
+    //
+    // Our frames are latched while latch crabbing without holding any other
+    // lock, so a frame can't be removed from our buffer pool at once. Instead,
+    // a removed frame is no longer handed out for new pages, and its page is
+    // written back and evicted once no thread uses it anymore.
+
+    pub fn pool_size(&self) -> usize {
+        self.instances
+            .iter()
+            .map(BufferPoolInstance::pool_size)
+            .sum()
+    }
+
+    /// Grow or shrink our buffer pool to the given number of frames while
+    /// other threads keep using it.
+    ///
+    /// Shrinking blocks until the pages of the removed frames are unpinned.
+    /// If it takes longer than our frame_wait_timeout, our buffer pool is
+    /// left at its previous size and PagerError::NoFreePageAvailable is
+    /// returned.
+    pub fn resize(&self, new_size: usize) -> Result<(), PagerError> {
+        let min_pool_size = min_pool_size(self.instances.len());
+        if new_size < min_pool_size {
+            return Err(PagerError::TooFewFrames(min_pool_size));
+        }
+        let _resize_lock = self.resize_lock.lock();
+        let old_size = self.pool_size();
+
+        let old_sizes: Vec<usize> = self.instances.iter().map(|i| i.pool_size()).collect();
+        let new_sizes = instance_pool_sizes(new_size, self.instances.len());
+        for (i, instance) in self.instances.iter().enumerate() {
+            if let Err(err) = self.resize_instance(instance, old_sizes[i], new_sizes[i]) {
+                // Growing can't fail, so we can always go back.
+                for (i, instance) in self.instances.iter().enumerate().take(i) {
+                    self.resize_instance(instance, new_sizes[i], old_sizes[i])?;
+                }
+                return Err(err);
+            }
+        }
+
+        debug!("resized buffer pool from {old_size} to {new_size} frames");
+        Ok(())
+    }
+
+    fn resize_instance(
+        &self,
+        instance: &BufferPoolInstance,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<(), PagerError> {
+        if new_size > old_size {
+            // Our replacer has to know about our new frames before they're
+            // handed out.
+            instance.pages.allocate(new_size);
+            instance.replacer.resize(new_size);
+            instance.set_pool_size(new_size);
+            // Threads might be waiting for a frame.
+            instance.releases.notify();
+        } else if new_size < old_size {
+            instance.set_pool_size(new_size);
+            if let Err(err) = self.drain_frames(instance, new_size..old_size) {
+                instance.set_pool_size(old_size);
+                instance.releases.notify();
+                return Err(err);
+            }
+            instance.replacer.resize(new_size);
+        }
+
+        Ok(())
+    }
+
+    /// Block until every given frames are emptied.
+    fn drain_frames(
+        &self,
+        instance: &BufferPoolInstance,
+        frame_ids: std::ops::Range<usize>,
+    ) -> Result<(), PagerError> {
+        let deadline = self.wait_deadline();
+
+        for frame_id in frame_ids {
+            loop {
+                let epoch = instance.releases.epoch();
+                let mut page_table = instance.page_table.write();
+                // Latches are taken after our page table everywhere else.
+                let page = instance.try_write_frame(frame_id);
+                let is_locked = page.is_none();
+                if let Some(mut page) = page.filter(|page| page.pin_count == 0) {
+                    self.empty_frame(&mut page_table, &mut page)?;
+                    instance.replacer.remove(frame_id);
+                    drop(page);
+                    drop(page_table);
+                    break;
+                }
+                drop(page_table);
+                // We don't wake up from releasing the frame ourselves.
+                let epoch = epoch + u64::from(!is_locked);
+
+                // Frames are also latched without being unpinned afterwards
+                // (e.g. by Pager::flush_all_pages), so we don't wait for too long.
+                let retry_at = Instant::now() + Duration::from_millis(10);
+                let wait_until = deadline.map_or(retry_at, |deadline| deadline.min(retry_at));
+                if !instance.releases.wait(epoch, Some(wait_until))
+                    && deadline.is_some_and(|deadline| Instant::now() >= deadline)
+                {
+                    return Err(PagerError::NoFreePageAvailable);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the page of an unpinned frame back to disk if needed, and
+    /// remove it from our buffer pool.
+    fn empty_frame(
+        &self,
+        page_table: &mut HashMap<usize, usize>,
+        page: &mut Page,
+    ) -> Result<(), PagerError> {
+        let Some(page_id) = page.page_id else {
+            return Ok(());
+        };
+        if page.is_dirty {
+            self.flush_write_page(page_id, page)?;
+        }
+
+        page_table.remove(&page_id);
+        page.deallocate();
+
+        Ok(())
+    }
+
+    /// The instance of our buffer pool the page goes to.
+    fn instance(&self, page_id: usize) -> &BufferPoolInstance {
+        &self.instances[page_id % self.instances.len()]
+    }
+
+    /// The frame at the given index, counting the frames of every instances
+    /// of our buffer pool one after another.
+    fn frame_at(&self, mut index: usize) -> (&BufferPoolInstance, usize) {
+        for instance in &self.instances {
+            if index < instance.pool_size() {
+                return (instance, index);
+            }
+            index -= instance.pool_size();
+        }
+
+        unreachable!("frame {index} is beyond our buffer pool")
+    }
+
+    /// Every frames allocated by the instances of our buffer pool, together
+    /// with their instance.
+    fn frames(&self) -> impl Iterator<Item = (&BufferPoolInstance, usize)> {
+        self.instances.iter().flat_map(|instance| {
+            (0..instance.pages.capacity()).map(move |frame_id| (instance, frame_id))
+        })
+    }
+
+    /// Reuse a free page if any, else, grow our database file by a page.
+    pub(super) fn allocate_page_id(&self) -> usize {
+        self.free_page_ids
+            .lock()
+            .pop()
+            .unwrap_or_else(|| self.next_page_id.fetch_add(1, Ordering::Acquire))
+    }
+
+    // --------------
+    // Overflow Pages
+    // --------------
+
+    // TRADEOFF: Overflow pages are not part of our buffer pool.
+    //
+    // Similar to our header page, they are read and written directly with our
+    // DiskManager. An overflowing record is always read and written as a whole
+    // together with its leaf node, so caching its overflow pages separately
+    // would only take frames away from our B+ tree nodes.
+    //
+    // The overflow pages of a cell are only written when its leaf node is
+    // written to disk, which keeps Node free from any I/O.
+    /// Write the overflow pages of the cells that haven't been written yet and
+    /// free the overflow pages of the cells removed from the node.
+    fn write_overflow_pages(&self, node: &mut Node) -> Result<(), PagerError> {
+        self.free_overflow_pages(node)?;
+
+        for cell in node.cells.iter_mut() {
+            if cell.overflow_page_id() == Some(0) {
+                let page_id = self.write_overflow_chain(cell.overflow())?;
+                cell.set_overflow_page_id(page_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load the overflow pages of every cells of a node read from disk.
+    fn read_overflow_pages(&self, node: &mut Node) -> Result<(), PagerError> {
+        for cell in node.cells.iter_mut() {
+            if let Some(page_id) = cell.overflow_page_id() {
+                let overflow = self.read_overflow_chain(page_id)?;
+                cell.set_overflow(overflow);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn free_overflow_pages(&self, node: &mut Node) -> Result<(), PagerError> {
+        for page_id in std::mem::take(&mut node.freed_overflow_page_ids) {
+            self.free_overflow_chain(page_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the payload into a new chain of overflow pages and return
+    /// the page id of the first page.
+    fn write_overflow_chain(&self, payload: &[u8]) -> Result<u32, std::io::Error> {
+        let mut next_page_id = 0;
+
+        // Write from the last page, so we know the next page id of every page.
+        let page_size = self.page_size();
+        for chunk in payload.chunks(overflow_page_payload_size(page_size)).rev() {
+            let page_id = self.allocate_page_id();
+            let page = OverflowPage::new(next_page_id, chunk);
+            self.disk_manager
+                .read()
+                .write_page(page_id, &page.as_bytes(page_id, page_size))?;
+
+            next_page_id = page_id as u32;
+        }
+
+        Ok(next_page_id)
+    }
+
+    fn read_overflow_chain(&self, first_page_id: u32) -> Result<Vec<u8>, PagerError> {
+        let mut payload = Vec::new();
+        let mut page_id = first_page_id;
+
+        while page_id != 0 {
+            let bytes = self.disk_manager.read().read_page(page_id as usize)?;
+            let mut page = OverflowPage::from_bytes(page_id as usize, &bytes)?;
+            payload.append(&mut page.payload);
+            page_id = page.next_page_id;
+        }
+
+        Ok(payload)
+    }
+
+    fn free_overflow_chain(&self, first_page_id: u32) -> Result<(), PagerError> {
+        let mut page_id = first_page_id;
+
+        while page_id != 0 {
+            let bytes = self.disk_manager.read().read_page(page_id as usize)?;
+            self.free_page_ids.lock().push(page_id as usize);
+
+            // TRADEOFF: We can't trust the next page id of a corrupted page, so
+            // the rest of the chain is leaked until the next VACUUM rather than
+            // risking to put pages that are still in use into our freelist.
+            match OverflowPage::from_bytes(page_id as usize, &bytes) {
+                Ok(page) => page_id = page.next_page_id,
+                Err(err) => {
+                    warn!("stop freeing overflow chain {first_page_id}: {err}");
+                    break;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_page_with_write_guard(&self, page: WritePageGuard<'_>) -> bool {
+        let mut page = page.into_inner();
         let page_id = page.page_id.unwrap();
 
         assert!(page.pin_count >= 1);
         // unpin the page first.
         //
-        // no need to call replacer here as to delete a page
+        // no need to unpin it in our replacer as to delete a page
         // require a thread to hold a page, which means it's pinned
-        // and shouldn't be in a replacer.
+        // and can't be evicted. Our replacer only has to forget
+        // about its frame once the page is deallocated.
         page.pin_count -= 1;
-        self.replacer.pin(page_id);
 
-        let mut page_table = self.page_table.write();
-        if let Some(&frame_id) = page_table.get(&page_id) {
+        let instance = self.instance(page_id);
+        let mut page_table = instance.page_table.write();
+        let is_deleted = if let Some(&frame_id) = page_table.get(&page_id) {
             if page.pin_count == 0 {
+                instance.replacer.remove(frame_id);
+                // Like a corrupted chain, the rest of a chain we fail to
+                // read is leaked until the next VACUUM.
+                if let Some(err) = page
+                    .node
+                    .as_mut()
+                    .and_then(|node| self.free_overflow_pages(node).err())
+                {
+                    warn!("stop freeing overflow pages of page {page_id}: {err}");
+                }
+
                 page.deallocate();
                 page_table.remove(&page_id);
                 drop(page_table);
                 drop(page);
 
-                self.free_list.lock().push(frame_id);
+                instance.free_frame(frame_id);
+                self.free_page_ids.lock().push(page_id);
 
                 true
             } else {
@@ -277,19 +1237,29 @@ impl Pager {
             drop(page);
             drop(page_table);
             true
-        }
+        };
+
+        instance.releases.notify();
+        is_deleted
     }
 
-    pub fn unpin_page_with_write_guard(&self, mut page: RwLockWriteGuard<Page>, is_dirty: bool) {
-        let page_table = self.page_table.read();
-        if let Some(&frame_id) = page_table.get(&page.page_id.unwrap()) {
+    /// Unpin a page once its WritePageGuard is dropped.
+    pub(super) fn unpin_page_with_write_guard(
+        &self,
+        mut page: RwLockWriteGuard<'_, Page>,
+        is_dirty: bool,
+    ) {
+        let page_id = page.page_id.unwrap();
+        let instance = self.instance(page_id);
+        let page_table = instance.page_table.read();
+        if let Some(&frame_id) = page_table.get(&page_id) {
             if !page.is_dirty {
                 page.is_dirty = is_dirty;
             }
             page.pin_count -= 1;
 
             if page.pin_count == 0 {
-                self.replacer.unpin(frame_id);
+                instance.unpin_frame(frame_id, &page);
             };
 
             drop(page_table);
@@ -298,349 +1268,498 @@ impl Pager {
             drop(page_table);
             drop(page);
         }
+
+        instance.releases.notify();
     }
 
-    pub fn unpin_page_with_read_guard(
-        &self,
-        page: RwLockUpgradableReadGuard<Page>,
-        is_dirty: bool,
-    ) {
+    /// Unpin a page once its ReadPageGuard is dropped.
+    pub(super) fn unpin_page_with_read_guard(&self, page: RwLockUpgradableReadGuard<'_, Page>) {
         let page_id = page.page_id.unwrap();
-        let page_table = self.page_table.read();
+        let instance = self.instance(page_id);
+        let page_table = instance.page_table.read();
         if let Some(&frame_id) = page_table.get(&page_id) {
             let mut page = RwLockUpgradableReadGuard::upgrade(page);
-            if !page.is_dirty {
-                page.is_dirty = is_dirty;
-            }
             page.pin_count -= 1;
 
             if page.pin_count == 0 {
-                self.replacer.unpin(frame_id);
+                instance.unpin_frame(frame_id, &page);
             };
 
             drop(page_table);
             drop(page);
         } else {
             drop(page_table);
-            let duration = std::time::Duration::from_millis(SLEEP_MS);
-            std::thread::sleep(duration);
-
-            self.unpin_page_with_read_guard(page, is_dirty);
+            drop(page);
         }
-    }
 
-    pub fn select(&self, root_page_num: usize) -> String {
-        let mut output = String::new();
+        instance.releases.notify();
+    }
 
-        let mut page = self.search_page(root_page_num, 0);
+    // ------
+    // Vacuum
+    // ------
+
+    /// Stop reading ahead until the returned guard is dropped, waiting for
+    /// the pages being read ahead, so a vacuum never races with our
+    /// read-ahead, see Catalog::vacuum.
+    ///
+    /// The scans detected in the meantime are not read ahead.
+    pub fn pause_read_ahead(&self) -> RwLockWriteGuard<'_, ()> {
+        self.read_ahead_lock.write()
+    }
 
-        let mut node = page.node.as_ref().unwrap();
-        assert_eq!(node.node_type, NodeType::Leaf);
+    /// Create an empty database next to ours with our page size, for a
+    /// vacuum to rebuild our trees into, see Pager::replace_with.
+    ///
+    /// A database left behind by an interrupted vacuum is discarded.
+    pub fn create_vacuum_database(&self) -> Result<Pager, PagerError> {
+        let mut path = self.path.clone().into_os_string();
+        path.push("-vacuum");
+        match std::fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
 
-        if node.num_of_cells == 0 {
-            self.unpin_page_with_read_guard(page, false);
-            return output;
+        // Our trees are written directly to disk, see write_vacuum_page, so
+        // a single frame is enough.
+        let options = PagerOptions {
+            page_size: self.page_size(),
+            buffer_pool_instances: Some(1),
+            background_writer: None,
+            read_ahead: 0,
+            storage: self.storage,
+            synchronous: self.synchronous(),
+            ..PagerOptions::default()
         };
+        Self::open_with_options(path, 1, options)
+    }
 
-        loop {
-            for i in 0..node.num_of_cells as usize {
-                let row = node.get(i);
-                output.push_str(&row.to_string());
-                output.push('\n');
-            }
+    /// Delete a database created for a vacuum that failed, see
+    /// create_vacuum_database.
+    pub fn discard(self) -> Result<(), PagerError> {
+        let path = self.path.clone();
+        drop(self);
+        Ok(std::fs::remove_file(path)?)
+    }
 
-            if node.next_leaf_offset == 0 {
-                self.unpin_page_with_read_guard(page, false);
-                break;
-            } else {
-                let page_num = node.next_leaf_offset as usize;
-                self.unpin_page_with_read_guard(page, false);
+    /// Forget every page allocated after the given number of pages, which
+    /// start at the root of our catalog, so the trees rebuilt by a vacuum are
+    /// written to a contiguous set of pages, see Catalog::vacuum.
+    pub fn reset_page_allocation(&self, num_of_reserved_pages: usize) {
+        let first_page_id = self.catalog_root_page_id();
+        self.free_page_ids.lock().clear();
+        self.next_page_id
+            .store(first_page_id + num_of_reserved_pages, Ordering::Release);
+    }
 
-                page = self.fetch_read_page_with_retry(page_num);
-                node = page.node.as_ref().unwrap();
-            }
+    /// Replace our database file with the database a vacuum rebuilt our trees
+    /// into, see create_vacuum_database.
+    ///
+    /// The vacuumed database is renamed over our file, so a crash leaves
+    /// either of them in place. Every page in our buffer pool is discarded,
+    /// so we fail with PagerError::PagesInUse, and discard the vacuumed
+    /// database instead, while any of them is still pinned.
+    pub fn replace_with(&self, vacuumed: Pager) -> Result<(), PagerError> {
+        let prepared = vacuumed
+            .flush_header()
+            .and_then(|_| Ok(vacuumed.disk_manager.read().sync(self.synchronous())?))
+            .and_then(|_| self.reset_buffer_pool());
+        if let Err(err) = prepared {
+            let _ = vacuumed.discard();
+            return Err(err);
+        }
+        let header = vacuumed.header();
+        let next_page_id = vacuumed.next_page_id.load(Ordering::Acquire);
+        let vacuumed_path = vacuumed.path.clone();
+        drop(vacuumed);
+
+        let mut disk_manager = self.disk_manager.write();
+        std::fs::rename(&vacuumed_path, &self.path)?;
+        if self.synchronous() != SynchronousMode::Off {
+            // The rename itself only reaches the disk with our directory.
+            let dir = self.path.parent().filter(|dir| !dir.as_os_str().is_empty());
+            std::fs::File::open(dir.unwrap_or(Path::new(".")))?.sync_all()?;
         }
 
-        output
-    }
-
-    fn search_page(&self, page_num: usize, key: u32) -> RwLockUpgradableReadGuard<Page> {
-        match self.fetch_read_page_guard(page_num) {
-            Err(_) => {
-                let duration = std::time::Duration::from_millis(SLEEP_MS);
-                std::thread::sleep(duration);
+        let mut reopened = self.storage.open(&self.path)?;
+        reopened.set_page_size(self.page_size());
+        *disk_manager = reopened;
+        *self.header.lock() = header;
+        self.free_page_ids.lock().clear();
+        self.next_page_id.store(next_page_id, Ordering::Release);
+        self.next_sequential_leaf.store(0, Ordering::Relaxed);
 
-                self.search_page(0, key)
-            }
-            Ok(page) => {
-                let node = page.node.as_ref().unwrap();
+        Ok(())
+    }
 
-                if node.node_type == NodeType::Leaf {
-                    return page;
+    /// Discard every pages of our buffer pool without flushing them, unless
+    /// one of them is still pinned, or latched once our frame_wait_timeout
+    /// elapses, in which case our buffer pool is left untouched.
+    fn reset_buffer_pool(&self) -> Result<(), PagerError> {
+        // A pinned page can't be unpinned without our page tables, so we
+        // never wait forever for its latch.
+        let deadline = self
+            .wait_deadline()
+            .unwrap_or_else(|| Instant::now() + DEFAULT_FRAME_WAIT_TIMEOUT);
+        // No page can be fetched (and pinned) without our page tables.
+        let mut page_tables: Vec<_> = self
+            .instances
+            .iter()
+            .map(|instance| instance.page_table.write())
+            .collect();
+
+        let mut latches = Vec::with_capacity(self.instances.len());
+        for instance in &self.instances {
+            let mut pages = Vec::with_capacity(instance.pages.capacity());
+            for page in instance.pages.iter() {
+                match page.try_write_until(deadline) {
+                    Some(page) if page.pin_count == 0 => pages.push(page),
+                    _ => return Err(PagerError::PagesInUse),
                 }
-
-                let next_page_num = node.search(key).unwrap();
-                self.unpin_page_with_read_guard(page, false);
-                self.search_page(next_page_num, key)
             }
+            latches.push(pages);
         }
-    }
-
-    pub fn find(
-        &self,
-        page_num: usize,
-        parent_page_guard: Option<RwLockUpgradableReadGuard<Page>>,
-        key: u32,
-    ) -> String {
-        self.find_with_retry(page_num, parent_page_guard, key, MAX_RETRY)
-    }
-
-    pub fn find_with_retry(
-        &self,
-        page_num: usize,
-        parent_page_guard: Option<RwLockUpgradableReadGuard<Page>>,
-        key: u32,
-        retry: usize,
-    ) -> String {
-        match self.fetch_read_page_guard(page_num) {
-            Err(_) => {
-                if retry == 0 {
-                    panic!("exceed max retry...");
-                }
 
-                if let Some(page) = parent_page_guard {
-                    self.unpin_page_with_read_guard(page, false);
+        for ((instance, page_table), pages) in
+            self.instances.iter().zip(&mut page_tables).zip(latches)
+        {
+            let pool_size = instance.pool_size();
+            for (frame_id, mut page) in pages.into_iter().enumerate() {
+                page.deallocate();
+                if frame_id < pool_size {
+                    instance.replacer.remove(frame_id);
                 }
-
-                let duration = std::time::Duration::from_millis(SLEEP_MS);
-                std::thread::sleep(duration);
-
-                self.find_with_retry(0, None, key, retry - 1)
             }
-            Ok(page) => {
-                let node = page.node.as_ref().unwrap();
-
-                if let Some(page) = parent_page_guard {
-                    self.unpin_page_with_read_guard(page, false);
-                }
 
-                if node.node_type == NodeType::Leaf {
-                    match node.search(key) {
-                        Ok(index) => {
-                            let row = node.get(index);
-                            self.unpin_page_with_read_guard(page, false);
-                            format!("{}\n", row.to_string())
-                        }
-                        Err(_index) => {
-                            self.unpin_page_with_read_guard(page, false);
-                            "".to_string()
-                        }
-                    }
-                } else if let Ok(next_page_num) = node.search(key) {
-                    self.find_with_retry(next_page_num, Some(page), key, retry)
-                } else {
-                    unreachable!("this shouldn't happen!");
-                }
-            }
+            let mut free_list = instance.free_list.lock();
+            free_list.clear();
+            free_list.extend((0..pool_size).rev());
+            page_table.clear();
         }
-    }
+        drop(page_tables);
 
-    fn min_key(&self, max_size: usize) -> usize {
-        let mut min_key = max_size / 2;
-
-        if min_key == 0 {
-            min_key = 1;
+        for instance in &self.instances {
+            instance.releases.notify();
         }
 
-        min_key
+        Ok(())
     }
 
-    pub fn node_to_string(&self, node_index: usize, indent_level: usize) -> String {
-        let page = self.fetch_read_page_guard(node_index).unwrap();
-        let node = page.node.as_ref().unwrap();
-        let mut result = String::new();
-
-        if node.node_type == NodeType::Internal {
-            for _ in 0..indent_level {
-                result += "  ";
-            }
-            result += &format!("- internal (size {})\n", node.num_of_cells);
-            let most_righ_child_index = node.right_child_offset as usize;
-
-            let mut child_pointers = vec![];
-            for c in &node.internal_cells {
-                let child_index = c.child_pointer() as usize;
-                child_pointers.push((child_index, c.key()));
-            }
-            self.unpin_page_with_read_guard(page, false);
-
-            for (i, k) in child_pointers {
-                result += &self.node_to_string(i, indent_level + 1);
-
-                for _ in 0..indent_level + 1 {
-                    result += "  ";
-                }
-                result += &format!("- key {}\n", k);
-            }
+    /// Write a node of a tree rebuilt by our vacuum directly to disk, see
+    /// BPlusTree::bulk_load.
+    pub(super) fn write_vacuum_page(
+        &self,
+        page_id: usize,
+        mut node: Node,
+    ) -> Result<(), PagerError> {
+        self.write_overflow_pages(&mut node)?;
 
-            result += &self.node_to_string(most_righ_child_index, indent_level + 1);
-        } else if node.node_type == NodeType::Leaf {
-            for _ in 0..indent_level {
-                result += "  ";
-            }
+        let mut page = Page::new(Some(page_id));
+        page.node = Some(node);
+        self.disk_manager
+            .read()
+            .write_page(page_id, &page.as_bytes())?;
 
-            result += &format!("- leaf (size {})\n", node.num_of_cells);
-            for c in &node.cells {
-                for _ in 0..indent_level + 1 {
-                    result += "  ";
-                }
-                result += &format!("- {}\n", c.key());
-            }
+        Ok(())
+    }
 
-            self.unpin_page_with_read_guard(page, false);
-        }
+    // ---------------------
+    // Concurrent Operations
+    // ---------------------
+    pub(super) fn fetch_write_page_guard_with_retry(
+        &self,
+        page_num: usize,
+    ) -> Result<WritePageGuard<'_>, PagerError> {
+        self.wait_for_page(page_num, || self.fetch_write_page_guard(page_num))
+    }
 
-        result
+    pub fn fetch_read_page_with_retry(
+        &self,
+        page_num: usize,
+    ) -> Result<ReadPageGuard<'_>, PagerError> {
+        self.wait_for_page(page_num, || self.fetch_read_page_guard(page_num))
     }
 
-    pub fn to_tree_string(&self) -> String {
-        if self.next_page_id.load(Ordering::Acquire) != 0 {
-            self.node_to_string(0, 0)
-        } else {
-            "Empty tree...".to_string()
-        }
+    pub fn fetch_scan_page_with_retry(
+        &self,
+        page_num: usize,
+    ) -> Result<ReadPageGuard<'_>, PagerError> {
+        self.wait_for_page(page_num, || self.fetch_scan_page_guard(page_num))
     }
 
-    // ---------------------
-    // Concurrent Operations
-    // ---------------------
-    fn retry<T, F>(&self, max_retry: usize, func: F) -> T
+    // -----------------
+    // Waiting for pages
+    // -----------------
+    //
+    // A page can't be fetched while every frames are pinned, or while its
+    // latch is held by another thread. Instead of sleeping and trying again,
+    // we block until another thread unpins a page of the same instance, which
+    // either releases a latch or makes a frame available for eviction, see
+    // ReleaseQueue.
+
+    /// Fetch the page with the given page id, blocking while the error is
+    /// transient, until our frame_wait_timeout elapses.
+    fn wait_for_page<T, F>(&self, page_id: usize, fetch: F) -> Result<T, PagerError>
     where
         F: Fn() -> Result<T, PagerError>,
     {
-        match func() {
-            Err(_) => {
-                if max_retry == 0 {
-                    panic!("exceed max retry");
-                }
-
-                let duration = std::time::Duration::from_millis(SLEEP_MS);
-                std::thread::sleep(duration);
+        let deadline = self.wait_deadline();
 
-                self.retry(max_retry - 1, func)
+        loop {
+            let epoch = self.release_epoch(page_id);
+            match fetch() {
+                Err(err) if err.is_transient() => {
+                    self.wait_for_retry(err, page_id, epoch, deadline)?
+                }
+                result => return result,
             }
-            Ok(page) => page,
         }
     }
 
-    fn fetch_write_page_guard_with_retry(&self, page_num: usize) -> RwLockWriteGuard<Page> {
-        self.retry(MAX_RETRY, || self.fetch_write_page_guard(page_num))
+    /// When a thread fetching pages gives up waiting for them, see
+    /// wait_for_retry.
+    pub(super) fn wait_deadline(&self) -> Option<Instant> {
+        self.frame_wait_timeout
+            .map(|timeout| Instant::now() + timeout)
     }
 
-    pub fn fetch_read_page_with_retry(&self, page_num: usize) -> RwLockUpgradableReadGuard<Page> {
-        self.retry(MAX_RETRY, || self.fetch_read_page_guard(page_num))
+    /// The number of times a page of the instance of the given page was
+    /// released so far, to be taken before fetching it, see wait_for_retry.
+    pub(super) fn release_epoch(&self, page_id: usize) -> u64 {
+        self.instance(page_id).releases.epoch()
     }
 
-    pub fn fetch_write_page_guard(
+    /// The number of releases the given pages notify to the threads waiting
+    /// for the given page, i.e. those in the same instance.
+    pub(super) fn releases_of(
         &self,
         page_id: usize,
-    ) -> Result<RwLockWriteGuard<Page>, PagerError> {
-        let page_table = self.page_table.upgradable_read();
+        page_ids: impl IntoIterator<Item = usize>,
+    ) -> u64 {
+        let num_of_instances = self.instances.len();
+        page_ids
+            .into_iter()
+            .filter(|id| id % num_of_instances == page_id % num_of_instances)
+            .count() as u64
+    }
+
+    /// Block until a page of the instance of the given page is released
+    /// after the given epoch once fetching it failed, so it can be tried
+    /// again, or return the error if it's not transient.
+    ///
+    /// A thread holding other pages while fetching (e.g. latch crabbing down
+    /// our B+ tree) must release them first, without waking up from it, see
+    /// Pager::releases_of.
+    pub(super) fn wait_for_retry(
+        &self,
+        err: PagerError,
+        page_id: usize,
+        epoch: u64,
+        deadline: Option<Instant>,
+    ) -> Result<(), PagerError> {
+        if !err.is_transient() {
+            return Err(err);
+        }
+        self.record_retry(&err);
+
+        if self.instance(page_id).releases.wait(epoch, deadline) {
+            Ok(())
+        } else {
+            Err(PagerError::NoFreePageAvailable)
+        }
+    }
+
+    /// Count a fetch that is tried again after the given transient error.
+    fn record_retry(&self, err: &PagerError) {
+        PagerCounters::increment(&self.counters.retries);
+        if matches!(err, PagerError::NoFreePageAvailable) {
+            PagerCounters::increment(&self.counters.pin_waits);
+        }
+    }
+
+    pub fn fetch_write_page_guard(&self, page_id: usize) -> Result<WritePageGuard<'_>, PagerError> {
+        let instance = self.instance(page_id);
+        let page_table = instance.page_table.upgradable_read();
 
         if let Some(&frame_id) = page_table.get(&page_id) {
-            let page = self.pages.get(frame_id).unwrap();
+            let page = instance.pages.get(frame_id).unwrap();
 
             if let Some(mut page) = page.try_write() {
                 page.pin_count += 1;
-                self.replacer.pin(frame_id);
+                instance.replacer.pin(frame_id);
                 drop(page_table);
+                PagerCounters::increment(&self.counters.hits);
+                self.record_read_ahead_hit(&mut page);
+                page.is_cold = false;
 
-                return Ok(page);
+                return Ok(WritePageGuard::new(self, page));
             } else {
                 drop(page_table);
                 return Err(PagerError::FailToAcquirePageLock);
             }
         }
 
-        self.replace_page(page_table, page_id)
+        self.replace_page(instance, page_table, page_id)
+            .map(|page| WritePageGuard::new(self, page))
+    }
+
+    pub fn fetch_read_page_guard(&self, page_id: usize) -> Result<ReadPageGuard<'_>, PagerError> {
+        self.fetch_read_page(page_id, false)
     }
 
-    pub fn fetch_read_page_guard(
+    /// Fetch a page for a sequential scan, which is evicted first unless
+    /// it's also fetched by another operation, see Replacer::unpin_cold.
+    pub fn fetch_scan_page_guard(&self, page_id: usize) -> Result<ReadPageGuard<'_>, PagerError> {
+        self.fetch_read_page(page_id, true)
+    }
+
+    fn fetch_read_page(
         &self,
         page_id: usize,
-    ) -> Result<RwLockUpgradableReadGuard<Page>, PagerError> {
-        let page_table = self.page_table.upgradable_read();
+        is_scan: bool,
+    ) -> Result<ReadPageGuard<'_>, PagerError> {
+        let instance = self.instance(page_id);
+        let page_table = instance.page_table.upgradable_read();
 
         if let Some(&frame_id) = page_table.get(&page_id) {
-            let page = self.pages.get(frame_id).unwrap();
+            let page = instance.pages.get(frame_id).unwrap();
             if let Some(mut page) = page.try_write() {
                 page.pin_count += 1;
-                self.replacer.pin(frame_id);
+                instance.replacer.pin(frame_id);
                 drop(page_table);
+                PagerCounters::increment(&self.counters.hits);
+                self.record_read_ahead_hit(&mut page);
+                self.detect_sequential_scan(&page);
+                if !is_scan {
+                    page.is_cold = false;
+                }
 
                 let page = RwLockWriteGuard::downgrade_to_upgradable(page);
-                return Ok(page);
+                return Ok(ReadPageGuard::new(self, page));
             } else {
                 drop(page_table);
                 return Err(PagerError::FailToAcquirePageLock);
             }
         }
 
-        self.replace_page(page_table, page_id)
-            .map(RwLockWriteGuard::downgrade_to_upgradable)
+        self.replace_page(instance, page_table, page_id)
+            .map(|mut page| {
+                page.is_cold = is_scan;
+                self.detect_sequential_scan(&page);
+                ReadPageGuard::new(self, RwLockWriteGuard::downgrade_to_upgradable(page))
+            })
     }
 
-    fn replace_page(
+    /// Pick a frame to evict from our replacer.
+    ///
+    /// The frames removed by Pager::resize are emptied instead of being
+    /// reused, as our replacer forgets about them.
+    fn evict(
         &self,
-        page_table: RwLockUpgradableReadGuard<HashMap<usize, usize>>,
+        instance: &BufferPoolInstance,
+        page_table: &mut HashMap<usize, usize>,
+    ) -> Option<usize> {
+        loop {
+            let frame_id = instance.replacer.victim()?;
+            if frame_id < instance.pool_size() {
+                PagerCounters::increment(&self.counters.evictions);
+                return Some(frame_id);
+            }
+
+            let mut page = instance.pages.get(frame_id).unwrap().write();
+            if let Err(err) = self.empty_frame(page_table, &mut page) {
+                // Our frame is emptied by Pager::resize instead.
+                warn!("failed to empty frame {frame_id}: {err}");
+                return None;
+            }
+        }
+    }
+
+    fn replace_page<'a>(
+        &'a self,
+        instance: &'a BufferPoolInstance,
+        page_table: RwLockUpgradableReadGuard<'_, HashMap<usize, usize>>,
         page_id: usize,
-    ) -> Result<RwLockWriteGuard<Page>, PagerError> {
+    ) -> Result<RwLockWriteGuard<'a, Page>, PagerError> {
         let mut page_table = RwLockUpgradableReadGuard::upgrade(page_table);
-        let mut free_list = self.free_list.lock();
+        let mut free_list = instance.free_list.lock();
         let frame_id = free_list
             .pop()
-            .or_else(|| self.replacer.victim().map(|md| md.frame_id));
+            .or_else(|| self.evict(instance, &mut page_table));
         drop(free_list);
 
         if let Some(frame_id) = frame_id {
-            let unlock_page = self.pages.get(frame_id).unwrap();
+            let unlock_page = instance.pages.get(frame_id).unwrap();
             let mut page = unlock_page.write();
 
-            // Update page table
-            page_table.retain(|_, &mut fid| fid != frame_id);
-            page_table.insert(page_id, frame_id);
-
             // Check if page is dirty. Flush page to disk
             // if needed
             if page.is_dirty {
                 let dirty_page_id = page.page_id.unwrap();
-                self.flush_write_page(dirty_page_id, &page);
+                if let Err(err) = self.flush_write_page(dirty_page_id, &mut page) {
+                    // Our victim stays in our buffer pool until it's written.
+                    instance.unpin_frame(frame_id, &page);
+                    return Err(err);
+                }
             }
 
+            // Update page table
+            page_table.retain(|_, &mut fid| fid != frame_id);
+            page_table.insert(page_id, frame_id);
+
             // Reset page
             page.is_dirty = false;
             page.pin_count = 1;
+            page.is_prefetched = false;
+            page.is_cold = false;
             page.page_id = Some(page_id);
+            instance.replacer.set_page(frame_id, page_id);
+            instance.replacer.pin(frame_id);
+
+            // Our page is latched and pinned, so the threads fetching it fail
+            // to latch it until it's read, while the other misses of this
+            // instance are read from disk at the same time.
+            drop(page_table);
 
-            match self.disk_manager.read_page(page_id) {
+            PagerCounters::increment(&self.counters.misses);
+            let bytes = self.disk_manager.read().read_page(page_id);
+            match bytes {
                 Ok(bytes) => {
-                    let page_from_disk = Page::from_bytes(&bytes);
-                    page.lsn = page_from_disk.lsn;
-                    page.page_id = page_from_disk.page_id;
-                    page.node = page_from_disk.node;
-                }
-                Err(_err) => {
-                    // This either mean the file is corrupted or is a partial page
-                    // or it's just a new file.
-                    if page_id == 0 {
-                        page.node = Some(Node::root());
+                    let page_from_disk = Page::from_bytes(page_id, &bytes).and_then(|mut page| {
+                        if let Some(node) = page.node.as_mut() {
+                            self.read_overflow_pages(node)?;
+                        }
+                        Ok(page)
+                    });
+
+                    match page_from_disk {
+                        Ok(page_from_disk) => {
+                            page.lsn = page_from_disk.lsn;
+                            page.page_id = page_from_disk.page_id;
+                            page.node = page_from_disk.node;
+                        }
+                        Err(err) => {
+                            Self::release_unread_frame(instance, frame_id, page_id, page);
+                            return Err(err);
+                        }
                     }
-
-                    self.next_page_id.fetch_add(1, Ordering::SeqCst);
+                }
+                Err(err) if self.is_past_end_of_file(page_id, &err) => {
+                    // A page that was never allocated nor written, which is
+                    // allocated by reading it.
+                    page.node = None;
+                    self.next_page_id.fetch_max(page_id + 1, Ordering::SeqCst);
+                }
+                Err(err) => {
+                    // Either our read failed, or our page was only partially
+                    // written, e.g. our file was truncated in the middle of it.
+                    Self::release_unread_frame(instance, frame_id, page_id, page);
+                    return Err(match err.kind() {
+                        std::io::ErrorKind::UnexpectedEof => PagerError::CorruptedPage(page_id),
+                        _ => PagerError::Io(err),
+                    });
                 }
             };
-            self.replacer.pin(frame_id);
-            drop(page_table);
 
             Ok(page)
         } else {
@@ -649,1030 +1768,1073 @@ impl Pager {
         }
     }
 
-    pub fn search_and_then<F, T>(
-        &self,
-        mut parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-        page_num: usize,
-        key: u32,
-        operation: Operation,
-        func: F,
-    ) -> Option<T>
-    where
-        F: FnOnce(Cursor, Vec<RwLockWriteGuard<Page>>, RwLockWriteGuard<Page>) -> Option<T>,
-    {
-        match self.fetch_write_page_guard(page_num) {
-            Ok(page) => {
-                let node = page.node.as_ref().unwrap();
-                let num_of_cells = node.num_of_cells as usize;
-                let might_split_or_merge = if operation == Operation::Insert {
-                    let max_cell = if node.node_type == NodeType::Leaf {
-                        LEAF_NODE_MAX_CELLS
-                    } else {
-                        INTERNAL_NODE_MAX_CELLS
-                    };
-                    num_of_cells + 1 > max_cell
-                } else if num_of_cells == 0 {
-                    false
-                } else {
-                    let min_key_length = if node.node_type == NodeType::Leaf {
-                        LEAF_NODE_MAX_CELLS / 2
-                    } else {
-                        self.min_key(INTERNAL_NODE_MAX_CELLS)
-                    };
-
-                    num_of_cells - 1 <= min_key_length
-                };
-
-                if !might_split_or_merge {
-                    while let Some(page) = parent_page_guards.pop() {
-                        self.unpin_page_with_write_guard(page, false);
-                    }
-                }
-                if node.node_type == NodeType::Leaf {
-                    match node.search(key) {
-                        Ok(index) => func(
-                            Cursor {
-                                page_num,
-                                cell_num: index,
-                                key_existed: true,
-                                end_of_table: index == num_of_cells,
-                            },
-                            parent_page_guards,
-                            page,
-                        ),
-                        Err(index) => func(
-                            Cursor {
-                                page_num,
-                                cell_num: index,
-                                key_existed: false,
-                                end_of_table: index == num_of_cells,
-                            },
-                            parent_page_guards,
-                            page,
-                        ),
-                    }
-                } else if let Ok(next_page_num) = node.search(key) {
-                    let mut parent_page_guards = parent_page_guards;
-                    parent_page_guards.push(page);
-                    self.search_and_then(parent_page_guards, next_page_num, key, operation, func)
-                } else {
-                    unreachable!("this shouldn't happen!");
-                }
-            }
-            Err(_) => {
-                for page in parent_page_guards {
-                    self.unpin_page_with_write_guard(page, false);
-                }
+    /// Give back the frame of a page we failed to read, so the page is read
+    /// and its error reported again by the next fetch.
+    fn release_unread_frame(
+        instance: &BufferPoolInstance,
+        frame_id: usize,
+        page_id: usize,
+        mut page: RwLockWriteGuard<'_, Page>,
+    ) {
+        let mut page_table = instance.page_table.write();
+        page.deallocate();
+        page_table.remove(&page_id);
+        instance.replacer.remove(frame_id);
+        instance.free_frame(frame_id);
+        drop(page_table);
+        drop(page);
+        instance.releases.notify();
+    }
 
-                let duration = std::time::Duration::from_millis(SLEEP_MS);
-                std::thread::sleep(duration);
+    /// Whether reading our page failed because it starts past the end of
+    /// our file and was never allocated, rather than being torn or lost.
+    fn is_past_end_of_file(&self, page_id: usize, err: &std::io::Error) -> bool {
+        let disk_manager = self.disk_manager.read();
+        err.kind() == std::io::ErrorKind::UnexpectedEof
+            && page_id >= self.next_page_id.load(Ordering::Acquire)
+            && page_id * disk_manager.page_size() >= disk_manager.file_len()
+    }
 
-                // Restart at root
-                self.search_and_then(vec![], 0, key, operation, func)
+    /// A snapshot of the statistics of our buffer pool, see PagerStats.
+    pub fn stats(&self) -> PagerStats {
+        let mut stats = self.counters.snapshot();
+        stats.pool_size = self.pool_size();
+        stats.free_frames = self
+            .instances
+            .iter()
+            .map(|instance| instance.free_list.lock().len())
+            .sum();
+
+        for (instance, frame_id) in self.frames() {
+            let page = instance.read_frame(frame_id);
+            match page.node.as_ref().map(|node| &node.node_type) {
+                Some(NodeType::Internal) => stats.internal_pages += 1,
+                Some(NodeType::Leaf) => stats.leaf_pages += 1,
+                None => {}
             }
         }
-    }
 
-    pub fn search(&self, root_page_num: usize, key: u32) -> Option<(usize, usize)> {
-        self.search_and_then(
-            vec![],
-            root_page_num,
-            key,
-            Operation::Insert,
-            |cursor, _parent_page_guards, _page| Some((cursor.page_num, cursor.cell_num)),
-        )
+        stats
     }
 
-    pub fn insert_row(&self, root_page_num: usize, row: &Row) -> Result<(usize, usize), String> {
-        self.search_and_then(
-            vec![],
-            root_page_num,
-            row.id,
-            Operation::Insert,
-            |cursor, parent_page_guards, mut page| {
-                if cursor.key_existed {
-                    return None;
-                };
-
-                let node = page.node.as_ref().unwrap();
-                let num_of_cells = node.num_of_cells as usize;
-
-                // If num cell = MAX CELL, inserting into it cause it to overflow
-                // which mean we need to insert and split.
-                if num_of_cells >= LEAF_NODE_MAX_CELLS {
-                    self.concurrent_insert_and_split_node(parent_page_guards, page, &cursor, row);
-                } else {
-                    let node = page.node.as_mut().unwrap();
-                    node.insert(row, &cursor);
-
-                    for page in parent_page_guards {
-                        self.unpin_page_with_write_guard(page, false);
-                    }
-
-                    self.unpin_page_with_write_guard(page, true);
-                }
+    pub fn debug_pages(&self) -> String {
+        use std::fmt::Write;
+        let mut result = String::new();
+        writeln!(&mut result, "--- Header ---").unwrap();
+        writeln!(&mut result, "{:?}", self.header()).unwrap();
 
-                Some((cursor.page_num, cursor.cell_num))
-            },
-        )
-        .ok_or_else(|| "duplicate key".to_string())
-    }
-
-    pub fn insert(&self, root_page_num: usize, row: &Row) -> Option<String> {
-        self.search_and_then(
-            vec![],
-            root_page_num,
-            row.id,
-            Operation::Insert,
-            |cursor, parent_page_guards, mut page| {
-                if cursor.key_existed {
-                    return Some("duplicate key\n".to_string());
-                };
-
-                let node = page.node.as_ref().unwrap();
-                let num_of_cells = node.num_of_cells as usize;
-
-                // If num cell = MAX CELL, inserting into it cause it to overflow
-                // which mean we need to insert and split.
-                //
-                // TRADEOFF: We are only splitting nodes when it's full.
-                //
-                // However, it could be done better by delaying splitting by moving cell
-                // to sibling nodes when necessary, which is called load balancing.
-                //
-                // This result in higher occupancy and delayed of node splitting.
-                if num_of_cells >= LEAF_NODE_MAX_CELLS {
-                    self.concurrent_insert_and_split_node(parent_page_guards, page, &cursor, row);
-                } else {
-                    let node = page.node.as_mut().unwrap();
-                    node.insert(row, &cursor);
-
-                    for page in parent_page_guards {
-                        self.unpin_page_with_write_guard(page, false);
-                    }
+        let free_page_ids = self.free_page_ids.lock().clone();
+        for i in HEADER_PAGE_ID + 1..self.next_page_id.load(Ordering::Relaxed) {
+            writeln!(&mut result, "--- Page {} ---", i).unwrap();
+            if free_page_ids.contains(&i) {
+                writeln!(&mut result, "free").unwrap();
+                continue;
+            }
 
-                    self.unpin_page_with_write_guard(page, true);
+            let bytes = self.disk_manager.read().read_page(i).unwrap();
+            let page = match bytes[PAGE_HEADER_BYTES] {
+                OVERFLOW_PAGE_TYPE => OverflowPage::from_bytes(i, &bytes).map(|p| format!("{p:?}")),
+                FREELIST_TRUNK_PAGE_TYPE => {
+                    FreelistTrunkPage::from_bytes(i, &bytes).map(|p| format!("{p:?}"))
                 }
-
-                Some(format!(
-                    "inserting into page: {}, cell: {}...\n",
-                    cursor.page_num, cursor.cell_num
-                ))
-            },
-        )
-    }
-
-    fn concurrent_insert_and_split_node(
-        &self,
-        parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-        mut left_page: RwLockWriteGuard<Page>,
-        cursor: &Cursor,
-        row: &Row,
-    ) {
-        let left_node = left_page.node.as_mut().unwrap();
-        let old_max = left_node.get_max_key();
-        left_node.insert(row, cursor);
-
-        let mut right_node = Node::new(false, left_node.node_type);
-        for _i in 0..LEAF_NODE_RIGHT_SPLIT_COUNT {
-            let cell = left_node.cells.remove(LEAF_NODE_LEFT_SPLIT_COUNT);
-            left_node.num_of_cells -= 1;
-
-            right_node.cells.push(cell);
-            right_node.num_of_cells += 1;
-        }
-
-        if left_node.is_root {
-            let left_max_key = left_node.get_max_key();
-
-            // If left node is root it shouldn't have any parent.
-            assert_eq!(parent_page_guards.len(), 0);
-
-            self.concurrent_create_new_root(left_page, right_node, left_max_key);
-        } else {
-            self.concurrent_split_node_and_update_parent(
-                parent_page_guards,
-                left_page,
-                right_node,
-                old_max,
-            );
-        }
-    }
-
-    fn concurrent_split_node_and_update_parent(
-        &self,
-        mut parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-        mut left_page: RwLockWriteGuard<Page>,
-        mut right_node: Node,
-        max_key: u32,
-    ) {
-        let mut right_page = self.new_page().unwrap();
-        let right_page_id = right_page.page_id.unwrap();
-        let left_node = left_page.node.as_mut().unwrap();
-        let new_max = left_node.get_max_key();
-
-        right_node.next_leaf_offset = left_node.next_leaf_offset;
-        left_node.next_leaf_offset = right_page_id as u32;
-
-        let new_child_max_key = right_node.get_max_key();
-        right_node.parent_offset = left_node.parent_offset;
-        self.unpin_page_with_write_guard(left_page, true);
-
-        right_page.node = Some(right_node);
-        self.unpin_page_with_write_guard(right_page, true);
-
-        assert!(!parent_page_guards.is_empty());
-        let mut parent_page = parent_page_guards.pop().unwrap();
-        let parent_node = parent_page.node.as_mut().unwrap();
-        parent_node.update_internal_key(max_key, new_max);
-
-        let split_at_page_num = right_page_id;
-
-        let parent_node = parent_page.node.as_ref().unwrap();
-        let parent_right_child_offset = parent_node.right_child_offset as usize;
-
-        let most_right_page = self.fetch_write_page_guard_with_retry(parent_right_child_offset);
-        let right_node = most_right_page.node.as_ref().unwrap();
-        let right_max_key = right_node.get_max_key();
-        self.unpin_page_with_write_guard(most_right_page, false);
-
-        let parent_node = parent_page.node.as_mut().unwrap();
-        parent_node.num_of_cells += 1;
-
-        let index = parent_node.internal_search(new_child_max_key);
-        if new_child_max_key > right_max_key {
-            parent_node.right_child_offset = split_at_page_num as u32;
-            parent_node.internal_insert(
-                index,
-                InternalCell::new(parent_right_child_offset as u32, right_max_key),
-            );
-        } else {
-            parent_node.internal_insert(
-                index,
-                InternalCell::new(split_at_page_num as u32, new_child_max_key),
-            );
+                _ => Page::from_bytes(i, &bytes).map(|p| format!("{:?}", p.node.unwrap())),
+            };
+            match page {
+                Ok(page) => writeln!(&mut result, "{page}").unwrap(),
+                Err(err) => writeln!(&mut result, "{err}").unwrap(),
+            }
         }
-
-        self.concurrent_split_internal_node(parent_page, parent_page_guards);
-    }
-
-    fn concurrent_create_new_root(
-        &self,
-        mut page: RwLockWriteGuard<Page>,
-        mut right_node: Node,
-        max_key: u32,
-    ) {
-        let mut left_page = self.new_page().unwrap();
-        let left_page_id = left_page.page_id.unwrap() as u32;
-
-        let mut right_page = self.new_page().unwrap();
-        let right_page_id = right_page.page_id.unwrap() as u32;
-
-        let mut root_node = Node::new(true, NodeType::Internal);
-        root_node.num_of_cells += 1;
-        root_node.right_child_offset = right_page_id;
-
-        right_node.parent_offset = 0;
-        right_node.next_leaf_offset = 0;
-
-        let mut left_node = page.node.take().unwrap();
-        left_node.is_root = false;
-        left_node.next_leaf_offset = right_page_id;
-        left_node.parent_offset = 0;
-
-        let cell = InternalCell::new(left_page_id, max_key);
-        root_node.internal_cells.insert(0, cell);
-
-        page.node = Some(root_node);
-        left_page.node = Some(left_node);
-        right_page.node = Some(right_node);
-
-        self.concurrent_update_children_parent_offset(&mut left_page);
-        self.unpin_page_with_write_guard(left_page, true);
-
-        self.concurrent_update_children_parent_offset(&mut right_page);
-        self.unpin_page_with_write_guard(right_page, true);
-        self.unpin_page_with_write_guard(page, true);
-    }
-
-    // TRADEOFF (Parent pointer):
-    //
-    // Upon parent pointer changes (due to split/merge), we will need to update
-    // all the childrens parent offset. Our internal nodes can store around 500
-    // child pointer. If we were to update around 250 child nodes parent offset
-    // during a split/merge (since only half of the childrens will be move),
-    // the cost of page in/out and potentially disk I/O will add up.
-    pub fn update_parent_offset(&self, page_id: usize, parent_page_id: usize) {
-        let mut page = self.fetch_write_page_guard_with_retry(page_id);
-        let child = page.node.as_mut().unwrap();
-        child.parent_offset = parent_page_id as u32;
-        self.unpin_page_with_write_guard(page, true);
+        result
     }
+}
 
-    pub fn concurrent_update_children_parent_offset(&self, page: &mut RwLockWriteGuard<Page>) {
-        let node = page.node.as_ref().unwrap();
-        let parent_page_id = page.page_id.unwrap();
-
-        let mut child_pointers = vec![node.right_child_offset as usize];
-        for cell in &node.internal_cells {
-            child_pointers.push(cell.child_pointer() as usize);
-        }
-
-        child_pointers.retain(|&i| i != 0);
-        for i in child_pointers {
-            self.update_parent_offset(i, parent_page_id);
-        }
-    }
+impl Drop for Pager {
+    fn drop(&mut self) {
+        if let Some(writer) = self.background_writer.get_mut().take() {
+            drop(writer.stop);
 
-    pub fn concurrent_split_internal_node(
-        &self,
-        mut left_page: RwLockWriteGuard<Page>,
-        mut parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-    ) {
-        // Check if our internal node need to be split. If it is equal to or less than MAX,
-        // no split is required.
-        if left_page.node.as_ref().unwrap().num_of_cells <= INTERNAL_NODE_MAX_CELLS as u32 {
-            for page in parent_page_guards {
-                self.unpin_page_with_write_guard(page, false);
+            // Our writer drops our pager if it holds the last reference to
+            // it, and a thread can't wait for itself.
+            if writer.handle.thread().id() != thread::current().id() {
+                let _ = writer.handle.join();
             }
-
-            self.unpin_page_with_write_guard(left_page, true);
-
-            return;
-        }
-
-        let left_node = left_page.node.as_mut().unwrap();
-        let split_at_index = left_node.num_of_cells as usize / 2;
-
-        let mut right_node = Node::new(false, NodeType::Internal);
-        right_node.right_child_offset = left_node.right_child_offset;
-        right_node.parent_offset = left_node.parent_offset as u32;
-
-        let ic = left_node.internal_cells.remove(split_at_index);
-        left_node.num_of_cells -= 1;
-        left_node.right_child_offset = ic.child_pointer();
-
-        let remaining_len = left_node.num_of_cells as usize - split_at_index;
-        for i in 0..remaining_len {
-            let ic = left_node.internal_cells.remove(split_at_index);
-            left_node.num_of_cells -= 1;
-            right_node.internal_insert(i, ic);
-            right_node.num_of_cells += 1;
         }
 
-        let left_node = left_page.node.as_ref().unwrap();
-
-        if left_node.is_root {
-            assert_eq!(parent_page_guards.len(), 0);
-            self.concurrent_create_new_root(left_page, right_node, ic.key());
-        } else {
-            let page_num = left_page.page_id.unwrap();
-
-            assert!(!parent_page_guards.is_empty());
-            let mut parent_page = parent_page_guards.pop().unwrap();
-            let parent = parent_page.node.as_mut().unwrap();
-            let index = parent.internal_search_child_pointer(page_num as u32);
-
-            let mut right_page = self.new_page().unwrap();
-            let right_page_id = right_page.page_id.unwrap() as u32;
-            right_page.is_dirty = true;
-            right_page.node = Some(right_node);
-
-            if parent.num_of_cells == index as u32 {
-                parent.right_child_offset = right_page_id;
-                parent.internal_insert(index, InternalCell::new(page_num as u32, ic.key()));
-                parent.num_of_cells += 1;
-            } else {
-                parent.internal_insert(index, InternalCell::new(page_num as u32, ic.key()));
-
-                let internel_cell = parent.internal_cells.remove(index + 1);
-                parent.internal_insert(
-                    index + 1,
-                    InternalCell::new(right_page_id, internel_cell.key()),
-                );
-                parent.num_of_cells += 1;
+        if let Some(read_ahead) = self.read_ahead.get_mut().take() {
+            drop(read_ahead.requests);
+            if read_ahead.handle.thread().id() != thread::current().id() {
+                let _ = read_ahead.handle.join();
             }
-
-            self.unpin_page_with_write_guard(left_page, true);
-            self.concurrent_update_children_parent_offset(&mut right_page);
-            self.unpin_page_with_write_guard(right_page, true);
-
-            self.concurrent_split_internal_node(parent_page, parent_page_guards);
         }
     }
+}
 
-    pub fn delete_by_key(&self, root_page_num: usize, key: u32) -> Option<String> {
-        self.search_and_then(
-            vec![],
-            root_page_num,
-            key,
-            Operation::Delete,
-            |cursor, parent_page_guards, mut page| {
-                if cursor.key_existed {
-                    let node = page.node.as_mut().unwrap();
-                    node.delete(cursor.cell_num);
-                    self.concurrent_maybe_merge_nodes(page, parent_page_guards);
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::row::Row;
+    use crate::row::Value;
+    use crate::storage::BPlusTree;
+    use crate::table::Table;
+    use std::str::FromStr;
+    use std::sync::atomic::AtomicU32;
 
-                    Some(format!("deleted {}", key))
-                } else {
-                    for page in parent_page_guards {
-                        self.unpin_page_with_write_guard(page, false);
-                    }
+    #[test]
+    fn pager_create_header_and_root_page_for_new_database() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let header = pager.header();
+        assert_eq!(header.catalog_root_page_id, 1);
+        assert_eq!(pager.catalog_root_page_id(), 1);
+        assert_eq!(tree(&pager, 1).to_tree_string(), "- leaf (size 0)\n");
 
-                    self.unpin_page_with_write_guard(page, false);
+        cleanup_test_db_file();
+    }
 
-                    Some(format!("item not found with id {}", key))
-                }
-            },
-        )
+    #[test]
+    fn pager_persist_header_on_flush() {
+        setup_test_db_file();
+        let pager = setup_test_pager();
+        let header = pager.header();
+        assert_eq!(header.catalog_root_page_id, 1);
+        assert_eq!(
+            header.page_count as usize,
+            pager.next_page_id.load(Ordering::Acquire)
+        );
+        assert!(header.page_count > 2);
+
+        cleanup_test_db_file();
     }
 
-    pub fn delete(&self, root_page_num: usize, row: &Row) -> Option<String> {
-        self.search_and_then(
-            vec![],
-            root_page_num,
-            row.id,
-            Operation::Delete,
-            |cursor, parent_page_guards, mut page| {
-                if cursor.key_existed {
-                    let node = page.node.as_mut().unwrap();
-                    node.delete(cursor.cell_num);
-                    self.concurrent_maybe_merge_nodes(page, parent_page_guards);
-
-                    Some(format!("deleted {}", row.id))
-                } else {
-                    for page in parent_page_guards {
-                        self.unpin_page_with_write_guard(page, false);
-                    }
+    #[test]
+    fn pager_error_when_open_file_that_is_not_a_database() {
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        std::fs::write(&file, [1; DEFAULT_PAGE_SIZE]).unwrap();
 
-                    self.unpin_page_with_write_guard(page, false);
+        let result = Pager::open(&file, 8);
+        assert!(matches!(result, Err(PagerError::NotADatabase)));
 
-                    Some(format!("item not found with id {}", row.id))
-                }
-            },
-        )
+        cleanup_test_db_file();
     }
 
-    fn concurrent_maybe_merge_nodes(
-        &self,
-        page: RwLockWriteGuard<Page>,
-        parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-    ) {
-        let node = page.node.as_ref().unwrap();
-
-        // TRADEOFF: We could leave the node to be underflow.
-        //
-        // We avoid load balancing or even merging because we are hoping
-        // for the subsequent insert or defragmentation to resolve it.
-        //
-        // Study has show that, rebalancing on deletion can be considered harmful.
-        if node.node_type == NodeType::Leaf
-            && node.num_of_cells <= LEAF_NODE_MAX_CELLS as u32 / 2
-            && !node.is_root
-        {
-            return self.concurrent_merge_leaf_nodes(page, parent_page_guards);
+    #[test]
+    fn pager_create_database_with_page_size() {
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        for page_size in [1024, 16 * 1024, 64 * 1024] {
+            cleanup_test_db_file();
+            let pager =
+                Pager::open_with_options(&file, 8, options_with_page_size(page_size)).unwrap();
+            let root = BPlusTree::<u64, Row>::create(&pager)
+                .unwrap()
+                .root_page_id();
+            let rows: Vec<Row> = (1..=100)
+                .map(|i| Row::new(i, vec![Value::Text("a".repeat(300))]))
+                .collect();
+            for row in &rows {
+                assert!(tree(&pager, root).insert(row.id, row).is_ok());
+            }
+            pager.flush_all_pages().unwrap();
+            assert_eq!(pager.header().page_size as usize, page_size);
+            let file_len = std::fs::metadata(&file).unwrap().len() as usize;
+            assert_eq!(file_len % page_size, 0);
+
+            // The page size of an existing database can't be changed.
+            let pager =
+                Pager::open_with_options(&file, 8, options_with_page_size(DEFAULT_PAGE_SIZE))
+                    .unwrap();
+            assert_eq!(pager.page_size(), page_size);
+            assert_eq!(tree(&pager, root).values().unwrap(), rows);
         }
 
-        for page in parent_page_guards {
-            self.unpin_page_with_write_guard(page, false);
-        }
+        cleanup_test_db_file();
+        let result = Pager::open_with_options(&file, 8, options_with_page_size(1000));
+        assert!(matches!(result, Err(PagerError::InvalidPageSize(1000))));
 
-        self.unpin_page_with_write_guard(page, true);
+        cleanup_test_db_file();
     }
 
-    fn concurrent_merge_leaf_nodes(
-        &self,
-        page: RwLockWriteGuard<Page>,
-        mut parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-    ) {
-        let page_id = page.page_id.unwrap();
-        let node = page.node.as_ref().unwrap();
-        let node_cells_len = node.cells.len();
-
-        let parent_page = parent_page_guards.pop().unwrap();
-        let parent = parent_page.node.as_ref().unwrap();
-        let (left_child_pointer, right_child_pointer) = parent.siblings(page_id as u32);
-        debug!("-- merge leaf node {page_id}: {left_child_pointer:?}, {right_child_pointer:?}");
-
-        if let Some(cp) = left_child_pointer {
-            if cp != page_id && cp != 0 {
-                let left_page = self.fetch_write_page_guard_with_retry(cp);
-                let left_nb = left_page.node.as_ref().unwrap();
-
-                // If merging both result does not exceed MAX, proceed
-                if left_nb.cells.len() + node_cells_len <= LEAF_NODE_MAX_CELLS {
-                    debug!("-- merge leaf node {} with its left neighbour...", page_id);
-                    return self.concurrent_do_merge_leaf_nodes(
-                        parent_page,
-                        left_page,
-                        page,
-                        parent_page_guards,
-                    );
-                }
-
-                if node_cells_len == 0 {
-                    warn!(
-                        "-- failed to merge leaf {page_id}, len: {} + {} >= MAX",
-                        node_cells_len,
-                        left_nb.cells.len()
-                    );
-                }
-
-                self.unpin_page_with_write_guard(left_page, false);
-            } else {
-                warn!("-- fail to merge {page_id}, cp: {cp}");
+    #[test]
+    fn pager_write_pages_with_every_synchronous_mode() {
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let rows: Vec<Row> = (1..=100)
+            .map(|i| Row::new(i, vec![Value::Text(format!("user{i}"))]))
+            .collect();
+        for synchronous in [
+            SynchronousMode::Off,
+            SynchronousMode::Normal,
+            SynchronousMode::Full,
+        ] {
+            cleanup_test_db_file();
+            let options = PagerOptions {
+                synchronous,
+                background_writer: None,
+                ..PagerOptions::default()
+            };
+            // Our pages are evicted, so they are written one by one.
+            let pager = Pager::open_with_options(&file, 4, options).unwrap();
+            let root = BPlusTree::<u64, Row>::create(&pager)
+                .unwrap()
+                .root_page_id();
+            for row in &rows {
+                assert!(tree(&pager, root).insert(row.id, row).is_ok());
             }
+            assert!(pager.stats().evictions > 0);
+            pager.flush_all_pages().unwrap();
+
+            let pager = Pager::open_with_options(&file, 4, options).unwrap();
+            assert_eq!(pager.synchronous(), synchronous);
+            assert_eq!(tree(&pager, root).values().unwrap(), rows);
         }
 
-        if let Some(cp) = right_child_pointer {
-            if cp != page_id && cp != 0 {
-                let right_page = self.fetch_write_page_guard_with_retry(cp);
-                let right_nb = right_page.node.as_ref().unwrap();
+        cleanup_test_db_file();
+    }
 
-                if right_nb.cells.len() + node_cells_len <= LEAF_NODE_MAX_CELLS {
-                    debug!("-- merge leaf node {} with its right neighbour...", page_id);
-                    return self.concurrent_do_merge_leaf_nodes(
-                        parent_page,
-                        page,
-                        right_page,
-                        parent_page_guards,
-                    );
-                }
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn pager_read_and_flush_pages_through_io_uring() {
+        cleanup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let open_pager = |storage| {
+            let options = PagerOptions {
+                storage,
+                background_writer: None,
+                read_ahead: 0,
+                ..PagerOptions::default()
+            };
+            Pager::open_with_options(&file, 16, options).unwrap()
+        };
+        let pager = open_pager(StorageBackend::IoUring);
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        let rows: Vec<Row> = (1..=400)
+            .map(|i| Row::new(i, vec![Value::Text(format!("user{i}"))]))
+            .collect();
+        for row in &rows {
+            assert!(tree(&pager, root).insert(row.id, row).is_ok());
+        }
+        pager.flush_all_pages().unwrap();
+        drop(pager);
+
+        // Our misses are read from disk at the same time.
+        let pager = open_pager(StorageBackend::IoUring);
+        std::thread::scope(|s| {
+            for rows in rows.chunks(100) {
+                let pager = &pager;
+                s.spawn(move || {
+                    for row in rows.iter().rev() {
+                        assert_eq!(tree(pager, root).get(row.id).unwrap().as_ref(), Some(row));
+                    }
+                });
+            }
+        });
+        assert!(pager.stats().misses > 16);
+        drop(pager);
 
-                if node_cells_len == 0 {
-                    warn!(
-                        "-- failed to merge leaf {page_id}, len: {} + {} >= MAX",
-                        node_cells_len,
-                        right_nb.cells.len()
-                    );
-                }
+        let pager = open_pager(StorageBackend::File);
+        assert_eq!(tree(&pager, root).values().unwrap(), rows);
 
-                self.unpin_page_with_write_guard(right_page, false);
-            } else {
-                warn!("-- fail to merge {page_id}, cp: {cp}");
-            }
-        }
+        cleanup_test_db_file();
+    }
 
-        // Drop parent guards lock
-        for page in parent_page_guards {
-            self.unpin_page_with_write_guard(page, false);
+    #[test]
+    fn pager_open_database_with_mmap_storage() {
+        cleanup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let open_pager = |storage| {
+            let options = PagerOptions {
+                storage,
+                background_writer: None,
+                ..PagerOptions::default()
+            };
+            Pager::open_with_options(&file, 8, options).unwrap()
+        };
+        let pager = open_pager(StorageBackend::Mmap);
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        let rows: Vec<Row> = (1..=300)
+            .map(|i| Row::new(i, vec![Value::Text(format!("user{i}"))]))
+            .collect();
+        for row in &rows {
+            assert!(tree(&pager, root).insert(row.id, row).is_ok());
+        }
+        assert_eq!(tree(&pager, root).values().unwrap(), rows);
+        pager.flush_all_pages().unwrap();
+        drop(pager);
+
+        // Our database file is the same whatever our storage backend is.
+        for storage in [StorageBackend::File, StorageBackend::Mmap] {
+            let pager = open_pager(storage);
+            assert_eq!(tree(&pager, root).values().unwrap(), rows);
         }
 
-        self.unpin_page_with_write_guard(parent_page, false);
-        self.unpin_page_with_write_guard(page, true);
+        cleanup_test_db_file();
     }
 
-    fn concurrent_do_merge_leaf_nodes(
-        &self,
-        mut parent_page: RwLockWriteGuard<Page>,
-        mut left_page: RwLockWriteGuard<Page>,
-        mut right_page: RwLockWriteGuard<Page>,
-        parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-    ) {
-        let right_page_id = right_page.page_id.unwrap();
-        let left_page_id = left_page.page_id.unwrap();
-        // Take the node of right page and left page out of page.
-        //
-        // Free up the pages as we don't need it anymore.
-        let left_node = left_page.node.as_mut().unwrap();
-        let right_node = right_page.node.take().unwrap();
+    #[test]
+    fn pager_keep_root_and_upper_internal_pages_resident_during_scans() {
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let policies = [
+            ReplacementPolicy::Clock,
+            ReplacementPolicy::Lru,
+            ReplacementPolicy::LruK(2),
+            ReplacementPolicy::Lfu,
+        ];
+        for replacement_policy in policies {
+            cleanup_test_db_file();
+            let options = PagerOptions {
+                replacement_policy,
+                ..PagerOptions::default()
+            };
+            // Our internal nodes fit into our buffer pool, but our leaf nodes don't.
+            let pager = Pager::open_with_options(&file, 16, options).unwrap();
+            let root = BPlusTree::<u64, Row>::create(&pager)
+                .unwrap()
+                .root_page_id();
+            let rows: Vec<Row> = (1..=150)
+                .map(|i| Row::new(i, vec![Value::Text(format!("user{i}"))]))
+                .collect();
+            for row in &rows {
+                assert!(tree(&pager, root).insert(row.id, row).is_ok());
+            }
 
-        // Merge the leaf nodes cells
-        for c in right_node.cells {
-            left_node.cells.push(c);
-            left_node.num_of_cells += 1;
-        }
-        left_node.next_leaf_offset = right_node.next_leaf_offset;
-        let parent = parent_page.node.as_mut().unwrap();
+            let page = pager.fetch_read_page_with_retry(root).unwrap();
+            let node = page.node.as_ref().unwrap();
+            let mut upper_page_ids = vec![root, node.right_child_offset as usize];
+            for cell in node.internal_cells.iter() {
+                upper_page_ids.push(cell.child_pointer() as usize);
+            }
+            drop(page);
 
-        if parent.num_of_cells == 1 && parent.is_root {
-            self.concurrent_promote_node_to_root(parent_page, left_page, right_page);
-        } else {
-            self.delete_page_with_write_guard(right_page);
-
-            let max_key = left_node.get_max_key();
-            debug!("-- left_page ({max_key}): {:?}", left_page);
-            self.unpin_page_with_write_guard(left_page, true);
-
-            let index = parent.internal_search_child_pointer(right_page_id as u32);
-            if index == parent.num_of_cells as usize {
-                // The right_cp is our right child offset
-
-                // Move last internal cell to become the right child offset
-                // if parent.num_of_cells > 1 {
-                debug!("update to right child pointer");
-                let internal_cell = parent.internal_cells.remove(index - 1);
-                parent.num_of_cells -= 1;
-                parent.right_child_offset = internal_cell.child_pointer();
-            } else {
-                debug!("remove index");
-                // Remove extra key, pointers cell as we now have one less child
-                // after merge
-                parent.num_of_cells -= 1;
-                parent.internal_cells.remove(index);
-
-                // Update the key for our existing child pointer pointing to our merged node
-                // to use the new max key.
-                if index != 0 {
-                    debug!("update {} to max key: {max_key}", index - 1);
-                    parent.internal_cells[index - 1] =
-                        InternalCell::new(left_page_id as u32, max_key);
+            // A few hot lookups go through our root and its children, followed
+            // by a scan through every leaf nodes.
+            //
+            // As the counts of LFU never decay, it takes a few rounds for our
+            // hot pages to be accessed more than the leaf nodes of our inserts.
+            for _ in 0..20 {
+                for row in [&rows[0], &rows[74], &rows[149]] {
+                    assert_eq!(tree(&pager, root).get(row.id).unwrap().as_ref(), Some(row));
                 }
+                assert_eq!(tree(&pager, root).values().unwrap(), rows);
             }
 
-            debug!("-- parent_page: {:?}", parent_page);
-            debug!("-- merge leaf node (end)\n\n");
-            self.concurrent_merge_internal_nodes(parent_page, parent_page_guards)
+            let is_resident = |page_id| {
+                pager
+                    .instance(page_id)
+                    .page_table
+                    .read()
+                    .contains_key(&page_id)
+            };
+            for page_id in upper_page_ids {
+                assert!(
+                    is_resident(page_id),
+                    "{replacement_policy:?} evicted page {page_id}"
+                );
+            }
         }
-    }
 
-    fn concurrent_promote_node_to_root(
-        &self,
-        mut parent_page: RwLockWriteGuard<Page>,
-        mut left_page: RwLockWriteGuard<Page>,
-        right_page: RwLockWriteGuard<Page>,
-    ) {
-        // Take left node out of left page as it will be used to replace
-        // the node in our parent.
-        let mut left_node = left_page.node.take().unwrap();
+        cleanup_test_db_file();
+    }
 
-        // Replace the parent.node with our new combined left node
-        left_node.is_root = true;
-        left_node.next_leaf_offset = 0;
-        parent_page.node = Some(left_node);
+    #[test]
+    fn pager_block_until_a_frame_is_released() {
+        setup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let open_pager = |timeout| {
+            let options = PagerOptions {
+                frame_wait_timeout: Some(timeout),
+                ..PagerOptions::default()
+            };
+            Pager::open_with_options(&file, 2, options).unwrap()
+        };
 
-        self.delete_page_with_write_guard(left_page);
-        self.delete_page_with_write_guard(right_page);
+        // Every frames are pinned, so we give up after the timeout.
+        let pager = open_pager(Duration::from_millis(50));
+        let page_1 = pager.fetch_read_page_with_retry(1).unwrap();
+        let page_2 = pager.fetch_read_page_with_retry(2).unwrap();
+        let started = Instant::now();
+        let result = pager.fetch_read_page_with_retry(3);
+        assert!(matches!(result, Err(PagerError::NoFreePageAvailable)));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        drop(page_1);
+        drop(page_2);
+
+        // Unpinning a page from another thread wakes us up.
+        let pager = open_pager(Duration::from_secs(10));
+        let page_2 = pager.fetch_read_page_with_retry(2).unwrap();
+        let barrier = std::sync::Barrier::new(2);
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let page_1 = pager.fetch_read_page_with_retry(1).unwrap();
+                barrier.wait();
+                std::thread::sleep(Duration::from_millis(20));
+                drop(page_1);
+            });
+
+            barrier.wait();
+            let page_3 = pager.fetch_read_page_with_retry(3).unwrap();
+            assert_eq!(page_3.page_id, Some(3));
+            drop(page_3);
+        });
+        drop(page_2);
 
-        self.concurrent_update_children_parent_offset(&mut parent_page);
-        debug!("parent_page: {parent_page:?}");
-        debug!("promote node to root (end)\n\n");
-        self.unpin_page_with_write_guard(parent_page, true);
+        cleanup_test_db_file();
     }
 
-    fn concurrent_merge_internal_nodes(
-        &self,
-        page: RwLockWriteGuard<Page>,
-        mut parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-    ) {
-        let page_id = page.page_id.unwrap();
-        let node = page.node.as_ref().unwrap();
-        let node_num_of_cells = node.num_of_cells;
-        let min_key_length = self.min_key(INTERNAL_NODE_MAX_CELLS) as u32;
-
-        // Skip merging internal node if it has more than min_key length.
-        // In our case > 1. If it's equals to, we will still need to merge.
-        if node.num_of_cells > min_key_length || node.is_root {
-            for page in parent_page_guards {
-                self.unpin_page_with_write_guard(page, false);
+    #[test]
+    fn pager_count_buffer_pool_statistics() {
+        setup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let options = PagerOptions {
+            frame_wait_timeout: Some(Duration::from_millis(10)),
+            background_writer: None,
+            ..PagerOptions::default()
+        };
+        let pager = Pager::open_with_options(&file, 2, options).unwrap();
+        assert_eq!(
+            pager.stats(),
+            PagerStats {
+                pool_size: 2,
+                free_frames: 2,
+                ..PagerStats::default()
             }
+        );
 
-            self.unpin_page_with_write_guard(page, true);
-            return;
-        }
+        // Our catalog root is read from disk once, then found in our buffer pool.
+        drop(pager.fetch_read_page_guard(1).unwrap());
+        let mut page = pager.fetch_write_page_guard(1).unwrap();
+        page.lsn += 1;
+        drop(page);
+        let stats = pager.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.hit_ratio(), Some(0.5));
+        assert_eq!((stats.free_frames, stats.leaf_pages), (1, 1));
+
+        // Our catalog root is evicted to make room for our table root and
+        // its first leaf, and written back as it's dirty.
+        drop(pager.fetch_read_page_guard(2).unwrap());
+        drop(pager.fetch_read_page_guard(3).unwrap());
+        let stats = pager.stats();
+        assert_eq!(
+            (stats.misses, stats.evictions, stats.dirty_writes),
+            (3, 1, 1)
+        );
+        assert_eq!((stats.internal_pages, stats.leaf_pages), (1, 1));
 
-        assert!(!parent_page_guards.is_empty());
-        let parent_page = parent_page_guards.pop().unwrap();
-        let parent = parent_page.node.as_ref().unwrap();
+        // Every frames are pinned, so we wait for a frame before giving up.
+        let page_2 = pager.fetch_read_page_guard(2).unwrap();
+        let page_3 = pager.fetch_read_page_guard(3).unwrap();
+        assert!(pager.fetch_read_page_with_retry(1).is_err());
+        let stats = pager.stats();
+        assert_eq!((stats.pin_waits, stats.retries), (1, 1));
+        drop(page_2);
+        drop(page_3);
 
-        let (left_child_pointer, right_child_pointer) = parent.siblings(page_id as u32);
-        debug!(
-            "-- merge internal page {}: {left_child_pointer:?}, {right_child_pointer:?}",
-            page.page_id.unwrap()
-        );
+        cleanup_test_db_file();
+    }
 
-        if let Some(cp) = left_child_pointer {
-            if cp != page_id && cp != 0 {
-                let left_page = self.fetch_write_page_guard_with_retry(cp);
-                let left_nb = left_page.node.as_ref().unwrap();
-
-                if left_nb.num_of_cells + node_num_of_cells < INTERNAL_NODE_MAX_CELLS as u32 {
-                    debug!("-- merge internal node {page_id} with left neighbour");
-                    self.concurrent_do_merge_internal_nodes(
-                        parent_page,
-                        left_page,
-                        page,
-                        parent_page_guards,
-                    );
-                    return;
+    #[test]
+    fn pager_resize_buffer_pool() {
+        cleanup_test_db_file();
+        setup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let open_pager = |pool_size, timeout| {
+            let options = PagerOptions {
+                frame_wait_timeout: Some(timeout),
+                background_writer: None,
+                ..PagerOptions::default()
+            };
+            Pager::open_with_options(&file, pool_size, options).unwrap()
+        };
+        let rows: Vec<Row> = (1..50)
+            .map(|i| Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap())
+            .collect();
+
+        // Growing gives us new frames for our pages.
+        let pager = open_pager(2, Duration::from_millis(50));
+        let mut pages = vec![pager.fetch_read_page_guard(1).unwrap()];
+        pages.push(pager.fetch_read_page_guard(2).unwrap());
+        assert!(pager.fetch_read_page_with_retry(3).is_err());
+        pager.resize(4).unwrap();
+        pages.push(pager.fetch_read_page_guard(3).unwrap());
+        pages.push(pager.fetch_read_page_guard(4).unwrap());
+        assert_eq!(pager.stats().pool_size, 4);
+
+        // Pinned frames can't be removed.
+        assert!(matches!(
+            pager.resize(2),
+            Err(PagerError::NoFreePageAvailable)
+        ));
+        assert_eq!(pager.stats().pool_size, 4);
+        drop(pages);
+
+        // Our dirty pages are written back before being evicted.
+        let mut page = pager.fetch_write_page_guard(4).unwrap();
+        page.lsn += 1;
+        drop(page);
+        pager.resize(2).unwrap();
+        let stats = pager.stats();
+        assert_eq!((stats.pool_size, stats.dirty_writes), (2, 1));
+        assert!(stats.internal_pages + stats.leaf_pages + stats.free_frames <= 2);
+        assert_eq!(tree(&pager, 2).values().unwrap(), rows);
+
+        // Our removed frames are reused once we grow again.
+        pager.resize(3).unwrap();
+        let pages: Vec<ReadPageGuard> = (1..=3)
+            .map(|page_id| pager.fetch_read_page_guard(page_id).unwrap())
+            .collect();
+        drop(pages);
+        drop(pager);
+
+        // Our inserts keep going while our buffer pool is resized.
+        let pager = open_pager(8, Duration::from_secs(10));
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 50..300 {
+                    let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+                    assert!(tree(&pager, 2).insert(row.id, &row).is_ok());
                 }
+            });
 
-                self.steal_from_sibling(parent_page, left_page, page, parent_page_guards);
-                return;
-
-                // self.unpin_page_with_write_guard(left_page, false);
-            } else {
-                warn!("-- failed to merge internal, cp: {cp}");
+            for pool_size in [16, 8, 32, 8, 12] {
+                pager.resize(pool_size).unwrap();
+                std::thread::sleep(Duration::from_millis(5));
             }
-        }
+        });
+        let ids: Vec<u64> = tree(&pager, 2)
+            .values()
+            .unwrap()
+            .iter()
+            .map(|row| row.id)
+            .collect();
+        assert_eq!(ids, (1..300).collect::<Vec<u64>>());
 
-        if let Some(cp) = right_child_pointer {
-            if cp != page_id && cp != 0 {
-                let right_page = self.fetch_write_page_guard_with_retry(cp);
-                let right_nb = right_page.node.as_ref().unwrap();
-
-                if right_nb.num_of_cells + node_num_of_cells <= INTERNAL_NODE_MAX_CELLS as u32 {
-                    debug!("-- merge internal node {page_id} with right neighbour");
-                    self.concurrent_do_merge_internal_nodes(
-                        parent_page,
-                        page,
-                        right_page,
-                        parent_page_guards,
-                    );
-                    return;
-                }
+        cleanup_test_db_file();
+    }
 
-                self.steal_from_sibling(parent_page, page, right_page, parent_page_guards);
-                return;
+    #[test]
+    fn pager_split_buffer_pool_into_instances() {
+        cleanup_test_db_file();
+        setup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let options = PagerOptions {
+            buffer_pool_instances: Some(4),
+            frame_wait_timeout: Some(Duration::from_secs(10)),
+            background_writer: None,
+            ..PagerOptions::default()
+        };
+        // Every instance needs the frames of a whole traversal.
+        assert!(matches!(
+            Pager::open_with_options(&file, 31, options),
+            Err(PagerError::TooFewFrames(32))
+        ));
+        let pager = Pager::open_with_options(&file, 32, options).unwrap();
+        assert!(matches!(
+            pager.resize(31),
+            Err(PagerError::TooFewFrames(32))
+        ));
 
-                // self.unpin_page_with_write_guard(right_page, false);
-            } else {
-                warn!("-- failed to merge internal, cp: {cp}");
+        // A page only goes to the instance of its page id.
+        let pages: Vec<ReadPageGuard> = (1..=3)
+            .map(|page_id| pager.fetch_read_page_guard(page_id).unwrap())
+            .collect();
+        for page_id in 1..=3 {
+            for (i, instance) in pager.instances.iter().enumerate() {
+                let page_table = instance.page_table.read();
+                assert_eq!(page_table.contains_key(&page_id), i == page_id % 4);
             }
         }
+        drop(pages);
+
+        // Our instances are shared by concurrent inserts.
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let pager = &pager;
+                scope.spawn(move || {
+                    for i in (50..400).filter(|i| i % 4 == thread) {
+                        let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+                        assert!(tree(pager, 2).insert(row.id, &row).is_ok());
+                    }
+                });
+            }
+        });
+        let ids: Vec<u64> = tree(&pager, 2)
+            .values()
+            .unwrap()
+            .iter()
+            .map(|row| row.id)
+            .collect();
+        assert_eq!(ids, (1..400).collect::<Vec<u64>>());
+        assert_eq!(pager.stats().pool_size, 32);
 
-        // Drop parent guards lock
-        for page in parent_page_guards {
-            self.unpin_page_with_write_guard(page, false);
-        }
-
-        self.unpin_page_with_write_guard(page, true);
-        self.unpin_page_with_write_guard(parent_page, false);
+        cleanup_test_db_file();
     }
 
-    fn steal_from_sibling(
-        &self,
-        mut parent_page: RwLockWriteGuard<Page>,
-        mut left_page: RwLockWriteGuard<Page>,
-        mut right_page: RwLockWriteGuard<Page>,
-        parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-    ) {
-        debug!("-- steal from sibling");
-        let min_key_length = self.min_key(INTERNAL_NODE_MAX_CELLS) as u32;
-        let left_page_id = left_page.page_id.unwrap();
-        let left_node = left_page.node.as_mut().unwrap();
-        let right_node = right_page.node.as_mut().unwrap();
-        let parent_node = parent_page.node.as_mut().unwrap();
-
-        // Left node have less cell so let's steal from our right node.
-        if left_node.num_of_cells < min_key_length {
-            debug!("-- steal from right");
-            // Get the parent key that's pointing to the left node
-            let index = parent_node.internal_search_child_pointer(left_page_id as u32);
-            let parent_key = parent_node.internal_cells[index].key();
-
-            // Move the parent key into internal cell and link it to our one and only right child.
-            // It won't be the most right child anymore as we are going to steal our most right
-            // child from our right siblings.
-            let internal_cell = InternalCell::new(left_node.right_child_offset, parent_key);
-            left_node.internal_cells.push(internal_cell);
-            left_node.num_of_cells += 1;
-
-            // Remove the first internal cell from our right siblings and make it our own
-            // children.
-            let min_internal_cell = right_node.internal_cells.remove(0);
-            let new_most_right_child_page_id = min_internal_cell.child_pointer();
-            right_node.num_of_cells -= 1;
-            left_node.right_child_offset = new_most_right_child_page_id;
-            debug!("-- right_page: {:?}", right_page);
-            self.unpin_page_with_write_guard(right_page, true);
-
-            // Update our new children parent offset
-            self.update_parent_offset(
-                new_most_right_child_page_id as usize,
-                left_page.page_id.unwrap(),
-            );
-            debug!("-- left_page: {:?}", left_page);
-            self.unpin_page_with_write_guard(left_page, true);
-
-            // Replace our parent key with the the node key we steal from right sibling.
-            parent_node.internal_cells[index].write_key(min_internal_cell.key());
-            debug!("-- parent_page: {:?}", parent_page);
-            debug!("-- steal sibling (end)\n\n");
-
-            for page in parent_page_guards {
-                self.unpin_page_with_write_guard(page, false);
-            }
+    #[test]
+    fn pager_read_ahead_leaf_pages() {
+        cleanup_test_db_file();
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let open_pager = |pool_size| {
+            let options = PagerOptions {
+                background_writer: None,
+                read_ahead: 4,
+                ..PagerOptions::default()
+            };
+            Pager::open_with_options(&file, pool_size, options).unwrap()
+        };
+        let pager = open_pager(32);
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        for i in 1..400 {
+            let row = Row::from_str(&format!("{i} user{i} user{i}@email.com")).unwrap();
+            assert!(tree(&pager, root).insert(row.id, &row).is_ok());
+        }
+        pager.flush_all_pages().unwrap();
+        drop(pager);
+
+        // The leaf pages after the given one are read into our free frames.
+        let pager = open_pager(32);
+        let page = tree(&pager, root).search_page(None).unwrap();
+        let next_leaf = page.node.as_ref().unwrap().next_leaf_offset as usize;
+        drop(page);
+        assert_eq!(pager.read_ahead(next_leaf), 4);
+        assert_eq!(pager.read_ahead(next_leaf), 0);
+
+        let misses = pager.stats().misses;
+        let page = pager.fetch_read_page_guard(next_leaf).unwrap();
+        assert!(page.node.as_ref().unwrap().num_of_cells > 0);
+        drop(page);
+        let stats = pager.stats();
+        assert_eq!(
+            (stats.misses, stats.read_ahead, stats.read_ahead_hits),
+            (misses, 4, 1)
+        );
 
-            self.unpin_page_with_write_guard(parent_page, true);
-            return;
+        // Reading ahead evicts unpinned pages once our free frames run out,
+        // but not while paused.
+        let pager = open_pager(6);
+        let page = tree(&pager, root).search_page(None).unwrap();
+        let next_leaf = page.node.as_ref().unwrap().next_leaf_offset as usize;
+        let free_frames = pager.stats().free_frames;
+        assert!((1..4).contains(&free_frames));
+        let paused = pager.pause_read_ahead();
+        assert_eq!(pager.read_ahead(next_leaf), 0);
+        drop(paused);
+        assert_eq!(pager.read_ahead(next_leaf), 4);
+        assert_eq!(pager.stats().evictions, (4 - free_frames) as u64);
+        drop(page);
+
+        // Our scans are detected and read ahead in the background.
+        let pager = Arc::new(open_pager(32));
+        Pager::start_read_ahead(&pager);
+        let page = tree(&pager, root).search_page(None).unwrap();
+        let next_leaf = page.node.as_ref().unwrap().next_leaf_offset as usize;
+        drop(page);
+        let page = pager.fetch_read_page_guard(next_leaf).unwrap();
+        drop(page);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pager.stats().read_ahead < 4 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
         }
+        assert_eq!(pager.stats().read_ahead, 4);
+        let ids: Vec<u64> = tree(&pager, root)
+            .values()
+            .unwrap()
+            .iter()
+            .map(|row| row.id)
+            .collect();
+        assert_eq!(ids, (1..400).collect::<Vec<u64>>());
+        assert!(pager.stats().read_ahead_hits >= 4);
+        drop(pager);
 
-        // right node have less cell so let's steal from our left node.
-        if right_node.num_of_cells < min_key_length {
-            debug!("-- steal from left");
-            // Get parent key that point to the left node, since we are stealing from
-            // our left siblings, we will need the key to create the separator key in our internal
-            // cell.
-            let index = parent_node.internal_search_child_pointer(left_page_id as u32);
-            let parent_key = parent_node.internal_cells[index].key();
-
-            // Create internal cell using parent key, then steal our left siblings most right child
-            // to become our first child.
-            let internal_cell = InternalCell::new(left_node.right_child_offset, parent_key);
-            right_node.internal_cells.insert(0, internal_cell);
-            right_node.num_of_cells += 1;
-
-            // Update our new child parent offset.
-            self.update_parent_offset(
-                left_node.right_child_offset as usize,
-                right_page.page_id.unwrap(),
-            );
-            debug!("-- right_page: {:?}", right_page);
-            self.unpin_page_with_write_guard(right_page, true);
-
-            // Remove our left sibling last internal node as now it has one less child, it don't
-            // need the internal node.
-            let max_internal_cell = left_node.internal_cells.pop().unwrap();
-            left_node.num_of_cells -= 1;
-
-            // Point the removed internal cell children as the left sibling most right child.
-            left_node.right_child_offset = max_internal_cell.child_pointer();
-            debug!("-- left_page: {:?}", left_page);
-            self.unpin_page_with_write_guard(left_page, true);
-
-            // Update parent key to use the key from the removed internal cell.
-            parent_node.internal_cells[index].write_key(max_internal_cell.key());
-
-            debug!("-- parent: {:?}", parent_page);
-            debug!("-- steal sibling (end)\n\n",);
-
-            for page in parent_page_guards {
-                self.unpin_page_with_write_guard(page, false);
-            }
+        cleanup_test_db_file();
+    }
 
-            self.unpin_page_with_write_guard(parent_page, true);
-            return;
+    #[test]
+    fn pager_write_dirty_pages_in_batches() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        let rows: Vec<Row> = (1..=50)
+            .map(|i| Row::new(i, vec![Value::Text("a".repeat(100))]))
+            .collect();
+        for row in &rows {
+            assert!(tree(&pager, root).insert(row.id, row).is_ok());
         }
 
-        self.unpin_page_with_write_guard(right_page, true);
-        self.unpin_page_with_write_guard(left_page, true);
-
-        // Drop parent guards lock
-        for page in parent_page_guards {
-            self.unpin_page_with_write_guard(page, false);
+        // Our root page is used, so it's left for later.
+        let mut page = pager.fetch_write_page_guard(root).unwrap();
+        page.lsn = 0;
+        drop(page);
+        let page = pager.fetch_read_page_guard(root).unwrap();
+        loop {
+            let written = pager.write_dirty_pages(2).unwrap();
+            assert!(written <= 2);
+            if written == 0 {
+                break;
+            }
         }
+        assert!(page.is_dirty);
+        drop(page);
+        assert_eq!(pager.write_dirty_pages(2).unwrap(), 1);
+        assert_eq!(pager.dirty_page_count(), 0);
+
+        // Our rows are on disk without flushing our pager.
+        let file = format!("test-{:?}.db", std::thread::current().id());
+        let reopened = Pager::new(&file, 8);
+        assert_eq!(tree(&reopened, root).values().unwrap(), rows);
 
-        self.unpin_page_with_write_guard(parent_page, false);
+        cleanup_test_db_file();
     }
 
-    fn concurrent_do_merge_internal_nodes(
-        &self,
-        mut parent_page: RwLockWriteGuard<Page>,
-        mut left_page: RwLockWriteGuard<Page>,
-        mut right_page: RwLockWriteGuard<Page>,
-        parent_page_guards: Vec<RwLockWriteGuard<Page>>,
-    ) {
-        debug!("-- concurrent do merge internal node");
-        let right_page_id = right_page.page_id.unwrap();
-        let left_page_id = left_page.page_id.unwrap();
+    #[test]
+    fn pager_write_dirty_pages_only_once_their_logs_are_flushed() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let log = Arc::new(TestLog::default());
+        log.flush_until(4);
+        pager.attach_log(log.clone());
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+
+        let mut page = pager.fetch_write_page_guard(root).unwrap();
+        page.lsn = 5;
+        drop(page);
+        assert_eq!(pager.write_dirty_pages(8).unwrap(), 0);
+
+        log.flush_until(5);
+        assert_eq!(pager.write_dirty_pages(8).unwrap(), 1);
+        assert_eq!(pager.write_dirty_pages(8).unwrap(), 0);
 
-        let left_node = left_page.node.as_ref().unwrap();
+        cleanup_test_db_file();
+    }
 
-        let left_max_key = self.get_node_max_key(left_node.right_child_offset as usize);
-        let left_node = left_page.node.as_mut().unwrap();
-        left_node.internal_cells.push(InternalCell::new(
-            left_node.right_child_offset,
-            left_max_key,
+    #[test]
+    fn pager_report_failed_writes_and_keep_their_pages_dirty() {
+        cleanup_test_db_file();
+        let pager = Pager::new(format!("test-{:?}.db", std::thread::current().id()), 1);
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        let row = Row::new(1, vec![Value::Text("user1".to_string())]);
+        assert!(tree(&pager, root).insert(row.id, &row).is_ok());
+
+        set_failing_writes(&pager, true);
+        assert!(matches!(pager.flush_all_pages(), Err(PagerError::Io(_))));
+        assert!(matches!(pager.write_dirty_pages(8), Err(PagerError::Io(_))));
+        assert_eq!(pager.dirty_page_count(), 1);
+        // Our dirty root can't be evicted, so it stays in our buffer pool.
+        assert!(matches!(
+            BPlusTree::<u64, Row>::create(&pager),
+            Err(PagerError::Io(_))
         ));
-        left_node.num_of_cells += 1;
+        assert_eq!(tree(&pager, root).get(1).unwrap(), Some(row.clone()));
 
-        let right_node = right_page.node.take().unwrap();
-        let left_node = left_page.node.as_mut().unwrap();
+        set_failing_writes(&pager, false);
+        pager.flush_all_pages().unwrap();
+        assert_eq!(pager.dirty_page_count(), 0);
+        let reopened = Pager::new(format!("test-{:?}.db", std::thread::current().id()), 1);
+        assert_eq!(tree(&reopened, root).get(1).unwrap(), Some(row));
 
-        for c in right_node.internal_cells {
-            left_node.internal_cells.push(c);
-            left_node.num_of_cells += 1;
-        }
-        left_node.right_child_offset = right_node.right_child_offset;
+        cleanup_test_db_file();
+    }
 
-        let new_left_max_key = self.get_node_max_key(left_node.right_child_offset as usize);
+    #[test]
+    fn pager_flush_log_before_evicting_a_dirty_page() {
+        cleanup_test_db_file();
+        let pager = Pager::new(format!("test-{:?}.db", std::thread::current().id()), 1);
+        let log = Arc::new(TestLog::default());
+        pager.attach_log(log.clone());
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+
+        let mut page = pager.fetch_write_page_guard(root).unwrap();
+        page.lsn = 5;
+        drop(page);
+        assert_eq!(log.flushed_lsn(), 0);
+
+        // Our catalog root page takes the only frame of our root page.
+        drop(
+            pager
+                .fetch_read_page_guard(pager.catalog_root_page_id())
+                .unwrap(),
+        );
+        assert_eq!(log.flushed_lsn(), 5);
 
-        // Update parent metadata
-        let parent = parent_page.node.as_ref().unwrap();
+        cleanup_test_db_file();
+    }
 
-        if parent.num_of_cells == 1 && parent.is_root {
-            assert!(parent_page_guards.is_empty());
-            self.concurrent_promote_node_to_root(parent_page, left_page, right_page);
-        } else {
-            let parent = parent_page.node.as_mut().unwrap();
-            let parent_right_child_offset = parent.right_child_offset as usize;
-            let index = parent.internal_search_child_pointer(left_page_id as u32);
+    #[test]
+    fn pager_split_and_merge_leaf_nodes_by_free_space() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+
+        // 7 rows of ~500 bytes fill up a leaf, far below LEAF_NODE_MAX_CELLS.
+        for i in 1..=8 {
+            let row = Row::new(i, vec![Value::Text("a".repeat(500))]);
+            assert!(tree(&pager, root).insert(row.id, &row).is_ok());
+        }
+        assert_eq!(
+            tree(&pager, root).to_tree_string(),
+            "- internal (size 1)\n  - leaf (size 4)\n    - 1\n    - 2\n    - 3\n    - 4\n  - key 4\n  - leaf (size 4)\n    - 5\n    - 6\n    - 7\n    - 8\n"
+        );
 
-            parent.internal_cells.remove(index);
-            parent.num_of_cells -= 1;
+        tree(&pager, root).delete(1).unwrap();
+        assert_eq!(
+            tree(&pager, root).to_tree_string(),
+            "- leaf (size 7)\n  - 2\n  - 3\n  - 4\n  - 5\n  - 6\n  - 7\n  - 8\n"
+        );
 
-            if right_page_id == parent_right_child_offset {
-                debug!("  update parent after merging most right child");
-                parent.right_child_offset = left_page_id as u32;
-            } else {
-                debug!("  update parent after merging child");
-                parent.internal_cells[index].write_child_pointer(left_page_id as u32);
-                parent.internal_cells[index].write_key(new_left_max_key as u32);
-            }
+        cleanup_test_db_file();
+    }
 
-            self.delete_page_with_write_guard(right_page);
+    #[test]
+    fn pager_flush_pages_after_merging_nodes() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
 
-            self.concurrent_update_children_parent_offset(&mut left_page);
-            debug!("-- left_page: {left_page:?}");
-            self.unpin_page_with_write_guard(left_page, true);
+        for i in 1..=20 {
+            let row = Row::new(i, vec![Value::Text("a".repeat(500))]);
+            assert!(tree(&pager, root).insert(row.id, &row).is_ok());
+        }
 
-            debug!("-- parent_page: {parent_page:?}");
-            debug!("-- concurrent do merge internal node (end)\n\n");
-            self.concurrent_merge_internal_nodes(parent_page, parent_page_guards);
+        // Merging leaf nodes free the frames of the deleted pages, which
+        // are not necessarily at the end of our buffer pool.
+        for i in 1..=8 {
+            tree(&pager, root).delete(i).unwrap();
         }
-    }
+        let rows = tree(&pager, root).values().unwrap();
+        assert_eq!(rows.len(), 12);
+        pager.flush_all_pages().unwrap();
 
-    pub fn get_node_max_key(&self, mut page_id: usize) -> u32 {
-        loop {
-            let page = self.fetch_write_page_guard_with_retry(page_id);
-            let node = page.node.as_ref().unwrap();
+        let pager = setup_test_pager();
+        assert_eq!(tree(&pager, root).values().unwrap(), rows);
 
-            if node.node_type == NodeType::Leaf {
-                let new_left_max_key = node.get_max_key();
-                self.unpin_page_with_write_guard(page, false);
-                return new_left_max_key;
-            } else {
-                page_id = node.right_child_offset as usize;
-                self.unpin_page_with_write_guard(page, false);
-            }
-        }
+        cleanup_test_db_file();
     }
 
-    pub fn debug_pages(&self) -> String {
-        use std::fmt::Write;
-        let mut result = String::new();
-        for i in 0..self.next_page_id.load(Ordering::Relaxed) {
-            let bytes = self.disk_manager.read_page(i).unwrap();
-            writeln!(&mut result, "--- Page {} ---", i).unwrap();
-            writeln!(&mut result, "{:?}", Node::new_from_bytes(&bytes)).unwrap();
+    #[test]
+    fn pager_reuse_free_pages() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        for i in 1..=20 {
+            let row = Row::new(i, vec![Value::Text("a".repeat(500))]);
+            assert!(tree(&pager, root).insert(row.id, &row).is_ok());
         }
-        result
-    }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::table::Table;
-    use std::str::FromStr;
+        tree(&pager, root).destroy().unwrap();
+        let num_of_free_pages = pager.free_page_ids.lock().len();
+        assert!(num_of_free_pages > 1);
+
+        pager.flush_all_pages().unwrap();
+        let header = pager.header();
+        assert_ne!(header.freelist_head, 0);
+        assert_eq!(header.freelist_count as usize, num_of_free_pages);
+
+        // New pages are taken from the freelist before growing the file.
+        let pager = setup_test_pager();
+        assert_eq!(pager.free_page_ids.lock().len(), num_of_free_pages);
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        assert!(root < header.page_count as usize);
+        for i in 1..=20 {
+            let row = Row::new(i, vec![Value::Text("a".repeat(500))]);
+            assert!(tree(&pager, root).insert(row.id, &row).is_ok());
+        }
+
+        pager.flush_all_pages().unwrap();
+        assert_eq!(pager.header().page_count, header.page_count);
+        assert_eq!(pager.header().freelist_count, 0);
+
+        cleanup_test_db_file();
+    }
 
     #[test]
-    fn lru_replacer_evict_least_recently_accessed_page() {
-        let replacer = LRUReplacer::new(4);
+    fn pager_persist_freelist_with_multiple_trunk_pages() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let free_page_ids: Vec<usize> =
+            (2..2 * freelist_trunk_page_max_leaves(DEFAULT_PAGE_SIZE) + 10).collect();
+        pager
+            .next_page_id
+            .store(free_page_ids.len() + 2, Ordering::Release);
+        *pager.free_page_ids.lock() = free_page_ids.clone();
+        pager.flush_header().unwrap();
 
-        // We have 3 candidates that can be choose to
-        // be evicted by our buffer pool.
-        replacer.unpin(2);
-        sleep(5);
-        replacer.unpin(0);
-        sleep(5);
-        replacer.unpin(1);
+        let pager = setup_test_pager();
+        assert_eq!(*pager.free_page_ids.lock(), free_page_ids);
 
-        let evicted_page = replacer.victim().unwrap();
-        assert_eq!(evicted_page.frame_id, 2);
+        cleanup_test_db_file();
     }
 
     #[test]
-    fn lru_replacer_do_not_evict_pin_page() {
-        let replacer = LRUReplacer::new(4);
+    fn pager_report_corrupted_pages() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        let overflow_root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        for i in 1..=3 {
+            let row = Row::new(i, vec![Value::Text(format!("user{i}"))]);
+            assert!(tree(&pager, root).insert(row.id, &row).is_ok());
+        }
+        let large_row = Row::new(1, vec![Value::Text("a".repeat(2 * DEFAULT_PAGE_SIZE))]);
+        assert!(tree(&pager, overflow_root)
+            .insert(large_row.id, &large_row)
+            .is_ok());
+        pager.flush_all_pages().unwrap();
+        let page_count = pager.header().page_count as usize;
+
+        // Flip a bit in the body of the leaf and of an overflow page without
+        // updating their checksums, as a torn write or bit rot would.
+        let disk_manager = pager.disk_manager.read();
+        let mut bytes = disk_manager.read_page(root).unwrap();
+        bytes[PAGE_HEADER_BYTES + 20] ^= 1;
+        disk_manager.write_page(root, &bytes).unwrap();
+        let overflow_page_id = (overflow_root + 1..page_count)
+            .find(|&id| {
+                disk_manager.read_page(id).unwrap()[PAGE_HEADER_BYTES] == OVERFLOW_PAGE_TYPE
+            })
+            .unwrap();
+        let mut bytes = disk_manager.read_page(overflow_page_id).unwrap();
+        bytes[DEFAULT_PAGE_SIZE - 1] ^= 1;
+        disk_manager.write_page(overflow_page_id, &bytes).unwrap();
+
+        let pager = setup_test_pager();
+        assert!(matches!(
+            pager.fetch_read_page_guard(root),
+            Err(PagerError::CorruptedPage(id)) if id == root
+        ));
+        // The frame is given back, so the page is reported again.
+        assert!(matches!(
+            pager.fetch_write_page_guard(root),
+            Err(PagerError::CorruptedPage(id)) if id == root
+        ));
+        assert!(matches!(
+            tree(&pager, root).values(),
+            Err(PagerError::CorruptedPage(id)) if id == root
+        ));
+        assert!(matches!(
+            tree(&pager, root).get(1),
+            Err(PagerError::CorruptedPage(id)) if id == root
+        ));
+        let row = Row::new(4, vec![Value::Text("user4".to_string())]);
+        assert_eq!(
+            tree(&pager, root).insert(row.id, &row),
+            Err(format!("database page {root} is corrupted"))
+        );
 
-        // We have 3 candidates that can be choose to
-        // be evicted by our buffer pool.
-        replacer.unpin(2);
-        sleep(5);
-        replacer.unpin(0);
-        sleep(5);
-        replacer.unpin(1);
-        replacer.pin(2);
+        assert!(matches!(
+            tree(&pager, overflow_root).values(),
+            Err(PagerError::CorruptedPage(id)) if id == overflow_page_id
+        ));
 
-        let evicted_page = replacer.victim().unwrap();
-        assert_eq!(evicted_page.frame_id, 0);
+        cleanup_test_db_file();
     }
 
-    use std::sync::Arc;
-    use std::thread;
     #[test]
-    // I'm not really sure how to further verify
-    // the behaviour when it being accessed concurrently.
-    //
-    // At least now it's "thread safe".
-    fn lru_replacer_works_concurrently() {
-        let replacer = Arc::new(LRUReplacer::new(4));
+    fn pager_report_pages_torn_by_a_truncated_file() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+        let row = Row::new(1, vec![Value::Text("user1".to_string())]);
+        assert!(tree(&pager, root).insert(row.id, &row).is_ok());
+        pager.flush_all_pages().unwrap();
+        assert_eq!(pager.header().page_count as usize, root + 1);
+        drop(pager);
+
+        // Cut our file in the middle of our root page.
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(format!("test-{:?}.db", std::thread::current().id()))
+            .unwrap();
+        file.set_len((root * DEFAULT_PAGE_SIZE + DEFAULT_PAGE_SIZE / 2) as u64)
+            .unwrap();
+
+        let pager = setup_test_pager();
+        assert!(matches!(
+            pager.fetch_read_page_guard(root),
+            Err(PagerError::CorruptedPage(id)) if id == root
+        ));
+        // The frame is given back, so the page is reported again.
+        assert!(matches!(
+            pager.fetch_write_page_guard(root),
+            Err(PagerError::CorruptedPage(id)) if id == root
+        ));
+        assert_eq!(pager.stats().free_frames, pager.pool_size());
+
+        cleanup_test_db_file();
+    }
 
-        let re = replacer.clone();
-        let handle = thread::spawn(move || re.unpin(2));
+    #[test]
+    fn pager_store_large_rows_in_overflow_pages() {
+        cleanup_test_db_file();
+        let pager = setup_test_pager();
+        let root = BPlusTree::<u64, Row>::create(&pager)
+            .unwrap()
+            .root_page_id();
+
+        let large_row = Row::new(1, vec![Value::Blob(vec![7; 3 * DEFAULT_PAGE_SIZE])]);
+        let small_row = Row::new(
+            2,
+            vec![Value::Blob(vec![
+                7;
+                pager.layout.max_record_size(
+                    std::mem::size_of::<u64>()
+                ) - 7
+            ])],
+        );
+        assert!(tree(&pager, root).insert(large_row.id, &large_row).is_ok());
+        assert!(tree(&pager, root).insert(small_row.id, &small_row).is_ok());
+        assert_eq!(
+            tree(&pager, root).values().unwrap(),
+            vec![large_row.clone(), small_row.clone()]
+        );
 
-        let re = replacer.clone();
-        let handle2 = thread::spawn(move || re.unpin(3));
+        // The overflow pages are written together with the leaf node and
+        // read back when the leaf node is read from disk.
+        pager.flush_all_pages().unwrap();
+        let page_count = pager.header().page_count;
+        let pager = setup_test_pager();
+        assert_eq!(
+            tree(&pager, root).values().unwrap(),
+            vec![large_row, small_row.clone()]
+        );
 
-        handle.join().unwrap();
-        handle2.join().unwrap();
+        // The overflow pages of updated and deleted rows are reused.
+        let mut page = pager.fetch_write_page_guard(root).unwrap();
+        let new_row = Row::new(2, vec![Value::Text("a".repeat(2 * DEFAULT_PAGE_SIZE))]);
+        assert!(page.update_row(1, &new_row, &[0]));
+        drop(page);
+        tree(&pager, root).delete(1).unwrap();
+        pager.flush_all_pages().unwrap();
+        assert_eq!(pager.header().page_count, page_count);
 
-        replacer.pin(2);
+        let pager = setup_test_pager();
+        assert_eq!(tree(&pager, root).values().unwrap(), vec![new_row]);
 
-        let evicted_page = replacer.victim().unwrap();
-        assert_eq!(evicted_page.frame_id, 3);
+        cleanup_test_db_file();
     }
 
     #[test]
@@ -1818,8 +2980,8 @@ mod test {
 
     //     let row = pager.get_record(&cursor);
     //     assert_eq!(row.id, 1);
-    //     assert_eq!(row.username(), "user1");
-    //     assert_eq!(row.email(), "user1@email.com");
+    //     assert_eq!(row.values[0].to_string(), "user1");
+    //     assert_eq!(row.values[1].to_string(), "user1@email.com");
     //     let page = pager.fetch_page(cursor.page_num).unwrap();
     //     let page = page.read();
     //     assert_eq!(page.pin_count, 1);
@@ -1834,8 +2996,8 @@ mod test {
 
     //     let row = pager.get_record(&cursor);
     //     assert_eq!(row.id, 9);
-    //     assert_eq!(row.username(), "user9");
-    //     assert_eq!(row.email(), "user9@email.com");
+    //     assert_eq!(row.values[0].to_string(), "user9");
+    //     assert_eq!(row.values[1].to_string(), "user9@email.com");
     //     let page = pager.fetch_page(cursor.page_num).unwrap();
     //     let page = page.read();
     //     assert_eq!(page.pin_count, 1);
@@ -1843,8 +3005,78 @@ mod test {
     //     cleanup_test_db_file();
     // }
 
+    #[derive(Debug, Default)]
+    struct TestLog {
+        flushed_lsn: AtomicU32,
+    }
+
+    impl WriteAheadLog for TestLog {
+        fn flushed_lsn(&self) -> u32 {
+            self.flushed_lsn.load(Ordering::Acquire)
+        }
+
+        fn flush_until(&self, lsn: u32) {
+            self.flushed_lsn.fetch_max(lsn, Ordering::AcqRel);
+        }
+    }
+
+    // Our storage, failing every writes and syncs as a full disk would.
+    #[derive(Debug)]
+    struct FailingStorage(Box<dyn Storage>);
+
+    impl Storage for FailingStorage {
+        fn file_len(&self) -> usize {
+            self.0.file_len()
+        }
+
+        fn page_size(&self) -> usize {
+            self.0.page_size()
+        }
+
+        fn set_page_size(&mut self, page_size: usize) {
+            self.0.set_page_size(page_size);
+        }
+
+        fn read_at(&self, offset: usize, len: usize) -> Result<Vec<u8>, std::io::Error> {
+            self.0.read_at(offset, len)
+        }
+
+        fn write_page(&self, _page_id: usize, _page_bytes: &[u8]) -> Result<(), std::io::Error> {
+            Err(std::io::Error::other("no space left on device"))
+        }
+
+        fn sync(&self, _synchronous: SynchronousMode) -> Result<(), std::io::Error> {
+            Err(std::io::Error::other("no space left on device"))
+        }
+    }
+
+    fn set_failing_writes(pager: &Pager, failing: bool) {
+        let mut storage = pager.storage.open(&pager.path).unwrap();
+        storage.set_page_size(pager.page_size());
+        *pager.disk_manager.write() = if failing {
+            Box::new(FailingStorage(storage))
+        } else {
+            storage
+        };
+    }
+
+    fn tree(pager: &Pager, root_page_id: usize) -> BPlusTree<'_, u64, Row> {
+        BPlusTree::new(pager, root_page_id)
+    }
+
     fn setup_test_table() -> Table {
-        Table::new(format!("test-{:?}.db", std::thread::current().id()), 8)
+        Table::new(
+            format!("test-{:?}.db", std::thread::current().id()),
+            8,
+            SynchronousMode::default(),
+        )
+    }
+
+    fn options_with_page_size(page_size: usize) -> PagerOptions {
+        PagerOptions {
+            page_size,
+            ..PagerOptions::default()
+        }
     }
 
     fn setup_test_pager() -> Pager {
@@ -1859,15 +3091,10 @@ mod test {
             table.insert(&row);
         }
 
-        table.flush();
+        table.flush().unwrap();
     }
 
     fn cleanup_test_db_file() {
         let _ = std::fs::remove_file(format!("test-{:?}.db", std::thread::current().id()));
     }
-
-    fn sleep(duration_in_ms: u64) {
-        let ten_millis = std::time::Duration::from_millis(duration_in_ms);
-        std::thread::sleep(ten_millis);
-    }
 }
@@ -0,0 +1,803 @@
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+// The K of our LRU-K replacer, unless another K is chosen.
+pub const DEFAULT_LRU_K: usize = 2;
+
+/// Decide which frame of our buffer pool is evicted when we need a frame
+/// for a page that isn't in memory.
+///
+/// A frame is pinned whenever its page is fetched, which is also how the
+/// accesses to a page are recorded, and unpinned once its page isn't used
+/// by any thread anymore. Only unpinned frames can be evicted.
+pub trait Replacer: std::fmt::Debug + Send + Sync {
+    /// Choose a frame to evict among the unpinned frames and forget
+    /// about it, as it's going to hold another page.
+    fn victim(&self) -> Option<usize>;
+
+    /// Record an access to the frame and prevent it from being evicted.
+    fn pin(&self, frame_id: usize);
+
+    /// Allow the frame to be evicted.
+    fn unpin(&self, frame_id: usize);
+
+    /// Allow the frame to be evicted before the frames unpinned by
+    /// Replacer::unpin, as its page is only read by sequential scans.
+    ///
+    /// LRU-K and LFU already evict the pages accessed once first.
+    fn unpin_cold(&self, frame_id: usize) {
+        self.unpin(frame_id);
+    }
+
+    /// Forget about the frame, as its page is discarded from our buffer pool.
+    fn remove(&self, frame_id: usize);
+
+    /// Tell our replacer which page the frame holds from now on, which
+    /// should be called before the frame is pinned.
+    ///
+    /// Only needed by the policies remembering the accesses of a page
+    /// after it's evicted.
+    fn set_page(&self, _frame_id: usize, _page_id: usize) {}
+
+    /// Track the given number of frames from now on, see Pager::resize.
+    ///
+    /// When our buffer pool shrinks, it's only called once the frames
+    /// beyond the new size are removed.
+    fn resize(&self, pool_size: usize);
+}
+
+// TRADEOFF: Clock is our default policy.
+//
+// Clock approximates LRU without sorting our frames on every eviction.
+// Like LRU, it would evict every other pages during a single scan through
+// our leaf nodes, including our root node which is the most frequently
+// accessed page, if the leaf pages read by scans weren't unpinned as cold.
+//
+// LRU-K and LFU keep our root and upper internal nodes in memory during
+// scans without any hint, but they are slower for inserts: a node created by a split is only
+// accessed once when it's evicted by LRU-K, while LFU keeps the leaf nodes
+// that used to be written a lot. Hence, they are only used when chosen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacementPolicy {
+    Lru,
+    #[default]
+    Clock,
+    LruK(usize),
+    Lfu,
+}
+
+impl ReplacementPolicy {
+    pub fn replacer(self, pool_size: usize) -> Box<dyn Replacer> {
+        match self {
+            ReplacementPolicy::Lru => Box::new(LRUReplacer::new(pool_size)),
+            ReplacementPolicy::Clock => Box::new(ClockReplacer::new(pool_size)),
+            ReplacementPolicy::LruK(k) => Box::new(LRUKReplacer::new(k, pool_size)),
+            ReplacementPolicy::Lfu => Box::new(LFUReplacer::new(pool_size)),
+        }
+    }
+}
+
+impl FromStr for ReplacementPolicy {
+    type Err = String;
+
+    /// Parse one of lru, clock, lfu, lru-k or lru-<k>, e.g. lru-3.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lru" => Ok(ReplacementPolicy::Lru),
+            "clock" => Ok(ReplacementPolicy::Clock),
+            "lfu" => Ok(ReplacementPolicy::Lfu),
+            "lru-k" => Ok(ReplacementPolicy::LruK(DEFAULT_LRU_K)),
+            policy => policy
+                .strip_prefix("lru-")
+                .and_then(|k| k.parse().ok())
+                .filter(|&k| k > 0)
+                .map(ReplacementPolicy::LruK)
+                .ok_or_else(|| format!("unknown replacement policy {s}")),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PageMetadata {
+    frame_id: usize,
+    last_accessed_at: Instant,
+    is_cold: bool,
+}
+
+impl PageMetadata {
+    pub fn new(frame_id: usize) -> Self {
+        Self {
+            frame_id,
+            last_accessed_at: Instant::now(),
+            is_cold: false,
+        }
+    }
+}
+
+// TRADEOFF: We are using the most naive replacement policies.
+//
+// We are replacing pages by considering the recency of a page instead
+// of frequency of access, and we sort our frames on every eviction.
+//
+// It's kept as the simplest policy to compare the others against.
+#[derive(Debug)]
+pub struct LRUReplacer {
+    // We are using Vec instead of HashMap as the size
+    // of the Vec is limited. Hence, a linear search
+    // would not caused much performance problem as well?
+    //
+    // And it's a bit easier to deal with Vec than
+    // HashMap for the time being.
+    page_table: RwLock<Vec<PageMetadata>>,
+}
+
+impl LRUReplacer {
+    pub fn new(pool_size: usize) -> Self {
+        Self {
+            page_table: RwLock::new(Vec::with_capacity(pool_size)),
+        }
+    }
+}
+
+impl Replacer for LRUReplacer {
+    /// Return the frame that is accessed least recently
+    /// as compared to the other frame, starting with the cold frames.
+    fn victim(&self) -> Option<usize> {
+        let mut page_table = self.page_table.write();
+        page_table.sort_by(|a, b| {
+            a.is_cold
+                .cmp(&b.is_cold)
+                .then(b.last_accessed_at.cmp(&a.last_accessed_at))
+        });
+        page_table.pop().map(|md| md.frame_id)
+    }
+
+    /// This should be called after our Pager place the page into
+    /// our memory. Here, pin a frame means removing it from our
+    /// replacer. I guess this prevent it from the page being
+    /// evicted
+    fn pin(&self, frame_id: usize) {
+        let mut page_table = self.page_table.write();
+        if let Some(index) = page_table.iter().position(|md| md.frame_id == frame_id) {
+            page_table.remove(index);
+        }
+    }
+
+    /// This should be called by our Pager when the page pin_count
+    /// becomes 0. Here, unpin a frame means adding it to our
+    /// replacer. This allow the page to be evicted.
+    fn unpin(&self, frame_id: usize) {
+        let mut page_table = self.page_table.write();
+        page_table.push(PageMetadata::new(frame_id));
+    }
+
+    fn unpin_cold(&self, frame_id: usize) {
+        let mut page_table = self.page_table.write();
+        page_table.push(PageMetadata {
+            is_cold: true,
+            ..PageMetadata::new(frame_id)
+        });
+    }
+
+    fn remove(&self, frame_id: usize) {
+        self.pin(frame_id);
+    }
+
+    fn resize(&self, _pool_size: usize) {}
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ClockFrame {
+    evictable: bool,
+    referenced: bool,
+    cold: bool,
+}
+
+#[derive(Debug)]
+struct ClockState {
+    frames: Vec<ClockFrame>,
+    hand: usize,
+}
+
+// Our frames are arranged in a circle with a clock hand pointing to one of
+// them. Every access set the reference bit of a frame, and the hand gives
+// a second chance to the referenced frames by clearing their bit as it goes
+// around, until it finds an unpinned frame that isn't referenced.
+//
+// It approximates LRU without sorting our frames on every eviction.
+//
+// The cold frames are evicted before the hand goes around, so a scan keeps
+// reusing its own frames instead of clearing the bits of every other frames.
+#[derive(Debug)]
+pub struct ClockReplacer {
+    state: Mutex<ClockState>,
+}
+
+impl ClockReplacer {
+    pub fn new(pool_size: usize) -> Self {
+        Self {
+            state: Mutex::new(ClockState {
+                frames: vec![ClockFrame::default(); pool_size],
+                hand: 0,
+            }),
+        }
+    }
+}
+
+impl Replacer for ClockReplacer {
+    fn victim(&self) -> Option<usize> {
+        let mut state = self.state.lock();
+        let num_of_frames = state.frames.len();
+
+        let cold_frame = state
+            .frames
+            .iter()
+            .position(|frame| frame.evictable && frame.cold);
+        if let Some(frame_id) = cold_frame {
+            state.frames[frame_id] = ClockFrame::default();
+            return Some(frame_id);
+        }
+
+        // Every referenced frames are cleared during the first round, so
+        // two rounds are enough to find a victim if there is one.
+        for _ in 0..2 * num_of_frames {
+            let frame_id = state.hand;
+            state.hand = (state.hand + 1) % num_of_frames;
+
+            let frame = &mut state.frames[frame_id];
+            if !frame.evictable {
+                continue;
+            }
+
+            if frame.referenced {
+                frame.referenced = false;
+            } else {
+                *frame = ClockFrame::default();
+                return Some(frame_id);
+            }
+        }
+
+        None
+    }
+
+    fn pin(&self, frame_id: usize) {
+        let mut state = self.state.lock();
+        state.frames[frame_id] = ClockFrame {
+            evictable: false,
+            referenced: true,
+            cold: false,
+        };
+    }
+
+    fn unpin(&self, frame_id: usize) {
+        let mut state = self.state.lock();
+        state.frames[frame_id].evictable = true;
+    }
+
+    fn unpin_cold(&self, frame_id: usize) {
+        let mut state = self.state.lock();
+        state.frames[frame_id] = ClockFrame {
+            evictable: true,
+            referenced: false,
+            cold: true,
+        };
+    }
+
+    fn remove(&self, frame_id: usize) {
+        let mut state = self.state.lock();
+        state.frames[frame_id] = ClockFrame::default();
+    }
+
+    fn resize(&self, pool_size: usize) {
+        let mut state = self.state.lock();
+        state.frames.resize(pool_size, ClockFrame::default());
+        state.hand %= pool_size;
+    }
+}
+
+// The accesses of the pages that are no longer in our buffer pool are
+// remembered, so a page evicted too early isn't evicted again as soon as
+// it's read back. Otherwise, a page has to be accessed again while it's
+// still in memory to ever be considered hot.
+//
+// We only remember as many evicted pages as our buffer pool has frames,
+// forgetting the least recently accessed pages first.
+//
+// A page is only accessed while its frame is pinned, so the priority of an
+// evictable frame never changes, and our frames and evicted pages are kept
+// ordered instead of being searched on every eviction.
+#[derive(Debug)]
+struct PageAccesses<T, P> {
+    // The accesses of every pages we remember, resident or not.
+    pages: HashMap<usize, T>,
+    // Mapping frame id to the page id it holds.
+    frames: HashMap<usize, usize>,
+    // Our evictable frames by the priority they were unpinned with.
+    evictable: BTreeSet<(P, usize)>,
+    priorities: HashMap<usize, P>,
+    // Our pages that are no longer resident by their last access.
+    evicted: BTreeSet<(u64, usize)>,
+    current_timestamp: u64,
+}
+
+/// The accesses of a page, see PageAccesses.
+trait PageHistory: Default {
+    /// The timestamp of the most recent access.
+    fn last_accessed_at(&self) -> u64;
+}
+
+impl<T, P> Default for PageAccesses<T, P> {
+    fn default() -> Self {
+        Self {
+            pages: HashMap::new(),
+            frames: HashMap::new(),
+            evictable: BTreeSet::new(),
+            priorities: HashMap::new(),
+            evicted: BTreeSet::new(),
+            current_timestamp: 0,
+        }
+    }
+}
+
+impl<T: PageHistory, P: Ord + Copy> PageAccesses<T, P> {
+    fn page_of(&mut self, frame_id: usize) -> &mut T {
+        let page_id = self.frames[&frame_id];
+        self.pages.entry(page_id).or_default()
+    }
+
+    fn next_timestamp(&mut self) -> u64 {
+        self.current_timestamp += 1;
+        self.current_timestamp
+    }
+
+    fn set_page(&mut self, frame_id: usize, page_id: usize) {
+        if let Some(old_page_id) = self.frames.insert(frame_id, page_id) {
+            self.forget_resident_page(old_page_id);
+        }
+
+        // The page is read back, so it's resident again.
+        if let Some(accesses) = self.pages.get(&page_id) {
+            self.evicted.remove(&(accesses.last_accessed_at(), page_id));
+        }
+    }
+
+    fn pin(&mut self, frame_id: usize) {
+        if let Some(priority) = self.priorities.remove(&frame_id) {
+            self.evictable.remove(&(priority, frame_id));
+        }
+    }
+
+    fn unpin(&mut self, frame_id: usize, priority: impl Fn(&T) -> P) {
+        if !self.frames.contains_key(&frame_id) || self.priorities.contains_key(&frame_id) {
+            return;
+        }
+
+        let priority = priority(self.page_of(frame_id));
+        self.evictable.insert((priority, frame_id));
+        self.priorities.insert(frame_id, priority);
+    }
+
+    fn remove(&mut self, frame_id: usize) {
+        self.pin(frame_id);
+        if let Some(page_id) = self.frames.remove(&frame_id) {
+            self.pages.remove(&page_id);
+        }
+    }
+
+    /// Remember the accesses of a page that is no longer resident, unless
+    /// it was never accessed.
+    fn forget_resident_page(&mut self, page_id: usize) {
+        if let Some(accesses) = self.pages.get(&page_id) {
+            self.evicted.insert((accesses.last_accessed_at(), page_id));
+        }
+    }
+
+    fn evict(&mut self, max_evicted_pages: usize) -> Option<usize> {
+        let (priority, frame_id) = self.evictable.pop_first()?;
+        debug_assert!(self.priorities.get(&frame_id) == Some(&priority));
+        self.priorities.remove(&frame_id);
+        let page_id = self.frames.remove(&frame_id).unwrap();
+        self.forget_resident_page(page_id);
+
+        while self.evicted.len() > max_evicted_pages {
+            let (_, page_id) = self.evicted.pop_first().unwrap();
+            self.pages.remove(&page_id);
+        }
+
+        Some(frame_id)
+    }
+}
+
+impl PageHistory for VecDeque<u64> {
+    fn last_accessed_at(&self) -> u64 {
+        self.back().copied().unwrap_or_default()
+    }
+}
+
+impl PageHistory for (u64, u64) {
+    fn last_accessed_at(&self) -> u64 {
+        self.1
+    }
+}
+
+// Whether a frame was accessed K times, followed by the oldest of its last K
+// accesses, so the frames with an infinite distance are evicted first.
+type KDistance = (bool, Option<u64>);
+
+// Evict the frame whose K-th most recent access is the oldest, that is, the
+// frame with the largest backward K-distance. Frames accessed less than K
+// times have an infinite distance and are evicted first, by least recent
+// first access.
+//
+// Our root node and upper internal nodes are accessed by every search, while
+// the leaf nodes of a scan are only accessed once, so a scan can't evict them.
+#[derive(Debug)]
+pub struct LRUKReplacer {
+    k: usize,
+    max_evicted_pages: AtomicUsize,
+    // The timestamps of the last K accesses of every pages, from the
+    // oldest to the most recent one.
+    state: Mutex<PageAccesses<VecDeque<u64>, KDistance>>,
+}
+
+impl LRUKReplacer {
+    pub fn new(k: usize, pool_size: usize) -> Self {
+        assert!(k > 0, "k must be at least 1");
+
+        Self {
+            k,
+            max_evicted_pages: AtomicUsize::new(pool_size),
+            state: Mutex::new(PageAccesses::default()),
+        }
+    }
+}
+
+impl Replacer for LRUKReplacer {
+    fn victim(&self) -> Option<usize> {
+        self.state
+            .lock()
+            .evict(self.max_evicted_pages.load(Ordering::Relaxed))
+    }
+
+    fn pin(&self, frame_id: usize) {
+        let mut state = self.state.lock();
+        let timestamp = state.next_timestamp();
+
+        let history = state.page_of(frame_id);
+        history.push_back(timestamp);
+        if history.len() > self.k {
+            history.pop_front();
+        }
+
+        state.pin(frame_id);
+    }
+
+    fn unpin(&self, frame_id: usize) {
+        self.state.lock().unpin(frame_id, |history| {
+            (history.len() >= self.k, history.front().copied())
+        });
+    }
+
+    fn remove(&self, frame_id: usize) {
+        self.state.lock().remove(frame_id);
+    }
+
+    fn set_page(&self, frame_id: usize, page_id: usize) {
+        self.state.lock().set_page(frame_id, page_id);
+    }
+
+    fn resize(&self, pool_size: usize) {
+        self.max_evicted_pages.store(pool_size, Ordering::Relaxed);
+    }
+}
+
+// TRADEOFF: Our access counts never decay.
+//
+// A page that was hot a long time ago stays in memory until every other
+// pages are accessed as many times, which is the usual weakness of LFU.
+//
+// Ties are broken by evicting the least recently accessed frame.
+#[derive(Debug)]
+pub struct LFUReplacer {
+    max_evicted_pages: AtomicUsize,
+    // The number of accesses and the timestamp of the last access of
+    // every pages.
+    state: Mutex<PageAccesses<(u64, u64), (u64, u64)>>,
+}
+
+impl LFUReplacer {
+    pub fn new(pool_size: usize) -> Self {
+        Self {
+            max_evicted_pages: AtomicUsize::new(pool_size),
+            state: Mutex::new(PageAccesses::default()),
+        }
+    }
+}
+
+impl Replacer for LFUReplacer {
+    fn victim(&self) -> Option<usize> {
+        self.state
+            .lock()
+            .evict(self.max_evicted_pages.load(Ordering::Relaxed))
+    }
+
+    fn pin(&self, frame_id: usize) {
+        let mut state = self.state.lock();
+        let timestamp = state.next_timestamp();
+
+        let (count, last_accessed_at) = state.page_of(frame_id);
+        *count += 1;
+        *last_accessed_at = timestamp;
+
+        state.pin(frame_id);
+    }
+
+    fn unpin(&self, frame_id: usize) {
+        self.state.lock().unpin(frame_id, |&frequency| frequency);
+    }
+
+    fn remove(&self, frame_id: usize) {
+        self.state.lock().remove(frame_id);
+    }
+
+    fn set_page(&self, frame_id: usize, page_id: usize) {
+        self.state.lock().set_page(frame_id, page_id);
+    }
+
+    fn resize(&self, pool_size: usize) {
+        self.max_evicted_pages.store(pool_size, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn lru_replacer_evict_least_recently_accessed_page() {
+        let replacer = LRUReplacer::new(4);
+
+        // We have 3 candidates that can be choose to
+        // be evicted by our buffer pool.
+        replacer.unpin(2);
+        sleep(5);
+        replacer.unpin(0);
+        sleep(5);
+        replacer.unpin(1);
+
+        let evicted_page = replacer.victim().unwrap();
+        assert_eq!(evicted_page, 2);
+    }
+
+    #[test]
+    fn lru_replacer_do_not_evict_pin_page() {
+        let replacer = LRUReplacer::new(4);
+
+        // We have 3 candidates that can be choose to
+        // be evicted by our buffer pool.
+        replacer.unpin(2);
+        sleep(5);
+        replacer.unpin(0);
+        sleep(5);
+        replacer.unpin(1);
+        replacer.pin(2);
+
+        let evicted_page = replacer.victim().unwrap();
+        assert_eq!(evicted_page, 0);
+    }
+
+    #[test]
+    fn lru_replacer_evict_cold_page_first() {
+        let replacer = LRUReplacer::new(4);
+
+        replacer.unpin(2);
+        sleep(5);
+        replacer.unpin_cold(0);
+        sleep(5);
+        replacer.unpin_cold(1);
+
+        assert_eq!(replacer.victim(), Some(0));
+        assert_eq!(replacer.victim(), Some(1));
+        assert_eq!(replacer.victim(), Some(2));
+    }
+
+    #[test]
+    // I'm not really sure how to further verify
+    // the behaviour when it being accessed concurrently.
+    //
+    // At least now it's "thread safe".
+    fn lru_replacer_works_concurrently() {
+        let replacer = Arc::new(LRUReplacer::new(4));
+
+        let re = replacer.clone();
+        let handle = thread::spawn(move || re.unpin(2));
+
+        let re = replacer.clone();
+        let handle2 = thread::spawn(move || re.unpin(3));
+
+        handle.join().unwrap();
+        handle2.join().unwrap();
+
+        replacer.pin(2);
+
+        let evicted_page = replacer.victim().unwrap();
+        assert_eq!(evicted_page, 3);
+    }
+
+    #[test]
+    fn clock_replacer_give_referenced_frames_a_second_chance() {
+        let replacer = ClockReplacer::new(4);
+        for frame_id in 0..4 {
+            replacer.pin(frame_id);
+            replacer.unpin(frame_id);
+        }
+
+        // Every frames are referenced, so the hand clears them all and
+        // comes back to the first one.
+        assert_eq!(replacer.victim(), Some(0));
+
+        // Frame 1 is accessed again before the hand comes back to it.
+        replacer.pin(1);
+        replacer.unpin(1);
+        assert_eq!(replacer.victim(), Some(2));
+
+        // Pinned frames are skipped.
+        replacer.pin(3);
+        assert_eq!(replacer.victim(), Some(1));
+        assert_eq!(replacer.victim(), None);
+
+        replacer.unpin(3);
+        assert_eq!(replacer.victim(), Some(3));
+    }
+
+    #[test]
+    fn clock_replacer_evict_cold_frames_first() {
+        let replacer = ClockReplacer::new(4);
+        for frame_id in 0..4 {
+            replacer.pin(frame_id);
+        }
+        replacer.unpin(0);
+        replacer.unpin_cold(2);
+        replacer.unpin_cold(3);
+
+        assert_eq!(replacer.victim(), Some(2));
+        assert_eq!(replacer.victim(), Some(3));
+        assert_eq!(replacer.victim(), Some(0));
+
+        // A cold frame accessed again is no longer cold.
+        replacer.pin(1);
+        replacer.unpin_cold(1);
+        replacer.pin(1);
+        replacer.unpin(1);
+        replacer.pin(2);
+        replacer.unpin_cold(2);
+        assert_eq!(replacer.victim(), Some(2));
+        assert_eq!(replacer.victim(), Some(1));
+    }
+
+    #[test]
+    fn clock_replacer_resize() {
+        let replacer = ClockReplacer::new(2);
+        replacer.resize(4);
+        for frame_id in 2..4 {
+            replacer.pin(frame_id);
+            replacer.unpin(frame_id);
+        }
+        assert_eq!(replacer.victim(), Some(2));
+
+        // The hand is beyond our remaining frames.
+        replacer.resize(2);
+        replacer.pin(1);
+        replacer.unpin(1);
+        assert_eq!(replacer.victim(), Some(1));
+        assert_eq!(replacer.victim(), None);
+    }
+
+    #[test]
+    fn lru_k_replacer_evict_frames_accessed_less_than_k_times_first() {
+        let replacer = LRUKReplacer::new(2, 4);
+        for frame_id in 0..4 {
+            replacer.set_page(frame_id, frame_id);
+        }
+
+        // Frame 0 is accessed twice, before frames 1 and 2 are accessed once.
+        access(&replacer, 0);
+        access(&replacer, 0);
+        access(&replacer, 1);
+        access(&replacer, 2);
+
+        assert_eq!(replacer.victim(), Some(1));
+        assert_eq!(replacer.victim(), Some(2));
+
+        // Between frames with K accesses, the one whose K-th most recent
+        // access is the oldest is evicted, even if it's the most recently
+        // accessed frame.
+        access(&replacer, 3);
+        access(&replacer, 3);
+        access(&replacer, 0);
+        assert_eq!(replacer.victim(), Some(0));
+
+        // The accesses of page 0 are remembered when it's read back.
+        replacer.set_page(1, 0);
+        access(&replacer, 1);
+        assert_eq!(replacer.victim(), Some(3));
+
+        replacer.set_page(2, 5);
+        access(&replacer, 2);
+        assert_eq!(replacer.victim(), Some(2));
+
+        replacer.pin(1);
+        assert_eq!(replacer.victim(), None);
+        replacer.remove(1);
+        replacer.unpin(1);
+        assert_eq!(replacer.victim(), None);
+    }
+
+    #[test]
+    fn lru_k_replacer_forget_least_recently_accessed_evicted_pages() {
+        let replacer = LRUKReplacer::new(2, 2);
+        for page_id in 0..10 {
+            replacer.set_page(0, page_id);
+            access(&replacer, 0);
+            assert_eq!(replacer.victim(), Some(0));
+        }
+
+        // Only the last two pages evicted are remembered.
+        let state = replacer.state.lock();
+        let mut page_ids: Vec<usize> = state.pages.keys().copied().collect();
+        page_ids.sort_unstable();
+        assert_eq!(page_ids, vec![8, 9]);
+        assert_eq!(state.evicted.len(), 2);
+    }
+
+    #[test]
+    fn lfu_replacer_evict_least_frequently_accessed_frame() {
+        let replacer = LFUReplacer::new(4);
+        for (frame_id, num_of_accesses) in [(0, 3), (1, 1), (2, 2), (3, 1)] {
+            replacer.set_page(frame_id, frame_id);
+            for _ in 0..num_of_accesses {
+                access(&replacer, frame_id);
+            }
+        }
+
+        // Frames 1 and 3 are accessed once, frame 1 less recently.
+        assert_eq!(replacer.victim(), Some(1));
+        assert_eq!(replacer.victim(), Some(3));
+
+        replacer.pin(2);
+        assert_eq!(replacer.victim(), Some(0));
+        assert_eq!(replacer.victim(), None);
+
+        // The count of page 1 is remembered when it's read back.
+        replacer.set_page(3, 1);
+        access(&replacer, 3);
+        replacer.unpin(2);
+        assert_eq!(replacer.victim(), Some(3));
+        assert_eq!(replacer.victim(), Some(2));
+    }
+
+    #[test]
+    fn parse_replacement_policy() {
+        assert_eq!("lru".parse(), Ok(ReplacementPolicy::Lru));
+        assert_eq!("Clock".parse(), Ok(ReplacementPolicy::Clock));
+        assert_eq!("lfu".parse(), Ok(ReplacementPolicy::Lfu));
+        assert_eq!("lru-k".parse(), Ok(ReplacementPolicy::LruK(DEFAULT_LRU_K)));
+        assert_eq!("lru-3".parse(), Ok(ReplacementPolicy::LruK(3)));
+        assert!("lru-0".parse::<ReplacementPolicy>().is_err());
+        assert!("mru".parse::<ReplacementPolicy>().is_err());
+    }
+
+    fn access(replacer: &dyn Replacer, frame_id: usize) {
+        replacer.pin(frame_id);
+        replacer.unpin(frame_id);
+    }
+
+    fn sleep(duration_in_ms: u64) {
+        thread::sleep(std::time::Duration::from_millis(duration_in_ms));
+    }
+}
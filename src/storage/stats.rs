@@ -0,0 +1,107 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// TRADEOFF: Our counters are relaxed atomics updated on the hot paths of our
+// Pager, while the residency per page type is sampled when our statistics are
+// taken.
+//
+// Counting with relaxed atomics means a snapshot might be slightly off while
+// other threads are running, e.g. a hit counted before the matching miss of
+// another thread, which is good enough to tune our pool_size.
+//
+// The type of a resident page changes in place (e.g. a leaf root becomes an
+// internal node when it's split), so keeping a counter per page type in sync
+// would mean updating it everywhere a node is replaced. Instead, we briefly
+// latch every frame to look at its page when our statistics are taken.
+
+/// The counters of our Pager, see Pager::stats.
+#[derive(Debug, Default)]
+pub(super) struct PagerCounters {
+    pub hits: AtomicU64,
+    pub misses: AtomicU64,
+    pub evictions: AtomicU64,
+    pub dirty_writes: AtomicU64,
+    pub pin_waits: AtomicU64,
+    pub retries: AtomicU64,
+    pub read_ahead: AtomicU64,
+    pub read_ahead_hits: AtomicU64,
+}
+
+impl PagerCounters {
+    pub fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> PagerStats {
+        PagerStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            dirty_writes: self.dirty_writes.load(Ordering::Relaxed),
+            pin_waits: self.pin_waits.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            read_ahead: self.read_ahead.load(Ordering::Relaxed),
+            read_ahead_hits: self.read_ahead_hits.load(Ordering::Relaxed),
+            ..PagerStats::default()
+        }
+    }
+}
+
+/// A snapshot of the statistics of our buffer pool since our Pager is opened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PagerStats {
+    // Pages fetched that were already in our buffer pool.
+    pub hits: u64,
+    // Pages fetched that had to be read from disk.
+    pub misses: u64,
+    // Pages removed from our buffer pool to make room for another page.
+    pub evictions: u64,
+    // Dirty pages written to disk, either when evicted, by our background
+    // writer or when every pages are flushed.
+    pub dirty_writes: u64,
+    // The number of times a thread blocked because every frames were pinned.
+    pub pin_waits: u64,
+    // The number of times a page was fetched again after a transient error,
+    // either because every frames were pinned or its latch was held.
+    pub retries: u64,
+    // Leaf pages read ahead of a sequential scan.
+    pub read_ahead: u64,
+    // Pages read ahead that were fetched before being evicted.
+    pub read_ahead_hits: u64,
+
+    pub pool_size: usize,
+    pub free_frames: usize,
+    pub internal_pages: usize,
+    pub leaf_pages: usize,
+}
+
+impl PagerStats {
+    /// The ratio of fetches served from our buffer pool, or None before
+    /// any page is fetched.
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let fetches = self.hits + self.misses;
+        (fetches > 0).then(|| self.hits as f64 / fetches as f64)
+    }
+}
+
+impl fmt::Display for PagerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hit_ratio = self
+            .hit_ratio()
+            .map_or_else(|| "-".to_string(), |ratio| format!("{:.1}%", ratio * 100.0));
+
+        writeln!(f, "hits: {}", self.hits)?;
+        writeln!(f, "misses: {}", self.misses)?;
+        writeln!(f, "hit ratio: {hit_ratio}")?;
+        writeln!(f, "evictions: {}", self.evictions)?;
+        writeln!(f, "dirty writes: {}", self.dirty_writes)?;
+        writeln!(f, "pin waits: {}", self.pin_waits)?;
+        writeln!(f, "retries: {}", self.retries)?;
+        writeln!(f, "read ahead: {}", self.read_ahead)?;
+        writeln!(f, "read ahead hits: {}", self.read_ahead_hits)?;
+        writeln!(f, "pool size: {}", self.pool_size)?;
+        writeln!(f, "free frames: {}", self.free_frames)?;
+        writeln!(f, "internal pages: {}", self.internal_pages)?;
+        write!(f, "leaf pages: {}", self.leaf_pages)
+    }
+}
@@ -0,0 +1,334 @@
+use io_uring::{cqueue, opcode, squeue, types, IoUring};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{Error, ErrorKind},
+    os::unix::io::AsRawFd,
+    path::Path,
+    sync::{mpsc, Arc},
+    thread::{self, JoinHandle},
+};
+use tracing::error;
+
+use super::disk_manager::{Storage, SynchronousMode};
+use super::pager::DEFAULT_PAGE_SIZE;
+
+// TRADEOFF: Similar to the DiskScheduler of BusTub, our page reads and writes
+// are queued to a single scheduler thread, which submits every requests
+// queued in the meantime to io_uring at once.
+//
+// The requests of different threads are in flight at the same time, so the
+// misses of our buffer pool no longer wait for each other, and a batch of
+// pages is written in parallel, see Storage::write_pages. However, every
+// request is handed over to another thread, and a request queued while our
+// scheduler waits for the kernel is only submitted once one of the requests
+// in flight completes.
+//
+// If our ring can't be submitted to anymore, every request fails from then
+// on instead of bringing our scheduler down, see IoUringStorage::fail.
+const QUEUE_DEPTH: usize = 64;
+
+#[derive(Debug)]
+enum IoOperation {
+    Read,
+    Write,
+}
+
+#[derive(Debug)]
+struct IoRequest {
+    operation: IoOperation,
+    offset: usize,
+    // Our page when writing, or the buffer our page is read into.
+    buffer: Vec<u8>,
+    done: mpsc::Sender<Result<Vec<u8>, Error>>,
+}
+
+/// A database file read and written asynchronously through io_uring, see
+/// StorageBackend.
+#[derive(Debug)]
+pub struct IoUringStorage {
+    file: Arc<File>,
+    // Only None once we're dropped.
+    requests: Option<mpsc::Sender<IoRequest>>,
+    scheduler: Option<JoinHandle<()>>,
+    // The length of our file when it was opened.
+    file_len: usize,
+    page_size: usize,
+}
+
+impl IoUringStorage {
+    /// Set up the ring of a new storage, which fails if io_uring is
+    /// unavailable, see StorageBackend::open.
+    pub fn setup_ring() -> Result<IoUring, Error> {
+        IoUring::new(QUEUE_DEPTH as u32)
+    }
+
+    pub fn open(path: impl AsRef<Path>, ring: IoUring) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let file_len = file.metadata()?.len() as usize;
+        let file = Arc::new(file);
+
+        let (requests, queued) = mpsc::channel();
+        let scheduler_file = file.clone();
+        let scheduler = thread::Builder::new()
+            .name("disk-scheduler".to_string())
+            .spawn(move || Self::schedule(ring, &scheduler_file, queued))?;
+
+        Ok(Self {
+            file,
+            requests: Some(requests),
+            scheduler: Some(scheduler),
+            file_len,
+            page_size: DEFAULT_PAGE_SIZE,
+        })
+    }
+
+    /// Submit our requests until every senders are dropped and the requests
+    /// in flight are completed.
+    fn schedule(mut ring: IoUring, file: &File, queued: mpsc::Receiver<IoRequest>) {
+        let fd = types::Fd(file.as_raw_fd());
+        let mut in_flight: HashMap<u64, IoRequest> = HashMap::new();
+        let mut next_request_id = 0;
+
+        loop {
+            // Only block on our queue when the kernel has nothing to complete.
+            if in_flight.is_empty() {
+                let Ok(request) = queued.recv() else {
+                    break;
+                };
+                Self::submit(&mut ring, fd, &mut in_flight, next_request_id, request);
+                next_request_id += 1;
+            }
+            while in_flight.len() < QUEUE_DEPTH {
+                let Ok(request) = queued.try_recv() else {
+                    break;
+                };
+                Self::submit(&mut ring, fd, &mut in_flight, next_request_id, request);
+                next_request_id += 1;
+            }
+
+            match ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                // Our completion queue is full, so it's reaped before trying again.
+                Err(err)
+                    if matches!(err.kind(), ErrorKind::ResourceBusy | ErrorKind::WouldBlock) => {}
+                Err(err) => {
+                    error!("fail to submit disk requests: {err}");
+                    Self::fail(ring, in_flight, queued, err);
+                    return;
+                }
+            }
+            for completion in ring.completion() {
+                let request = in_flight.remove(&completion.user_data()).unwrap();
+                Self::complete(request, &completion);
+            }
+        }
+    }
+
+    /// Fail our requests in flight and every request queued from now on,
+    /// until every senders are dropped.
+    ///
+    /// The kernel might still read or write the buffers of our requests in
+    /// flight, so they're only dropped once their completion is reaped, and
+    /// leaked if it never is.
+    fn fail(
+        mut ring: IoUring,
+        in_flight: HashMap<u64, IoRequest>,
+        queued: mpsc::Receiver<IoRequest>,
+        err: Error,
+    ) {
+        let failure = || Error::new(err.kind(), format!("disk scheduler failed: {err}"));
+        let mut buffers: HashMap<u64, Vec<u8>> = in_flight
+            .into_iter()
+            .map(|(request_id, request)| {
+                let _ = request.done.send(Err(failure()));
+                (request_id, request.buffer)
+            })
+            .collect();
+
+        for request in queued.iter() {
+            let _ = request.done.send(Err(failure()));
+            for completion in ring.completion() {
+                buffers.remove(&completion.user_data());
+            }
+        }
+        for completion in ring.completion() {
+            buffers.remove(&completion.user_data());
+        }
+
+        buffers.into_values().for_each(std::mem::forget);
+    }
+
+    fn submit(
+        ring: &mut IoUring,
+        fd: types::Fd,
+        in_flight: &mut HashMap<u64, IoRequest>,
+        request_id: u64,
+        mut request: IoRequest,
+    ) {
+        let len = request.buffer.len() as u32;
+        let offset = request.offset as u64;
+        let entry: squeue::Entry = match request.operation {
+            IoOperation::Read => opcode::Read::new(fd, request.buffer.as_mut_ptr(), len)
+                .offset(offset)
+                .build(),
+            IoOperation::Write => opcode::Write::new(fd, request.buffer.as_ptr(), len)
+                .offset(offset)
+                .build(),
+        };
+
+        // SAFETY: The buffer of our request lives in `in_flight` until its
+        // completion is reaped, and moving a Vec doesn't move its buffer.
+        // Our submission queue is never full, since we stop queuing requests
+        // once QUEUE_DEPTH requests are in flight.
+        unsafe {
+            ring.submission()
+                .push(&entry.user_data(request_id))
+                .expect("submission queue is full");
+        }
+        in_flight.insert(request_id, request);
+    }
+
+    fn complete(request: IoRequest, completion: &cqueue::Entry) {
+        let result = match completion.result() {
+            errno if errno < 0 => Err(Error::from_raw_os_error(-errno)),
+            len if (len as usize) < request.buffer.len() => Err(match request.operation {
+                IoOperation::Read => {
+                    Error::new(ErrorKind::UnexpectedEof, "failed to fill whole buffer")
+                }
+                IoOperation::Write => {
+                    Error::new(ErrorKind::WriteZero, "failed to write whole buffer")
+                }
+            }),
+            _ => Ok(request.buffer),
+        };
+
+        // The thread waiting for our request might be gone, e.g. if it panicked.
+        let _ = request.done.send(result);
+    }
+
+    /// Queue a request, and return where its result is sent to once it's
+    /// completed.
+    fn queue(
+        &self,
+        operation: IoOperation,
+        offset: usize,
+        buffer: Vec<u8>,
+    ) -> Result<mpsc::Receiver<Result<Vec<u8>, Error>>, Error> {
+        let (done, completed) = mpsc::channel();
+        let request = IoRequest {
+            operation,
+            offset,
+            buffer,
+            done,
+        };
+        self.requests
+            .as_ref()
+            .unwrap()
+            .send(request)
+            .map_err(|_| Error::other("disk scheduler stopped"))?;
+        Ok(completed)
+    }
+
+    fn wait(completed: mpsc::Receiver<Result<Vec<u8>, Error>>) -> Result<Vec<u8>, Error> {
+        completed
+            .recv()
+            .unwrap_or_else(|_| Err(Error::other("disk scheduler stopped")))
+    }
+}
+
+impl Storage for IoUringStorage {
+    fn file_len(&self) -> usize {
+        self.file_len
+    }
+
+    fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn set_page_size(&mut self, page_size: usize) {
+        self.page_size = page_size;
+    }
+
+    fn read_at(&self, offset: usize, len: usize) -> Result<Vec<u8>, Error> {
+        Self::wait(self.queue(IoOperation::Read, offset, vec![0; len])?)
+    }
+
+    fn write_page(&self, page_id: usize, page_bytes: &[u8]) -> Result<(), Error> {
+        self.write_pages(vec![(page_id, page_bytes.to_vec())])
+    }
+
+    fn write_pages(&self, pages: Vec<(usize, Vec<u8>)>) -> Result<(), Error> {
+        let completions: Vec<_> = pages
+            .into_iter()
+            .map(|(page_id, page_bytes)| {
+                debug_assert_eq!(page_bytes.len(), self.page_size);
+                self.queue(IoOperation::Write, page_id * self.page_size, page_bytes)
+            })
+            .collect();
+
+        // Every pages are waited for, even after an error, as they are
+        // still written.
+        let mut result = Ok(());
+        for completed in completions {
+            result = result.and(completed.and_then(Self::wait).map(|_| ()));
+        }
+        result
+    }
+
+    fn sync(&self, synchronous: SynchronousMode) -> Result<(), Error> {
+        // Our writes are completed before being returned, so they are all
+        // synced.
+        synchronous.sync_file(&self.file)
+    }
+}
+
+impl Drop for IoUringStorage {
+    fn drop(&mut self) {
+        // Our scheduler stops once our queue is closed and its requests in
+        // flight are completed.
+        self.requests.take();
+        if let Some(scheduler) = self.scheduler.take() {
+            let _ = scheduler.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fail_requests_once_our_ring_is_unusable() {
+        // io_uring might be unavailable, e.g. inside containers.
+        let Ok(ring) = IoUringStorage::setup_ring() else {
+            return;
+        };
+        let (requests, queued) = mpsc::channel();
+        let scheduler = thread::spawn(move || {
+            let err = Error::from(ErrorKind::InvalidInput);
+            IoUringStorage::fail(ring, HashMap::new(), queued, err)
+        });
+
+        for _ in 0..2 {
+            let (done, completed) = mpsc::channel();
+            let request = IoRequest {
+                operation: IoOperation::Read,
+                offset: 0,
+                buffer: vec![0; DEFAULT_PAGE_SIZE],
+                done,
+            };
+            requests.send(request).unwrap();
+            assert!(IoUringStorage::wait(completed).is_err());
+        }
+
+        drop(requests);
+        scheduler.join().unwrap();
+    }
+}
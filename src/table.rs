@@ -1,53 +1,111 @@
+use crate::catalog::{Catalog, CatalogEntry, TableTree};
+use crate::database::{default_table_schema, Database, DEFAULT_TABLE_NAME};
 use crate::query::Statement;
 use crate::row::Row;
-use crate::storage::Pager;
+use crate::schema::Schema;
+use crate::storage::{PagerError, PagerOptions, SynchronousMode};
 use std::path::Path;
+use std::sync::Arc;
 
 pub struct Table {
-    root_page_num: usize,
-    pager: Pager,
+    // Our root is looked up by name on every operation, as it's moved by
+    // our vacuum, see Catalog::table_tree.
+    name: String,
+    catalog: Arc<Catalog>,
+    schema: Schema,
 }
 
 impl Table {
-    pub fn new(path: impl AsRef<Path>, pool_size: usize) -> Table {
-        let pager = Pager::new(path, pool_size);
+    /// Open the default table of the database at the given path,
+    /// creating it if it doesn't exist yet, see SynchronousMode.
+    pub fn new(path: impl AsRef<Path>, pool_size: usize, synchronous: SynchronousMode) -> Table {
+        let options = PagerOptions {
+            synchronous,
+            ..PagerOptions::default()
+        };
+        Database::with_options(path, pool_size, options)
+            .table_or_create(DEFAULT_TABLE_NAME, &default_table_schema())
+            .unwrap()
+    }
+
+    pub fn from_catalog(catalog: Arc<Catalog>, entry: CatalogEntry) -> Table {
         Table {
-            root_page_num: 0,
-            pager,
+            name: entry.name,
+            catalog,
+            schema: entry.schema,
         }
     }
 
-    pub fn flush(&self) {
-        self.pager.flush_all_pages();
+    fn tree(&self) -> Result<TableTree<'_>, String> {
+        self.catalog
+            .table_tree(&self.name)
+            .ok_or_else(|| format!("no such table: {}", self.name))
+    }
+
+    pub fn flush(&self) -> Result<(), PagerError> {
+        self.catalog.flush()
     }
 
     pub fn select(&self, statement: &Statement) -> String {
-        let page_num = self.root_page_num;
+        let tree = match self.tree() {
+            Ok(tree) => tree,
+            Err(err) => return format!("{err}\n"),
+        };
+
         if let Some(row) = &statement.row {
-            self.pager.find(page_num, None, row.id)
+            match tree.get(row.id) {
+                Ok(Some(row)) => format!("{}\n", row.to_string()),
+                Ok(None) => "".to_string(),
+                Err(err) => format!("{err}\n"),
+            }
         } else {
-            self.pager.select(page_num)
+            match tree.values() {
+                Ok(rows) => rows
+                    .iter()
+                    .map(|row| format!("{}\n", row.to_string()))
+                    .collect(),
+                Err(err) => format!("{err}\n"),
+            }
         }
     }
 
     pub fn insert(&self, row: &Row) -> String {
-        let page_num = self.root_page_num;
-        self.pager.insert(page_num, row).unwrap()
+        let row = match self.schema.coerce(row) {
+            Ok(row) => row,
+            Err(reason) => return reason,
+        };
+
+        let tree = match self.tree() {
+            Ok(tree) => tree,
+            Err(err) => return format!("{err}\n"),
+        };
+
+        match tree.insert(row.id, &row) {
+            Ok((page_num, cell_num)) => {
+                format!("inserting into page: {page_num}, cell: {cell_num}...\n")
+            }
+            Err(err) => format!("{err}\n"),
+        }
     }
 
     pub fn delete(&self, row: &Row) -> String {
-        let page_num = self.root_page_num;
-        self.pager.delete(page_num, row).unwrap()
-    }
-
-    pub fn pages(&self) -> String {
-        self.pager.debug_pages()
+        let tree = match self.tree() {
+            Ok(tree) => tree,
+            Err(err) => return err,
+        };
+
+        match tree.delete(row.id) {
+            Ok(true) => format!("deleted {}", row.id),
+            Ok(false) => format!("item not found with id {}", row.id),
+            Err(err) => err.to_string(),
+        }
     }
 }
 
 impl std::string::ToString for Table {
     fn to_string(&self) -> String {
-        self.pager.to_tree_string()
+        self.tree()
+            .map_or_else(|err| err, |tree| tree.to_tree_string())
     }
 }
 
@@ -74,6 +132,34 @@ mod test {
         cleanup_test_db_file();
     }
 
+    #[test]
+    fn insert_and_select_ids_above_u32_max() {
+        cleanup_test_db_file();
+        let table = setup_test_table(8);
+        let ids = [
+            u32::MAX as u64 - 1,
+            u32::MAX as u64,
+            u32::MAX as u64 + 1,
+            u64::MAX,
+        ];
+        for i in ids.iter().rev() {
+            let query = format!("insert {i} user{i} user{i}@email.com");
+            let statement = prepare_statement(&query).unwrap();
+            table.insert(&statement.row.unwrap());
+        }
+        table.flush().unwrap();
+
+        let table = setup_test_table(8);
+        for i in ids {
+            let statement = prepare_statement(&format!("select {i}")).unwrap();
+            assert_eq!(table.select(&statement), expected_output(i..=i));
+        }
+        let statement = prepare_statement("select").unwrap();
+        assert_eq!(table.select(&statement), expected_output(ids));
+
+        cleanup_test_db_file();
+    }
+
     #[test]
     fn insert_row_into_leaf_root_node_with_new_buffer_pool_impl() {
         insertion_test(10);
@@ -171,7 +257,7 @@ mod test {
         let result = table.select(&statement);
         assert_eq!(result, expected_output);
 
-        table.flush();
+        table.flush().unwrap();
 
         // Testing select after we flush all pages
         //
@@ -198,7 +284,7 @@ mod test {
         let result = table.select(&statement);
         assert_eq!(result, expected_output);
 
-        table.flush();
+        table.flush().unwrap();
 
         // Testing select after we flush all pages
         //
@@ -845,6 +931,7 @@ mod test {
         return Table::new(
             format!("test-{:?}.db", std::thread::current().id()),
             pool_size,
+            SynchronousMode::default(),
         );
     }
 
@@ -856,7 +943,7 @@ mod test {
             table.insert(&row);
         }
 
-        table.flush();
+        table.flush().unwrap();
     }
 
     fn cleanup_test_db_file() {